@@ -0,0 +1,23 @@
+use voxelland::capturedump::dump_capture_to_stdout;
+
+
+fn main() {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        println!("Usage: capture_dump <capture file>...");
+        println!("Decodes jeffy_quintet capture files recorded by the server or a client into readable message lines.");
+        return;
+    }
+
+    for path in paths {
+        println!("== {} ==", path);
+        match dump_capture_to_stdout(&path) {
+            Ok(count) => println!("== {} records ==", count),
+            Err(err) => {
+                println!("Failed to read capture {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use voxelland::landiscovery::{discover_servers, DISCOVERY_PORT, DISCOVERY_TIMEOUT};
+
+
+fn main() {
+    // Optional timeout in milliseconds, to wait longer on slow networks
+    let timeout = match std::env::args().nth(1) {
+        Some(millis) => match millis.parse::<u64>() {
+            Ok(millis) => Duration::from_millis(millis),
+            Err(_) => {
+                println!("Usage: lan_discover [timeout in milliseconds]");
+                return;
+            }
+        },
+        None => DISCOVERY_TIMEOUT,
+    };
+
+    println!("Looking for VoxelLand servers on the LAN (discovery port {})...", DISCOVERY_PORT);
+
+    match discover_servers(DISCOVERY_PORT, timeout) {
+        Ok(servers) => {
+            if servers.is_empty() {
+                println!("No server answered.");
+            }
+            for server in servers {
+                let announcement = &server.announcement;
+                println!(
+                    "{} - {} - {} player(s), seed {}, planet type {}",
+                    server.address, announcement.name, announcement.players, announcement.seed, announcement.planet_type
+                );
+            }
+        }
+        Err(e) => {
+            println!("LAN discovery failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
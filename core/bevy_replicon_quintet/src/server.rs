@@ -65,7 +65,11 @@ impl RepliconQuintetServerPlugin {
         for event in conn_lost_events.read() {
             server_events.send(ServerEvent::ClientDisconnected {
                 client_id: ClientId::new(event.id),
-                reason: "".to_string(),
+                reason: event
+                    .reason
+                    .as_ref()
+                    .map(|reason| reason.message.clone())
+                    .unwrap_or_default(),
             });
         }
     }
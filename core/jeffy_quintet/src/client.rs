@@ -16,7 +16,7 @@ use tokio::{
 use crate::shared::{
     channels::{ChannelAsyncMessage, ChannelsConfiguration},
     error::QuintetError,
-    AsyncRuntime, ClientId, InternalConnectionRef, QuintetSyncUpdate,
+    AsyncRuntime, ClientId, DisconnectReason, InternalConnectionRef, QuintetSyncUpdate,
 };
 
 use self::{
@@ -25,9 +25,10 @@ use self::{
         CertVerificationStatus, CertVerifierAction, CertificateVerificationMode,
     },
     connection::{
-        async_connection_task, create_async_channels, ClientEndpointConfiguration, Connection,
-        ConnectionEvent, ConnectionFailedEvent, ConnectionLocalId, ConnectionLostEvent,
-        ConnectionState, InternalConnectionState,
+        async_connection_task, create_async_channels, BlobProgressEvent, BlobReceivedEvent,
+        ClientEndpointConfiguration, Connection, ConnectionEvent, ConnectionFailedEvent,
+        ConnectionLocalId, ConnectionLostEvent, ConnectionState, InternalConnectionState,
+        ReconnectAttemptEvent, ReconnectPoll,
     },
 };
 
@@ -35,6 +36,8 @@ use self::{
 pub mod certificate;
 /// Module for a client's connection to a server
 pub mod connection;
+/// Module for a client driven without Bevy
+pub mod handle;
 
 /// Default path for the known hosts file
 pub const DEFAULT_KNOWN_HOSTS_FILE: &str = "Quintet/known_hosts";
@@ -51,13 +54,19 @@ pub enum QuintetConnectionError {
     /// Client did not receive its client id
     #[error("Client did not receive its client id")]
     ClientIdNotReceived,
+    /// The join handshake with the server could not be completed
+    #[error("The join handshake with the server failed")]
+    JoinHandshakeFailed,
+    /// The server refused the client
+    #[error("Connection refused by the server: {0}")]
+    Rejected(String),
 }
 
 #[derive(Debug)]
 pub(crate) enum ClientAsyncMessage {
     Connected(InternalConnectionRef, Option<ClientId>),
     ConnectionFailed(QuintetConnectionError),
-    ConnectionClosed, // TODO Might set a ConnectionError
+    ConnectionClosed(Option<DisconnectReason>),
     CertificateInteractionRequest {
         status: CertVerificationStatus,
         info: CertVerificationInfo,
@@ -122,6 +131,14 @@ impl QuintetClient {
         }
     }
 
+    /// Returns true if the default connection exists and is automatically reconnecting.
+    pub fn is_reconnecting(&self) -> bool {
+        match self.get_connection() {
+            Some(connection) => connection.state() == ConnectionState::Reconnecting,
+            None => false,
+        }
+    }
+
     /// Returns true if the default connection does not exists or is disconnected.
     pub fn is_disconnected(&self) -> bool {
         match self.get_connection() {
@@ -204,7 +221,7 @@ impl QuintetClient {
             to_channels_recv,
             close_send,
             close_recv,
-        ) = create_async_channels();
+        ) = create_async_channels(endpoint_config.transport_config());
 
         let mut connection = Connection::new(
             local_id,
@@ -219,6 +236,7 @@ impl QuintetClient {
             from_channels_recv,
         );
         connection.open_configured_channels(channels_config)?;
+        let recv_counters = connection.recv_counters.clone();
 
         self.connections.insert(local_id, connection);
         if self.default_connection_id.is_none() {
@@ -233,6 +251,7 @@ impl QuintetClient {
                 cert_mode,
                 to_sync_client_send,
                 bytes_from_server_send,
+                recv_counters,
                 to_channels_recv,
                 from_channels_send,
                 close_recv,
@@ -267,7 +286,7 @@ impl QuintetClient {
                 if Some(connection_id) == self.default_connection_id {
                     self.default_connection_id = None;
                 }
-                connection.disconnect()
+                connection.disconnect(DisconnectReason::default())
             }
             None => Err(QuintetError::UnknownConnection(connection_id)),
         }
@@ -287,41 +306,79 @@ impl QuintetClient {
     }
 }
 
-/// Receive messages from the async client tasks and update the sync client.
-///
-/// This system generates the client's bevy events
-pub fn update_sync_client(
-    mut connection_events: EventWriter<ConnectionEvent>,
-    mut connection_failed_events: EventWriter<ConnectionFailedEvent>,
-    mut connection_lost_events: EventWriter<ConnectionLostEvent>,
-    mut certificate_interaction_events: EventWriter<CertInteractionEvent>,
-    mut cert_trust_update_events: EventWriter<CertTrustUpdateEvent>,
-    mut cert_connection_abort_events: EventWriter<CertConnectionAbortEvent>,
-    mut client: ResMut<QuintetClient>,
+/// Event raised by a client, as returned by [`handle::ClientHandle::poll`]. The Bevy plugin sends each variant as its own Bevy event
+pub enum ClientEvent {
+    /// A connection just connected to its server
+    Connection(ConnectionEvent),
+    /// A connection failed to connect to its server
+    ConnectionFailed(ConnectionFailedEvent),
+    /// A connection lost its connection to its server
+    ConnectionLost(ConnectionLostEvent),
+    /// A connection is trying to reconnect to its server
+    ReconnectAttempt(ReconnectAttemptEvent),
+    /// The server's certificate needs an action from the user
+    CertInteraction(CertInteractionEvent),
+    /// The trust of the server's certificate was updated
+    CertTrustUpdate(CertTrustUpdateEvent),
+    /// A connection was aborted because of the server's certificate
+    CertConnectionAbort(CertConnectionAbortEvent),
+    /// A blob sent by the server is being received
+    BlobProgress(BlobProgressEvent),
+    /// A blob sent by the server was fully received
+    BlobReceived(BlobReceivedEvent),
+}
+
+/// Receive messages from the async client tasks and update the sync connections, passing each raised event to `on_event`
+pub(crate) fn update_connections(
+    client: &mut QuintetClient,
+    mut on_event: impl FnMut(ClientEvent),
 ) {
-    for (connection_id, connection) in &mut client.connections {
+    for (connection_id, connection) in client.connections.iter_mut() {
         while let Ok(message) = connection.from_async_client_recv.try_recv() {
             match message {
                 ClientAsyncMessage::Connected(internal_connection, client_id) => {
                     connection.state =
                         InternalConnectionState::Connected(internal_connection, client_id);
-                    connection_events.send(ConnectionEvent {
+                    connection.reset_reconnection();
+                    on_event(ClientEvent::Connection(ConnectionEvent {
                         id: *connection_id,
                         client_id,
-                    });
-                }
-                ClientAsyncMessage::ConnectionFailed(err) => {
-                    connection.state = InternalConnectionState::Disconnected;
-                    connection_failed_events.send(ConnectionFailedEvent {
-                        id: *connection_id,
-                        err,
-                    });
+                    }));
                 }
-                ClientAsyncMessage::ConnectionClosed => match connection.state {
-                    InternalConnectionState::Disconnected => (),
+                ClientAsyncMessage::ConnectionFailed(err) => match connection.state {
+                    InternalConnectionState::Reconnecting => {
+                        info!(
+                            "Reconnection attempt of connection {} failed: {}",
+                            connection_id, err
+                        );
+                        // A refusal from the server will not change with more attempts
+                        let rejected = matches!(err, QuintetConnectionError::Rejected(_));
+                        if rejected || !connection.schedule_next_reconnect() {
+                            let reason = connection.stop_reconnecting();
+                            on_event(ClientEvent::ConnectionLost(ConnectionLostEvent {
+                                id: *connection_id,
+                                reason,
+                            }));
+                        }
+                    }
                     _ => {
-                        connection.try_disconnect();
-                        connection_lost_events.send(ConnectionLostEvent { id: *connection_id });
+                        connection.state = InternalConnectionState::Disconnected;
+                        on_event(ClientEvent::ConnectionFailed(ConnectionFailedEvent {
+                            id: *connection_id,
+                            err,
+                        }));
+                    }
+                },
+                ClientAsyncMessage::ConnectionClosed(reason) => match connection.state {
+                    InternalConnectionState::Disconnected | InternalConnectionState::Reconnecting => (),
+                    _ => {
+                        if !connection.start_reconnecting(reason.clone()) {
+                            connection.try_disconnect();
+                            on_event(ClientEvent::ConnectionLost(ConnectionLostEvent {
+                                id: *connection_id,
+                                reason,
+                            }));
+                        }
                     }
                 },
                 ClientAsyncMessage::CertificateInteractionRequest {
@@ -329,42 +386,131 @@ pub fn update_sync_client(
                     info,
                     action_sender,
                 } => {
-                    certificate_interaction_events.send(CertInteractionEvent {
+                    on_event(ClientEvent::CertInteraction(CertInteractionEvent {
                         connection_id: *connection_id,
                         status,
                         info,
                         action_sender: Mutex::new(Some(action_sender)),
-                    });
+                    }));
                 }
                 ClientAsyncMessage::CertificateTrustUpdate(info) => {
-                    cert_trust_update_events.send(CertTrustUpdateEvent {
+                    on_event(ClientEvent::CertTrustUpdate(CertTrustUpdateEvent {
                         connection_id: *connection_id,
                         cert_info: info,
-                    });
+                    }));
                 }
                 ClientAsyncMessage::CertificateConnectionAbort { status, cert_info } => {
-                    cert_connection_abort_events.send(CertConnectionAbortEvent {
+                    on_event(ClientEvent::CertConnectionAbort(CertConnectionAbortEvent {
                         connection_id: *connection_id,
                         status,
                         cert_info,
-                    });
+                    }));
                 }
             }
         }
         while let Ok(message) = connection.from_channels_recv.try_recv() {
             match message {
-                ChannelAsyncMessage::LostConnection => match connection.state {
-                    InternalConnectionState::Disconnected => (),
-                    _ => {
-                        connection.try_disconnect();
-                        connection_lost_events.send(ConnectionLostEvent { id: *connection_id });
-                    }
-                },
+                ChannelAsyncMessage::LostConnection => {
+                    // The connection closed task follows up with the reason from the close frame, if any, and raises the event then
+                    trace!("Channels of connection {} lost the connection", connection_id);
+                }
+                ChannelAsyncMessage::BlobProgress {
+                    blob_id,
+                    tag,
+                    received,
+                    total,
+                } => {
+                    on_event(ClientEvent::BlobProgress(BlobProgressEvent {
+                        id: *connection_id,
+                        blob_id,
+                        tag,
+                        received,
+                        total,
+                    }));
+                }
+                ChannelAsyncMessage::BlobReceived {
+                    blob_id,
+                    tag,
+                    payload,
+                } => {
+                    on_event(ClientEvent::BlobReceived(BlobReceivedEvent {
+                        id: *connection_id,
+                        blob_id,
+                        tag,
+                        payload,
+                    }));
+                }
             }
         }
+        let (attempt, gave_up) = match connection.poll_reconnect() {
+            ReconnectPoll::Idle => continue,
+            ReconnectPoll::Attempt(attempt) => (attempt, None),
+            ReconnectPoll::GaveUp(attempt, reason) => (attempt, Some(reason)),
+        };
+        on_event(ClientEvent::ReconnectAttempt(ReconnectAttemptEvent {
+            id: *connection_id,
+            attempt,
+            max_attempts: connection
+                .endpoint_configuration()
+                .reconnect_policy()
+                .map_or(0, |policy| policy.max_attempts),
+        }));
+        if let Some(reason) = gave_up {
+            on_event(ClientEvent::ConnectionLost(ConnectionLostEvent {
+                id: *connection_id,
+                reason,
+            }));
+        }
     }
 }
 
+/// Receive messages from the async client tasks and update the sync client.
+///
+/// This system generates the client's bevy events
+#[allow(clippy::too_many_arguments)]
+pub fn update_sync_client(
+    mut connection_events: EventWriter<ConnectionEvent>,
+    mut connection_failed_events: EventWriter<ConnectionFailedEvent>,
+    mut connection_lost_events: EventWriter<ConnectionLostEvent>,
+    mut reconnect_attempt_events: EventWriter<ReconnectAttemptEvent>,
+    mut certificate_interaction_events: EventWriter<CertInteractionEvent>,
+    mut cert_trust_update_events: EventWriter<CertTrustUpdateEvent>,
+    mut cert_connection_abort_events: EventWriter<CertConnectionAbortEvent>,
+    mut blob_progress_events: EventWriter<BlobProgressEvent>,
+    mut blob_received_events: EventWriter<BlobReceivedEvent>,
+    mut client: ResMut<QuintetClient>,
+) {
+    update_connections(&mut client, |event| match event {
+        ClientEvent::Connection(event) => {
+            connection_events.send(event);
+        }
+        ClientEvent::ConnectionFailed(event) => {
+            connection_failed_events.send(event);
+        }
+        ClientEvent::ConnectionLost(event) => {
+            connection_lost_events.send(event);
+        }
+        ClientEvent::ReconnectAttempt(event) => {
+            reconnect_attempt_events.send(event);
+        }
+        ClientEvent::CertInteraction(event) => {
+            certificate_interaction_events.send(event);
+        }
+        ClientEvent::CertTrustUpdate(event) => {
+            cert_trust_update_events.send(event);
+        }
+        ClientEvent::CertConnectionAbort(event) => {
+            cert_connection_abort_events.send(event);
+        }
+        ClientEvent::BlobProgress(event) => {
+            blob_progress_events.send(event);
+        }
+        ClientEvent::BlobReceived(event) => {
+            blob_received_events.send(event);
+        }
+    });
+}
+
 /// Quintet Server's plugin
 ///
 /// It is possbile to add both this plugin and the [`crate::server::QuintetServerPlugin`]
@@ -388,9 +534,12 @@ impl Plugin for QuintetClientPlugin {
         app.add_event::<ConnectionEvent>()
             .add_event::<ConnectionFailedEvent>()
             .add_event::<ConnectionLostEvent>()
+            .add_event::<ReconnectAttemptEvent>()
             .add_event::<CertInteractionEvent>()
             .add_event::<CertTrustUpdateEvent>()
-            .add_event::<CertConnectionAbortEvent>();
+            .add_event::<CertConnectionAbortEvent>()
+            .add_event::<BlobProgressEvent>()
+            .add_event::<BlobReceivedEvent>();
 
         if !self.initialize_later {
             app.init_resource::<QuintetClient>();
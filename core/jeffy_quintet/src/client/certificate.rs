@@ -81,6 +81,9 @@ pub enum CertificateVerificationMode {
     SignedByCertificateAuthority,
     /// The client will use a Trust on first authentication scheme (<https://en.wikipedia.org/wiki/Trust_on_first_use>) configured by a [`TrustOnFirstUseConfig`].
     TrustOnFirstUse(TrustOnFirstUseConfig),
+    /// The client will only trust a server certificate whose fingerprint is one of the given fingerprints, for example from a server list publishing them.
+    /// Any other certificate aborts the connection and raises a [`CertConnectionAbortEvent`] with the [`CertVerificationStatus::UntrustedCertificate`] status.
+    PinnedFingerprints(Vec<CertificateFingerprint>),
 }
 
 /// Configuration of the Trust on first authentication scheme for server certificates
@@ -220,6 +223,60 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
     }
 }
 
+/// Implementation of `ServerCertVerifier` that only trusts certificates with a pinned fingerprint.
+pub(crate) struct PinnedServerVerification {
+    fingerprints: Vec<CertificateFingerprint>,
+    to_sync_client: mpsc::Sender<ClientAsyncMessage>,
+}
+
+impl PinnedServerVerification {
+    pub(crate) fn new(
+        fingerprints: Vec<CertificateFingerprint>,
+        to_sync_client: mpsc::Sender<ClientAsyncMessage>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            fingerprints,
+            to_sync_client,
+        })
+    }
+}
+
+impl rustls::client::ServerCertVerifier for PinnedServerVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        let fingerprint = CertificateFingerprint::from(end_entity);
+        if self.fingerprints.contains(&fingerprint) {
+            return Ok(rustls::client::ServerCertVerified::assertion());
+        }
+
+        let cert_info = CertVerificationInfo {
+            server_name: ServerName(server_name.clone()),
+            fingerprint,
+            known_fingerprint: None,
+        };
+        match self
+            .to_sync_client
+            .try_send(ClientAsyncMessage::CertificateConnectionAbort {
+                status: CertVerificationStatus::UntrustedCertificate,
+                cert_info,
+            }) {
+            Ok(_) => Err(rustls::Error::General(
+                "The server certificate is not pinned".to_string(),
+            )),
+            Err(_) => Err(rustls::Error::General(
+                "Failed to signal CertificateConnectionAbort".to_string(),
+            )),
+        }
+    }
+}
+
 /// Implementation of `ServerCertVerifier` that follows the Trust on first use authentication scheme.
 pub(crate) struct TofuServerVerification {
     store: CertStore,
@@ -2,14 +2,17 @@ use std::{
     collections::BTreeSet,
     error::Error,
     net::{AddrParseError, IpAddr, SocketAddr},
-    sync::Arc,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
 };
 
 use bevy::utils::tracing::{error, info};
 use bevy::{log::trace, prelude::Event};
 use bytes::Bytes;
-use quinn::{ClientConfig, Endpoint};
+use quinn::{ClientConfig, VarInt};
 use quinn_proto::ConnectionStats;
+use rand::Rng;
 
 
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -32,19 +35,27 @@ mod client_id;
 use client_id::receive_client_id;
 
 use crate::shared::{
+    admission::{request_join, JoinRequest, JoinResponse},
+    capture::{CaptureDirection, CaptureRecorder},
     channels::{
-        spawn_recv_channels_tasks, spawn_send_channels_tasks, Channel, ChannelAsyncMessage,
-        ChannelId, ChannelSyncMessage, ChannelType, ChannelsConfiguration,
+        spawn_recv_channels_tasks, spawn_send_channels_tasks, BlobId, Channel, ChannelAsyncMessage,
+        ChannelConfig, ChannelId, ChannelSyncMessage, ChannelsConfiguration, ChannelsRecvCounters,
+        MAX_BLOB_LEN,
     },
     error::QuintetError,
-    ClientId, InternalConnectionRef, DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE,
-    DEFAULT_KILL_MESSAGE_QUEUE_SIZE, DEFAULT_MESSAGE_QUEUE_SIZE,
+    link_conditioner::LinkConditionerConfig,
+    loopback::{bind_endpoint, EndpointTransport},
+    routing::{serialize_routed, MessageDirection, RoutedMessage},
+    stats::{ChannelStats, ChannelsStats, TrafficStats},
+    transport::TransportConfig,
+    ClientId, DisconnectReason, InternalConnectionRef, DEFAULT_KILL_MESSAGE_QUEUE_SIZE,
+    LOOPBACK_IP,
 };
 
 use super::{
     certificate::{
-        load_known_hosts_store_from_config, CertificateVerificationMode, SkipServerVerification,
-        TofuServerVerification,
+        load_known_hosts_store_from_config, CertificateVerificationMode, PinnedServerVerification,
+        SkipServerVerification, TofuServerVerification,
     },
     ClientAsyncMessage, QuintetConnectionError,
 };
@@ -73,10 +84,53 @@ pub struct ConnectionFailedEvent {
 }
 
 /// ConnectionLost event raised when the client is considered disconnected from the server. Raised in the CoreStage::PreUpdate stage.
+///
+/// With a [`ReconnectPolicy`], it is only raised once all the reconnection attempts failed.
 #[derive(Event)]
 pub struct ConnectionLostEvent {
     /// Local id of the connection
     pub id: ConnectionLocalId,
+    /// Reason given by the server when it closed the connection. `None` if the connection was lost without a reason (timeout, network error, ...)
+    pub reason: Option<DisconnectReason>,
+}
+
+/// ReconnectAttempt event raised each time a connection with a [`ReconnectPolicy`] starts a new attempt to reconnect to the server. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ReconnectAttemptEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Number of this attempt, starting at 1
+    pub attempt: u32,
+    /// Max number of attempts of the [`ReconnectPolicy`]
+    pub max_attempts: u32,
+}
+
+/// BlobProgress event raised while a blob sent by the server is being received, at most once per [`crate::shared::channels::DEFAULT_BLOB_CHUNK_SIZE`] bytes. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct BlobProgressEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Id of the blob, given by the server when sending it
+    pub blob_id: BlobId,
+    /// Application defined tag of the blob
+    pub tag: u32,
+    /// Number of bytes of the blob received so far
+    pub received: usize,
+    /// Total length of the blob, in bytes
+    pub total: usize,
+}
+
+/// BlobReceived event raised when a blob sent by the server was fully received. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Clone)]
+pub struct BlobReceivedEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Id of the blob, given by the server when sending it
+    pub blob_id: BlobId,
+    /// Application defined tag of the blob
+    pub tag: u32,
+    /// Content of the blob
+    pub payload: Bytes,
 }
 
 /// Configuration of a client connection, used when connecting to a server
@@ -85,6 +139,14 @@ pub struct ClientEndpointConfiguration {
     server_addr: SocketAddr,
     server_hostname: String,
     local_bind_addr: SocketAddr,
+    transport: EndpointTransport,
+    #[borsh(skip)]
+    link_conditioner: Option<LinkConditionerConfig>,
+    join_request: JoinRequest,
+    #[borsh(skip)]
+    reconnect_policy: Option<ReconnectPolicy>,
+    #[borsh(skip)]
+    transport_config: TransportConfig,
 }
 
 impl ClientEndpointConfiguration {
@@ -203,11 +265,7 @@ impl ClientEndpointConfiguration {
     ///    );
     /// ```
     pub fn from_addrs(server_addr: SocketAddr, local_bind_addr: SocketAddr) -> Self {
-        Self {
-            server_addr,
-            server_hostname: server_addr.ip().to_string(),
-            local_bind_addr,
-        }
+        Self::from_addrs_with_name(server_addr, server_addr.ip().to_string(), local_bind_addr)
     }
 
     /// Same as [`ClientEndpointConfiguration::from_addrs`], but with an additional `server_hostname` for certificate verification if it is not just the server IP.
@@ -220,8 +278,132 @@ impl ClientEndpointConfiguration {
             server_addr,
             server_hostname,
             local_bind_addr,
+            transport: EndpointTransport::Udp,
+            link_conditioner: None,
+            join_request: JoinRequest::default(),
+            reconnect_policy: None,
+            transport_config: TransportConfig::default(),
         }
     }
+
+    /// Creates a new ClientEndpointConfiguration using the in-process [`EndpointTransport::Loopback`] transport.
+    ///
+    /// Connects to a server of the same process started with [`crate::server::ServerEndpointConfiguration::loopback`] on the same port. No UDP socket is bound, and a local loopback port is assigned automatically.
+    ///
+    /// # Arguments
+    ///
+    /// * `server_port` - Loopback port of the server
+    ///
+    /// # Examples
+    ///
+    /// Connect to a server listening on loopback port 6000.
+    /// ```
+    /// use jeffy_quintet::client::connection::ClientEndpointConfiguration;
+    /// let config = ClientEndpointConfiguration::loopback(6000);
+    /// ```
+    pub fn loopback(server_port: u16) -> Self {
+        Self {
+            server_addr: SocketAddr::new(LOOPBACK_IP, server_port),
+            server_hostname: LOOPBACK_IP.to_string(),
+            local_bind_addr: SocketAddr::new(LOOPBACK_IP, 0),
+            transport: EndpointTransport::Loopback,
+            link_conditioner: None,
+            join_request: JoinRequest::default(),
+            reconnect_policy: None,
+            transport_config: TransportConfig::default(),
+        }
+    }
+
+    /// Sets the [`JoinRequest`] sent to the server when connecting, which the server may use to admit or reject the client
+    pub fn with_join_request(mut self, join_request: JoinRequest) -> Self {
+        self.join_request = join_request;
+        self
+    }
+
+    /// Simulates degraded network conditions on all the messages sent by the connection to the server. See [`LinkConditionerConfig`].
+    pub fn with_link_conditioner(mut self, link_conditioner: LinkConditionerConfig) -> Self {
+        self.link_conditioner = Some(link_conditioner);
+        self
+    }
+
+    /// Returns the [`ReconnectPolicy`] of the connection, if it reconnects automatically
+    pub fn reconnect_policy(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect_policy.as_ref()
+    }
+
+    /// Automatically reconnects to the server when the connection is lost, according to `reconnect_policy`. See [`ReconnectPolicy`].
+    pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(reconnect_policy);
+        self
+    }
+
+    /// Returns the [`TransportConfig`] of the connection
+    pub fn transport_config(&self) -> &TransportConfig {
+        &self.transport_config
+    }
+
+    /// Sets the transport parameters of the connection, see [`TransportConfig`]
+    pub fn with_transport_config(mut self, transport_config: TransportConfig) -> Self {
+        self.transport_config = transport_config;
+        self
+    }
+}
+
+/// Policy used by a connection to automatically reconnect to the server after losing its connection.
+///
+/// The delay before each attempt grows exponentially from `initial_delay` up to `max_delay`, and is randomized by `jitter` so that many clients losing the same server do not all reconnect at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Max number of reconnection attempts before the connection is considered lost
+    pub max_attempts: u32,
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound of the delay between two attempts, before jitter
+    pub max_delay: Duration,
+    /// Factor applied to the delay after each failed attempt
+    pub backoff_factor: f64,
+    /// Fraction (between 0 and 1) of the delay which is randomly added or removed
+    pub jitter: f64,
+    /// Whether to also reconnect when the server closed the connection with a [`DisconnectReason`] (kick, endpoint stopped, ...). Only connections lost without a reason (timeout, network error, ...) reconnect otherwise
+    pub reconnect_on_server_close: bool,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            backoff_factor: 2.,
+            jitter: 0.2,
+            reconnect_on_server_close: false,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay to wait before reconnection attempt number `attempt` (starting at 1), without jitter
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay_s = self.initial_delay.as_secs_f64() * self.backoff_factor.max(1.).powi(exponent);
+        Duration::from_secs_f64(delay_s.min(self.max_delay.as_secs_f64()))
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let jitter = self.jitter.clamp(0., 1.);
+        let factor = 1. + rand::thread_rng().gen_range(-jitter..=jitter);
+        self.base_delay(attempt).mul_f64(factor)
+    }
+}
+
+/// Outcome of [`Connection::poll_reconnect`]
+pub(crate) enum ReconnectPoll {
+    /// No reconnection attempt was due
+    Idle,
+    /// Reconnection attempt with this number was made
+    Attempt(u32),
+    /// Reconnection attempt with this number failed to start and was the last one. Holds the reason the connection was initially lost
+    GaveUp(u32, Option<DisconnectReason>),
 }
 
 /// Current state of a client connection
@@ -231,6 +413,8 @@ pub enum ConnectionState {
     Connecting,
     /// The connection is currently connected to the specified server
     Connected,
+    /// The connection was lost and is automatically reconnecting to the specified server, according to its [`ReconnectPolicy`]
+    Reconnecting,
     /// The connection is currently disconnected from the specified server.
     ///
     /// It may have never been connected if the connection failed.
@@ -241,6 +425,7 @@ impl From<&InternalConnectionState> for ConnectionState {
         match internal_conn {
             InternalConnectionState::Connecting => ConnectionState::Connecting,
             InternalConnectionState::Connected(_, _) => ConnectionState::Connected,
+            InternalConnectionState::Reconnecting => ConnectionState::Reconnecting,
             InternalConnectionState::Disconnected => ConnectionState::Disconnected,
         }
     }
@@ -251,6 +436,7 @@ impl From<&InternalConnectionState> for ConnectionState {
 pub(crate) enum InternalConnectionState {
     Connecting,
     Connected(InternalConnectionRef, Option<ClientId>),
+    Reconnecting,
     Disconnected,
 }
 
@@ -262,10 +448,12 @@ pub(crate) type ChannelAsyncMsgSend = mpsc::Sender<ChannelAsyncMessage>;
 pub(crate) type ChannelAsyncMsgRecv = mpsc::Receiver<ChannelAsyncMessage>;
 pub(crate) type ChannelSyncMsgSend = mpsc::Sender<ChannelSyncMessage>;
 pub(crate) type ChannelSyncMsgRecv = mpsc::Receiver<ChannelSyncMessage>;
-pub(crate) type CloseSend = broadcast::Sender<()>;
-pub(crate) type CloseRecv = broadcast::Receiver<()>;
+pub(crate) type CloseSend = broadcast::Sender<DisconnectReason>;
+pub(crate) type CloseRecv = broadcast::Receiver<DisconnectReason>;
 
-pub(crate) fn create_async_channels() -> (
+pub(crate) fn create_async_channels(
+    transport_config: &TransportConfig,
+) -> (
     MessageSend,
     MessageRecv,
     ClientAsyncMsgSend,
@@ -278,13 +466,13 @@ pub(crate) fn create_async_channels() -> (
     CloseRecv,
 ) {
     let (bytes_from_server_send, bytes_from_server_recv) =
-        mpsc::channel::<(ChannelId, Bytes)>(DEFAULT_MESSAGE_QUEUE_SIZE);
+        mpsc::channel::<(ChannelId, Bytes)>(transport_config.message_queue_size);
     let (to_sync_client_send, to_sync_client_recv) =
-        mpsc::channel::<ClientAsyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        mpsc::channel::<ClientAsyncMessage>(transport_config.internal_message_channel_size);
     let (from_channels_send, from_channels_recv) =
-        mpsc::channel::<ChannelAsyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        mpsc::channel::<ChannelAsyncMessage>(transport_config.internal_message_channel_size);
     let (to_channels_send, to_channels_recv) =
-        mpsc::channel::<ChannelSyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        mpsc::channel::<ChannelSyncMessage>(transport_config.internal_message_channel_size);
     let (close_send, close_recv) = broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
     (
         bytes_from_server_send,
@@ -321,14 +509,24 @@ pub struct Connection {
     default_channel: Option<ChannelId>,
 
     bytes_from_server_recv: mpsc::Receiver<(ChannelId, Bytes)>,
-    close_sender: broadcast::Sender<()>,
+    close_sender: CloseSend,
 
     pub(crate) from_async_client_recv: mpsc::Receiver<ClientAsyncMessage>,
     pub(crate) to_channels_send: mpsc::Sender<ChannelSyncMessage>,
     pub(crate) from_channels_recv: mpsc::Receiver<ChannelAsyncMessage>,
 
+    blob_id_gen: BlobId,
+
+    // Reconnection
+    reconnect_attempt: u32,
+    next_reconnect_at: Option<Instant>,
+    lost_reason: Option<DisconnectReason>,
+
     /// Quintet stats
     received_messages_count: u64,
+    pub(crate) recv_counters: Arc<ChannelsRecvCounters>,
+    channels_stats: Arc<ChannelsStats>,
+    capture: Option<CaptureRecorder>,
 }
 
 impl Connection {
@@ -351,7 +549,14 @@ impl Connection {
             channels: Vec::new(),
             default_channel: None,
             available_channel_ids: (0..255).collect(),
+            blob_id_gen: 0,
+            reconnect_attempt: 0,
+            next_reconnect_at: None,
+            lost_reason: None,
             received_messages_count: 0,
+            recv_counters: Arc::new(ChannelsRecvCounters::default()),
+            channels_stats: Arc::new(ChannelsStats::default()),
+            capture: None,
             bytes_from_server_recv,
             close_sender,
             from_async_client_recv,
@@ -416,10 +621,17 @@ impl Connection {
     ) -> Result<(), QuintetError> {
         let channel_id = channel_id.into();
         match &self.state {
-            InternalConnectionState::Disconnected => Err(QuintetError::ConnectionClosed),
+            InternalConnectionState::Disconnected | InternalConnectionState::Reconnecting => {
+                Err(QuintetError::ConnectionClosed)
+            }
             _ => match self.channels.get(channel_id as usize) {
                 Some(Some(channel)) => match borsh::to_vec(&message) {
-                    Ok(payload) => channel.send_payload(payload.into()),
+                    Ok(payload) => {
+                        let payload: Bytes = payload.into();
+                        channel.send_payload(payload.clone())?;
+                        self.capture_payload(CaptureDirection::Sent, channel_id, &payload);
+                        Ok(())
+                    }
                     Err(_) => Err(QuintetError::Serialization),
                 },
                 Some(None) => Err(QuintetError::ChannelClosed),
@@ -448,6 +660,28 @@ impl Connection {
         }
     }
 
+    /// Queues a [`RoutedMessage`] to be sent to the server, on the channel of its route
+    ///
+    /// Will return an [`Err`] if:
+    /// - the message can not be sent by a client
+    /// - or if the message is larger than the maximum size of its route
+    /// - or if the channel does not exist/is closed
+    /// - or if the client is disconnected
+    /// - or if a serialization error occurs
+    /// - (or if the message queue is full)
+    pub fn send_typed<T: RoutedMessage>(&self, message: T) -> Result<(), QuintetError> {
+        let (channel_id, payload) = serialize_routed(&message, MessageDirection::ClientToServer)?;
+        self.send_payload_on(channel_id, payload)
+    }
+
+    /// Same as [Connection::send_typed] but will log the error instead of returning it
+    pub fn try_send_typed<T: RoutedMessage>(&self, message: T) {
+        match self.send_typed(message) {
+            Ok(_) => {}
+            Err(err) => error!("try_send_typed: {}", err),
+        }
+    }
+
     /// Same as [Connection::send_payload_on] but on the default channel
     pub fn send_payload<T: Into<Bytes>>(&self, payload: T) -> Result<(), QuintetError> {
         match self.default_channel {
@@ -469,9 +703,16 @@ impl Connection {
     ) -> Result<(), QuintetError> {
         let channel_id = channel_id.into();
         match &self.state {
-            InternalConnectionState::Disconnected => Err(QuintetError::ConnectionClosed),
+            InternalConnectionState::Disconnected | InternalConnectionState::Reconnecting => {
+                Err(QuintetError::ConnectionClosed)
+            }
             _ => match self.channels.get(channel_id as usize) {
-                Some(Some(channel)) => channel.send_payload(payload.into()),
+                Some(Some(channel)) => {
+                    let payload: Bytes = payload.into();
+                    channel.send_payload(payload.clone())?;
+                    self.capture_payload(CaptureDirection::Sent, channel_id, &payload);
+                    Ok(())
+                }
                 Some(None) => Err(QuintetError::ChannelClosed),
                 None => Err(QuintetError::UnknownChannel(channel_id)),
             },
@@ -505,10 +746,15 @@ impl Connection {
     /// - Can return an [`Err`] if the connection is closed
     pub fn receive_payload(&mut self) -> Result<Option<(ChannelId, Bytes)>, QuintetError> {
         match &self.state {
-            InternalConnectionState::Disconnected => Err(QuintetError::ConnectionClosed),
+            InternalConnectionState::Disconnected | InternalConnectionState::Reconnecting => {
+                Err(QuintetError::ConnectionClosed)
+            }
             _ => match self.bytes_from_server_recv.try_recv() {
                 Ok(msg_payload) => {
                     self.received_messages_count += 1;
+                    self.channels_stats
+                        .record_received(msg_payload.0, msg_payload.1.len());
+                    self.capture_payload(CaptureDirection::Received, msg_payload.0, &msg_payload.1);
                     Ok(Some(msg_payload))
                 }
                 Err(err) => match err {
@@ -530,15 +776,58 @@ impl Connection {
         }
     }
 
+    /// Sends a large payload to the server, in chunks, on a dedicated stream.
+    ///
+    /// Unlike a message sent on a channel, a blob does not delay the channels messages sent after it. The server gets a [`crate::server::BlobProgressEvent`] as the blob is received, then a [`crate::server::BlobReceivedEvent`] with its content. `tag` is not interpreted by Quintet and can be used to tell blobs apart.
+    ///
+    /// Returns the id of the blob. Will return an [`Err`] if:
+    /// - the client is disconnected
+    /// - the payload is larger than [`MAX_BLOB_LEN`]
+    /// - (or if the connection internal queue is full)
+    pub fn send_blob<T: Into<Bytes>>(&mut self, tag: u32, payload: T) -> Result<BlobId, QuintetError> {
+        let payload: Bytes = payload.into();
+        if payload.len() > MAX_BLOB_LEN {
+            return Err(QuintetError::BlobTooLarge(payload.len()));
+        }
+        match &self.state {
+            InternalConnectionState::Disconnected | InternalConnectionState::Reconnecting => {
+                Err(QuintetError::ConnectionClosed)
+            }
+            _ => {
+                let blob_id = self.blob_id_gen;
+                match self.to_channels_send.try_send(ChannelSyncMessage::SendBlob {
+                    blob_id,
+                    tag,
+                    payload,
+                }) {
+                    Ok(_) => {
+                        self.blob_id_gen += 1;
+                        Ok(blob_id)
+                    }
+                    Err(TrySendError::Full(_)) => Err(QuintetError::FullQueue),
+                    Err(TrySendError::Closed(_)) => Err(QuintetError::InternalChannelClosed),
+                }
+            }
+        }
+    }
+
     /// Immediately prevents new messages from being sent on the connection and signal the connection to closes all its background tasks.
     ///
     /// Before trully closing, the connection will wait for all buffered messages in all its opened channels to be properly sent according to their respective channel type.
-    pub fn disconnect(&mut self) -> Result<(), QuintetError> {
+    ///
+    /// The `reason` code and message are sent to the server in the QUIC close frame, and received in its [`crate::server::ConnectionLostEvent`].
+    pub fn disconnect(&mut self, reason: DisconnectReason) -> Result<(), QuintetError> {
         match &self.state {
             &InternalConnectionState::Disconnected => Ok(()),
+            &InternalConnectionState::Reconnecting => {
+                self.stop_reconnecting();
+                // Stops the current attempt if there is one. Between two attempts, there is nothing left to close.
+                let _ = self.close_sender.send(reason);
+                Ok(())
+            }
             _ => {
                 self.state = InternalConnectionState::Disconnected;
-                match self.close_sender.send(()) {
+                match self.close_sender.send(reason) {
                     Ok(_) => Ok(()),
                     Err(_) => {
                         // The only possible error for a send is that there is no active receivers, meaning that the tasks are already terminated.
@@ -551,7 +840,7 @@ impl Connection {
 
     /// Same as [Connection::disconnect] but will log the error instead of returning it
     pub(crate) fn try_disconnect(&mut self) {
-        match &self.disconnect() {
+        match &self.disconnect(DisconnectReason::default()) {
             Ok(_) => (),
             Err(err) => error!("Failed to properly close clonnection: {}", err),
         }
@@ -575,6 +864,54 @@ impl Connection {
         self.received_messages_count
     }
 
+    /// Returns how many messages were dropped by [`ChannelType::UnreliableSequenced`](crate::shared::channels::ChannelType::UnreliableSequenced) channels on this connection because they were older than an already received message
+    pub fn dropped_stale_messages_count(&self) -> u64 {
+        self.recv_counters
+            .dropped_stale_messages
+            .load(Ordering::Relaxed)
+    }
+
+    /// Returns the per channel [`TrafficStats`] of the connection, and its round-trip time if connected.
+    pub fn traffic_stats(&self) -> TrafficStats {
+        let rtt = match &self.state {
+            InternalConnectionState::Connected(connection, _) => Some(connection.rtt()),
+            _ => None,
+        };
+        self.channels_stats.traffic_stats(rtt)
+    }
+
+    /// Returns the [`ChannelStats`] of a channel of the connection, if there was some traffic on this channel.
+    pub fn channel_stats(&self, channel_id: ChannelId) -> Option<ChannelStats> {
+        self.channels_stats.channel(channel_id)
+    }
+
+    /// Starts recording every payload sent and received on this connection to a capture file at `path`.
+    ///
+    /// Replaces (and flushes) the capture in progress, if any. The capture goes on across reconnections, and can be read back with [`crate::shared::capture::read_capture`].
+    pub fn start_capture<P: AsRef<Path>>(&mut self, path: P) -> Result<(), QuintetError> {
+        self.capture = Some(CaptureRecorder::create(path)?);
+        Ok(())
+    }
+
+    /// Stops the capture in progress, if any, and flushes it to its file
+    pub fn stop_capture(&mut self) -> Result<(), QuintetError> {
+        match self.capture.take() {
+            Some(capture) => capture.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if this connection is recording a capture
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    fn capture_payload(&self, direction: CaptureDirection, channel_id: ChannelId, payload: &[u8]) {
+        if let Some(capture) = &self.capture {
+            capture.record(direction, self.client_id(), channel_id, payload);
+        }
+    }
+
     /// Returns the client_id assigned to this client by the server.
     ///
     /// Will be [None] if the `shared-client-id` feature is disabled
@@ -593,64 +930,141 @@ impl Connection {
     pub fn reconnect(&mut self) -> Result<(), QuintetError> {
         match &self.state {
             InternalConnectionState::Disconnected => {
-                let (
-                    bytes_from_server_send,
-                    bytes_from_server_recv,
-                    to_sync_client_send,
-                    to_sync_client_recv,
-                    from_channels_send,
-                    from_channels_recv,
-                    to_channels_send,
-                    to_channels_recv,
-                    close_send,
-                    close_recv,
-                ) = create_async_channels();
-
-                // Connection state reset
                 self.state = InternalConnectionState::Connecting;
-                self.channels = Vec::new();
-                self.default_channel = None;
-                self.available_channel_ids = (0..255).collect();
-                self.bytes_from_server_recv = bytes_from_server_recv;
-                self.close_sender = close_send;
-                self.from_async_client_recv = to_sync_client_recv;
-                self.to_channels_send = to_channels_send;
-                self.from_channels_recv = from_channels_recv;
-                // Connection stats reset
-                self.received_messages_count = 0;
-
-                // Open default channels
-                self.open_configured_channels(self.channels_config.clone())?;
-
-                // Async connection
-                let local_id = self.local_id;
-                let endpoint_config = self.endpoint_config.clone();
-                let cert_mode = self.cert_mode.clone();
-                self.runtime.spawn(async move {
-                    async_connection_task(
-                        local_id,
-                        endpoint_config,
-                        cert_mode,
-                        to_sync_client_send,
-                        bytes_from_server_send,
-                        to_channels_recv,
-                        from_channels_send,
-                        close_recv,
-                    )
-                    .await
-                });
+                self.restart()
             }
-            _ => (),
+            _ => Ok(()),
         }
+    }
+
+    /// Recreates the connection tasks and channels and starts connecting again to the server
+    fn restart(&mut self) -> Result<(), QuintetError> {
+        let (
+            bytes_from_server_send,
+            bytes_from_server_recv,
+            to_sync_client_send,
+            to_sync_client_recv,
+            from_channels_send,
+            from_channels_recv,
+            to_channels_send,
+            to_channels_recv,
+            close_send,
+            close_recv,
+        ) = create_async_channels(&self.endpoint_config.transport_config);
+
+        // Connection state reset
+        self.channels = Vec::new();
+        self.default_channel = None;
+        self.available_channel_ids = (0..255).collect();
+        self.bytes_from_server_recv = bytes_from_server_recv;
+        self.close_sender = close_send;
+        self.from_async_client_recv = to_sync_client_recv;
+        self.to_channels_send = to_channels_send;
+        self.from_channels_recv = from_channels_recv;
+        // Connection stats reset
+        self.received_messages_count = 0;
+        self.recv_counters = Arc::new(ChannelsRecvCounters::default());
+        self.channels_stats = Arc::new(ChannelsStats::default());
+
+        // Open default channels
+        self.open_configured_channels(self.channels_config.clone())?;
+
+        // Async connection
+        let local_id = self.local_id;
+        let endpoint_config = self.endpoint_config.clone();
+        let cert_mode = self.cert_mode.clone();
+        let recv_counters = self.recv_counters.clone();
+        self.runtime.spawn(async move {
+            async_connection_task(
+                local_id,
+                endpoint_config,
+                cert_mode,
+                to_sync_client_send,
+                bytes_from_server_send,
+                recv_counters,
+                to_channels_recv,
+                from_channels_send,
+                close_recv,
+            )
+            .await
+        });
         Ok(())
     }
 
+    /// Starts reconnecting to the server after the connection was lost, if the connection has a [`ReconnectPolicy`] allowing it.
+    ///
+    /// Returns false if the connection does not reconnect automatically, or if the server closed it with a `reason` and the policy does not reconnect on server closes.
+    pub(crate) fn start_reconnecting(&mut self, reason: Option<DisconnectReason>) -> bool {
+        let Some(policy) = self
+            .endpoint_config
+            .reconnect_policy
+            .filter(|policy| policy.max_attempts > 0)
+            .filter(|policy| reason.is_none() || policy.reconnect_on_server_close)
+        else {
+            return false;
+        };
+        self.try_disconnect();
+        self.state = InternalConnectionState::Reconnecting;
+        self.reconnect_attempt = 0;
+        self.next_reconnect_at = Some(Instant::now() + policy.delay(1));
+        self.lost_reason = reason;
+        true
+    }
+
+    /// Schedules the next reconnection attempt after a failed one.
+    ///
+    /// Returns false if all the attempts allowed by the [`ReconnectPolicy`] were used.
+    pub(crate) fn schedule_next_reconnect(&mut self) -> bool {
+        match self.endpoint_config.reconnect_policy {
+            Some(policy) if self.reconnect_attempt < policy.max_attempts => {
+                self.next_reconnect_at =
+                    Some(Instant::now() + policy.delay(self.reconnect_attempt + 1));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Starts the next reconnection attempt if it is due
+    pub(crate) fn poll_reconnect(&mut self) -> ReconnectPoll {
+        match self.next_reconnect_at {
+            Some(next_reconnect_at) if next_reconnect_at <= Instant::now() => {
+                self.next_reconnect_at = None;
+                self.reconnect_attempt += 1;
+                let attempt = self.reconnect_attempt;
+                if let Err(err) = self.restart() {
+                    error!(
+                        "Failed to start reconnection attempt {} of connection {}: {}",
+                        attempt, self.local_id, err
+                    );
+                    if !self.schedule_next_reconnect() {
+                        return ReconnectPoll::GaveUp(attempt, self.stop_reconnecting());
+                    }
+                }
+                ReconnectPoll::Attempt(attempt)
+            }
+            _ => ReconnectPoll::Idle,
+        }
+    }
+
+    /// Gives up reconnecting and returns the reason the connection was initially lost
+    pub(crate) fn stop_reconnecting(&mut self) -> Option<DisconnectReason> {
+        self.state = InternalConnectionState::Disconnected;
+        self.reset_reconnection();
+        self.lost_reason.take()
+    }
+
+    pub(crate) fn reset_reconnection(&mut self) {
+        self.reconnect_attempt = 0;
+        self.next_reconnect_at = None;
+    }
+
     pub(crate) fn open_configured_channels(
         &mut self,
         channels_config: ChannelsConfiguration,
     ) -> Result<(), QuintetError> {
-        for channel_type in channels_config.configs() {
-            self.open_channel(*channel_type)?;
+        for channel_config in channels_config.configs() {
+            self.open_channel(*channel_config)?;
         }
         Ok(())
     }
@@ -665,17 +1079,20 @@ impl Connection {
         &self.cert_mode
     }
 
-    /// Opens a channel of the requested [ChannelType] and returns its [ChannelId].
+    /// Opens a channel of the requested [ChannelType](crate::shared::channels::ChannelType) and returns its [ChannelId]. A [ChannelConfig] can be given instead to also set the channel's priority.
     ///
     /// If no channels were previously opened, the opened channel will be the new default channel.
     ///
     /// Can fail if the Connection is closed.
-    pub fn open_channel(&mut self, channel_type: ChannelType) -> Result<ChannelId, QuintetError> {
+    pub fn open_channel(
+        &mut self,
+        channel: impl Into<ChannelConfig>,
+    ) -> Result<ChannelId, QuintetError> {
         let channel_id = match self.available_channel_ids.pop_first() {
             Some(channel_id) => channel_id,
             None => return Err(QuintetError::MaxChannelsCountReached),
         };
-        match self.create_channel(channel_id, channel_type) {
+        match self.create_channel(channel_id, channel.into()) {
             Ok(channel_id) => {
                 if self.default_channel.is_none() {
                     self.default_channel = Some(channel_id);
@@ -692,7 +1109,7 @@ impl Connection {
 
     /// Closes the channel with the corresponding [ChannelId].
     ///
-    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [ChannelType], before fully closing.
+    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [ChannelType](crate::shared::channels::ChannelType), before fully closing.
     ///
     /// If the closed channel is the current default channel, the default channel gets set to `None`.
     ///
@@ -727,10 +1144,10 @@ impl Connection {
     fn create_channel(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelType,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, QuintetError> {
         let (bytes_to_channel_send, bytes_to_channel_recv) =
-            mpsc::channel::<Bytes>(DEFAULT_MESSAGE_QUEUE_SIZE);
+            mpsc::channel::<Bytes>(self.endpoint_config.transport_config.message_queue_size);
         let (channel_close_send, channel_close_recv) =
             mpsc::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
 
@@ -738,12 +1155,18 @@ impl Connection {
             .to_channels_send
             .try_send(ChannelSyncMessage::CreateChannel {
                 channel_id,
-                channel_type,
+                channel_type: channel_config.channel_type,
+                priority: channel_config.priority,
                 bytes_to_channel_recv,
                 channel_close_recv,
             }) {
             Ok(_) => {
-                let channel = Some(Channel::new(bytes_to_channel_send, channel_close_send));
+                let channel = Some(Channel::new(
+                    channel_id,
+                    bytes_to_channel_send,
+                    channel_close_send,
+                    self.channels_stats.clone(),
+                ));
                 if (channel_id as usize) < self.channels.len() {
                     self.channels[channel_id as usize] = channel;
                 } else {
@@ -763,12 +1186,15 @@ impl Connection {
     }
 }
 
+// Takes the ends of every async channel of the connection, which are handed over to its tasks once connected
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn async_connection_task(
     local_id: ConnectionLocalId,
     endpoint_config: ClientEndpointConfiguration,
     cert_mode: CertificateVerificationMode,
     to_sync_client_send: ClientAsyncMsgSend,
     bytes_from_server_send: MessageSend,
+    recv_counters: Arc<ChannelsRecvCounters>,
     to_channels_recv: ChannelSyncMsgRecv,
     from_channels_send: ChannelAsyncMsgSend,
     close_recv: CloseRecv,
@@ -778,11 +1204,19 @@ pub(crate) async fn async_connection_task(
         local_id, endpoint_config.server_addr
     );
 
-    let client_cfg = configure_client(cert_mode, to_sync_client_send.clone())
-        .expect("Failed to configure client");
+    let client_cfg = configure_client(
+        cert_mode,
+        &endpoint_config.transport_config,
+        to_sync_client_send.clone(),
+    )
+    .expect("Failed to configure client");
 
-    let mut endpoint = Endpoint::client(endpoint_config.local_bind_addr)
-        .expect("Failed to create client endpoint");
+    let mut endpoint = bind_endpoint(
+        endpoint_config.transport,
+        endpoint_config.local_bind_addr,
+        None,
+    )
+    .expect("Failed to create client endpoint");
     endpoint.set_default_client_config(client_cfg);
 
     let connection = endpoint
@@ -804,17 +1238,38 @@ pub(crate) async fn async_connection_task(
                 .expect("Failed to signal connection failure to sync client");
         }
         Ok(connection_handle) => {
+            // The server admits or rejects the client based on its join request
+            match request_join(&connection_handle, &endpoint_config.join_request).await {
+                Some(JoinResponse::Accepted) => (),
+                response => {
+                    let err = match response {
+                        Some(JoinResponse::Rejected(reason)) => {
+                            QuintetConnectionError::Rejected(reason)
+                        }
+                        _ => QuintetConnectionError::JoinHandshakeFailed,
+                    };
+                    error!("Connection {}, error while joining: {}", local_id, err);
+                    connection_handle.close(VarInt::from_u32(0), "join failed".as_bytes());
+                    to_sync_client_send
+                        .send(ClientAsyncMessage::ConnectionFailed(err))
+                        .await
+                        .expect("Failed to signal connection failure to sync client");
+                    return;
+                }
+            }
+
             // Spawn a task to listen for the underlying connection being closed
             {
                 let conn = connection_handle.clone();
                 let to_sync_client = to_sync_client_send.clone();
                 tokio::spawn(async move {
-                    let _conn_err = conn.closed().await;
-                    info!("Connection {} closed: {}", local_id, _conn_err);
+                    let conn_err = conn.closed().await;
+                    info!("Connection {} closed: {}", local_id, conn_err);
+                    let reason = DisconnectReason::from_connection_error(&conn_err);
                     // If we requested the connection to close, channel may have been closed already.
                     if !to_sync_client.is_closed() {
                         to_sync_client
-                            .send(ClientAsyncMessage::ConnectionClosed)
+                            .send(ClientAsyncMessage::ConnectionClosed(reason))
                             .await
                             .expect("Failed to signal connection closed in async connection");
                     }
@@ -826,6 +1281,9 @@ pub(crate) async fn async_connection_task(
                 local_id,
                 close_recv.resubscribe(),
                 bytes_from_server_send,
+                from_channels_send.clone(),
+                recv_counters,
+                endpoint_config.transport_config.max_reliable_frame_len,
             );
 
             spawn_send_channels_tasks(
@@ -833,6 +1291,8 @@ pub(crate) async fn async_connection_task(
                 close_recv.resubscribe(),
                 to_channels_recv,
                 from_channels_send,
+                endpoint_config.link_conditioner.clone(),
+                endpoint_config.transport_config.max_reliable_frame_len,
             );
 
             #[cfg(not(feature = "shared-client-id"))]
@@ -900,19 +1360,20 @@ async fn signal_connection(
 
 fn configure_client(
     cert_mode: CertificateVerificationMode,
+    transport_config: &TransportConfig,
     to_sync_client: mpsc::Sender<ClientAsyncMessage>,
 ) -> Result<ClientConfig, Box<dyn Error>> {
-    match cert_mode {
+    let mut client_config = match cert_mode {
         CertificateVerificationMode::SkipVerification => {
             let crypto = rustls::ClientConfig::builder()
                 .with_safe_defaults()
                 .with_custom_certificate_verifier(SkipServerVerification::new())
                 .with_no_client_auth();
 
-            Ok(ClientConfig::new(Arc::new(crypto)))
+            ClientConfig::new(Arc::new(crypto))
         }
         CertificateVerificationMode::SignedByCertificateAuthority => {
-            Ok(ClientConfig::with_native_roots())
+            ClientConfig::with_native_roots()
         }
         CertificateVerificationMode::TrustOnFirstUse(config) => {
             let (store, store_file) = load_known_hosts_store_from_config(config.known_hosts)?;
@@ -925,7 +1386,19 @@ fn configure_client(
                     store_file,
                 ))
                 .with_no_client_auth();
-            Ok(ClientConfig::new(Arc::new(crypto)))
+            ClientConfig::new(Arc::new(crypto))
         }
-    }
+        CertificateVerificationMode::PinnedFingerprints(fingerprints) => {
+            let crypto = rustls::ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(PinnedServerVerification::new(
+                    fingerprints,
+                    to_sync_client,
+                ))
+                .with_no_client_auth();
+            ClientConfig::new(Arc::new(crypto))
+        }
+    };
+    client_config.transport_config(Arc::new(transport_config.to_quinn_transport_config()?));
+    Ok(client_config)
 }
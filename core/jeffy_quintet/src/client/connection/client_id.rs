@@ -6,7 +6,7 @@ use tokio_util::codec::{FramedRead, LengthDelimitedCodec};
 
 use crate::{
     client::QuintetConnectionError,
-    shared::{ClientId, CLIENT_ID_LEN},
+    shared::{ClientId, DisconnectReason, CLIENT_ID_LEN},
 };
 
 pub(crate) enum ClientIdReception {
@@ -17,7 +17,7 @@ pub(crate) enum ClientIdReception {
 
 pub(crate) async fn receive_client_id(
     connection_handle: quinn::Connection,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
 ) -> ClientIdReception {
     let mut client_id = None;
     let mut err = QuintetConnectionError::ClientIdNotReceived;
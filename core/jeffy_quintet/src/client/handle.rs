@@ -0,0 +1,60 @@
+use std::ops::{Deref, DerefMut};
+
+use tokio::runtime::Runtime;
+
+use crate::client::{update_connections, ClientEvent, QuintetClient};
+
+/// A [`QuintetClient`] usable without Bevy, for headless tools or bots.
+///
+/// It owns the async runtime running the connections' tasks. Messages received from the servers and the connections' events are only processed during [`ClientHandle::poll`], which should be called regularly. All the [`QuintetClient`] methods (and through them, the [`crate::client::connection::Connection`] send/receive methods) are available on the handle.
+pub struct ClientHandle {
+    // Declared before the runtime, to be dropped while the runtime still exists
+    client: QuintetClient,
+    runtime: Runtime,
+}
+
+impl ClientHandle {
+    /// Creates a client handle with its own multi-threaded async runtime, without any connection. Connections are opened with [`QuintetClient::open_connection`]
+    pub fn new() -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self::with_runtime(runtime))
+    }
+
+    /// Creates a client handle using the given async runtime
+    pub fn with_runtime(runtime: Runtime) -> Self {
+        Self {
+            client: QuintetClient::new(runtime.handle().clone()),
+            runtime,
+        }
+    }
+
+    /// Returns the async runtime used by the client
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// Receive messages from the async client tasks and update the connections, the equivalent of the Bevy plugin's system.
+    ///
+    /// Returns the events raised since the previous poll, in order.
+    pub fn poll(&mut self) -> Vec<ClientEvent> {
+        let mut events = Vec::new();
+        update_connections(&mut self.client, |event| events.push(event));
+        events
+    }
+}
+
+impl Deref for ClientHandle {
+    type Target = QuintetClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl DerefMut for ClientHandle {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
@@ -1,12 +1,13 @@
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
     net::{AddrParseError, IpAddr, SocketAddr},
-    sync::Arc,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
 };
 
 use bevy::prelude::*;
 use bytes::Bytes;
-use quinn::{Endpoint as QuinnEndpoint, ServerConfig};
+use quinn::{ServerConfig, VarInt};
 use quinn_proto::ConnectionStats;
 //use serde::Deserialize;
 
@@ -27,14 +28,24 @@ use tokio::{
 use crate::{
     server::certificate::{retrieve_certificate, CertificateRetrievalMode, ServerCertificate},
     shared::{
+        admission::{
+            receive_join_request, send_join_response, Admission, AdmissionContext,
+            AdmissionValidator, JoinRequest, JoinResponse,
+        },
+        capture::{CaptureDirection, CaptureRecorder},
         channels::{
-            spawn_recv_channels_tasks, spawn_send_channels_tasks, Channel, ChannelAsyncMessage,
-            ChannelId, ChannelSyncMessage, ChannelType, ChannelsConfiguration,
+            spawn_recv_channels_tasks, spawn_send_channels_tasks, BlobId, Channel,
+            ChannelAsyncMessage, ChannelConfig, ChannelId, ChannelSyncMessage,
+            ChannelsConfiguration, ChannelsRecvCounters, MAX_BLOB_LEN,
         },
         error::QuintetError,
-        AsyncRuntime, ClientId, InternalConnectionRef, QuintetSyncUpdate,
-        DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE, DEFAULT_KEEP_ALIVE_INTERVAL_S,
-        DEFAULT_KILL_MESSAGE_QUEUE_SIZE, DEFAULT_MESSAGE_QUEUE_SIZE,
+        link_conditioner::LinkConditionerConfig,
+        loopback::{bind_endpoint, EndpointTransport},
+        routing::{serialize_routed, MessageDirection, RoutedMessage},
+        stats::{ChannelStats, ChannelsStats, TrafficStats},
+        transport::TransportConfig,
+        AsyncRuntime, ClientId, DisconnectReason, InternalConnectionRef, QuintetSyncUpdate,
+        DEFAULT_KILL_MESSAGE_QUEUE_SIZE, LOOPBACK_IP,
     },
 };
 
@@ -46,6 +57,8 @@ mod client_id;
 
 /// Module for the server's certificate features
 pub mod certificate;
+/// Module for a server driven without Bevy
+pub mod handle;
 
 /// Connection event raised when a client just connected to the server. Raised in the CoreStage::PreUpdate stage.
 #[derive(Event, Debug, Copy, Clone)]
@@ -55,16 +68,51 @@ pub struct ConnectionEvent {
 }
 
 /// ConnectionLost event raised when a client is considered disconnected from the server. Raised in the CoreStage::PreUpdate stage.
-#[derive(Event, Debug, Copy, Clone)]
+#[derive(Event, Debug, Clone)]
 pub struct ConnectionLostEvent {
     /// Id of the client who lost connection
     pub id: ClientId,
+    /// Reason given by the client when it closed the connection. `None` if the connection was lost without a reason (timeout, network error, ...)
+    pub reason: Option<DisconnectReason>,
+}
+
+/// BlobProgress event raised while a blob sent by a client is being received, at most once per [`crate::shared::channels::DEFAULT_BLOB_CHUNK_SIZE`] bytes. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct BlobProgressEvent {
+    /// Id of the client sending the blob
+    pub id: ClientId,
+    /// Id of the blob, given by the client when sending it
+    pub blob_id: BlobId,
+    /// Application defined tag of the blob
+    pub tag: u32,
+    /// Number of bytes of the blob received so far
+    pub received: usize,
+    /// Total length of the blob, in bytes
+    pub total: usize,
+}
+
+/// BlobReceived event raised when a blob sent by a client was fully received. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Clone)]
+pub struct BlobReceivedEvent {
+    /// Id of the client who sent the blob
+    pub id: ClientId,
+    /// Id of the blob, given by the client when sending it
+    pub blob_id: BlobId,
+    /// Application defined tag of the blob
+    pub tag: u32,
+    /// Content of the blob
+    pub payload: Bytes,
 }
 
 /// Configuration of the server, used when the server starts an Endpoint
 #[derive(Debug, BorshDeserialize, Clone)]
 pub struct ServerEndpointConfiguration {
     local_bind_addr: SocketAddr,
+    transport: EndpointTransport,
+    #[borsh(skip)]
+    link_conditioner: Option<LinkConditionerConfig>,
+    #[borsh(skip)]
+    transport_config: TransportConfig,
 }
 
 impl ServerEndpointConfiguration {
@@ -88,7 +136,7 @@ impl ServerEndpointConfiguration {
     /// ```
     pub fn from_string(local_bind_addr_str: &str) -> Result<Self, AddrParseError> {
         let local_bind_addr = local_bind_addr_str.parse()?;
-        Ok(Self { local_bind_addr })
+        Ok(Self::from_addr(local_bind_addr))
     }
 
     /// Creates a new ServerEndpointConfiguration
@@ -107,9 +155,7 @@ impl ServerEndpointConfiguration {
     /// let config = ServerEndpointConfiguration::from_ip(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 6000);
     /// ```
     pub fn from_ip(local_bind_ip: IpAddr, local_bind_port: u16) -> Self {
-        Self {
-            local_bind_addr: SocketAddr::new(local_bind_ip, local_bind_port),
-        }
+        Self::from_addr(SocketAddr::new(local_bind_ip, local_bind_port))
     }
 
     /// Creates a new ServerEndpointConfiguration
@@ -130,19 +176,66 @@ impl ServerEndpointConfiguration {
     ///       );
     /// ```
     pub fn from_addr(local_bind_addr: SocketAddr) -> Self {
-        Self { local_bind_addr }
+        Self {
+            local_bind_addr,
+            transport: EndpointTransport::Udp,
+            link_conditioner: None,
+            transport_config: TransportConfig::default(),
+        }
+    }
+
+    /// Creates a new ServerEndpointConfiguration using the in-process [`EndpointTransport::Loopback`] transport.
+    ///
+    /// Only clients of the same process, configured with [`crate::client::connection::ClientEndpointConfiguration::loopback`] and the same port, can connect to it. No UDP socket is bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `local_bind_port` - Loopback port to listen on.
+    ///
+    /// # Examples
+    ///
+    /// Listen on loopback port 6000.
+    /// ```
+    /// use jeffy_quintet::server::ServerEndpointConfiguration;
+    /// let config = ServerEndpointConfiguration::loopback(6000);
+    /// ```
+    pub fn loopback(local_bind_port: u16) -> Self {
+        Self {
+            local_bind_addr: SocketAddr::new(LOOPBACK_IP, local_bind_port),
+            transport: EndpointTransport::Loopback,
+            link_conditioner: None,
+            transport_config: TransportConfig::default(),
+        }
+    }
+
+    /// Simulates degraded network conditions on all the messages sent by the endpoint to its clients. See [`LinkConditionerConfig`].
+    pub fn with_link_conditioner(mut self, link_conditioner: LinkConditionerConfig) -> Self {
+        self.link_conditioner = Some(link_conditioner);
+        self
+    }
+
+    /// Returns the [`TransportConfig`] of the endpoint's connections
+    pub fn transport_config(&self) -> &TransportConfig {
+        &self.transport_config
+    }
+
+    /// Sets the transport parameters of the endpoint's connections, see [`TransportConfig`]
+    pub fn with_transport_config(mut self, transport_config: TransportConfig) -> Self {
+        self.transport_config = transport_config;
+        self
     }
 }
 
 #[derive(Debug)]
 pub(crate) enum ServerAsyncMessage {
     ClientConnected(ClientConnection),
-    ClientConnectionClosed(ClientId), // TODO Might add a ConnectionError
+    ClientConnectionClosed(ClientId, Option<DisconnectReason>),
 }
 
 #[derive(Debug, Clone)]
 pub(crate) enum ServerSyncMessage {
     ClientConnectedAck(ClientId),
+    ClientRejected(String),
 }
 
 /// Represents a connection from a Quintet client to a server's [`Endpoint`]
@@ -152,7 +245,11 @@ pub struct ClientConnection {
 
     channels: Vec<Option<Channel>>,
     bytes_from_client_recv: mpsc::Receiver<(ChannelId, Bytes)>,
-    close_sender: broadcast::Sender<()>,
+    close_sender: broadcast::Sender<DisconnectReason>,
+    recv_counters: Arc<ChannelsRecvCounters>,
+    channels_stats: Arc<ChannelsStats>,
+    join_request: JoinRequest,
+    message_queue_size: usize,
 
     pub(crate) to_connection_send: mpsc::Sender<ServerSyncMessage>,
     pub(crate) to_channels_send: mpsc::Sender<ChannelSyncMessage>,
@@ -177,10 +274,10 @@ impl ClientConnection {
     pub(crate) fn create_channel(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelType,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, QuintetError> {
         let (bytes_to_channel_send, bytes_to_channel_recv) =
-            mpsc::channel::<Bytes>(DEFAULT_MESSAGE_QUEUE_SIZE);
+            mpsc::channel::<Bytes>(self.message_queue_size);
         let (channel_close_send, channel_close_recv) =
             mpsc::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
 
@@ -188,12 +285,18 @@ impl ClientConnection {
             .to_channels_send
             .try_send(ChannelSyncMessage::CreateChannel {
                 channel_id,
-                channel_type,
+                channel_type: channel_config.channel_type,
+                priority: channel_config.priority,
                 bytes_to_channel_recv,
                 channel_close_recv,
             }) {
             Ok(_) => {
-                let channel = Some(Channel::new(bytes_to_channel_send, channel_close_send));
+                let channel = Some(Channel::new(
+                    channel_id,
+                    bytes_to_channel_send,
+                    channel_close_send,
+                    self.channels_stats.clone(),
+                ));
                 if (channel_id as usize) < self.channels.len() {
                     self.channels[channel_id as usize] = channel;
                 } else {
@@ -214,7 +317,7 @@ impl ClientConnection {
 
     /// Signal the connection to closes all its background tasks. Before trully closing, the connection will wait for all buffered messages in all its opened channels to be properly sent according to their respective channel type.
     pub(crate) fn close(&mut self) -> Result<(), QuintetError> {
-        match self.close_sender.send(()) {
+        match self.close_sender.send(DisconnectReason::default()) {
             Ok(_) => Ok(()),
             Err(_) => {
                 // The only possible error for a send is that there is no active receivers, meaning that the tasks are already terminated.
@@ -231,13 +334,15 @@ impl ClientConnection {
     }
 }
 
-/// By default, when starting an [Endpoint], Quintet creates 1 channel instance of each [ChannelType], each with their own [ChannelId].
-/// Among those, there is a `default` channel which will be used when you don't specify the channel. At startup, this default channel is a [ChannelType::OrderedReliable] channel.
+/// By default, when starting an [Endpoint], Quintet creates 1 channel instance of each [ChannelType](crate::shared::channels::ChannelType), each with their own [ChannelId].
+/// Among those, there is a `default` channel which will be used when you don't specify the channel. At startup, this default channel is a [ChannelType::OrderedReliable](crate::shared::channels::ChannelType::OrderedReliable) channel.
 pub struct Endpoint {
     clients: HashMap<ClientId, ClientConnection>,
     client_id_gen: ClientId,
+    groups: HashMap<String, HashSet<ClientId>>,
+    blob_id_gen: BlobId,
 
-    opened_channels: HashMap<ChannelId, ChannelType>,
+    opened_channels: HashMap<ChannelId, ChannelConfig>,
     available_channel_ids: BTreeSet<ChannelId>,
     default_channel: Option<ChannelId>,
 
@@ -245,6 +350,10 @@ pub struct Endpoint {
 
     pub(crate) from_async_server_recv: mpsc::Receiver<ServerAsyncMessage>,
 
+    admission_validator: Option<AdmissionValidator>,
+
+    capture: Option<CaptureRecorder>,
+
     stats: EndpointStats,
 }
 
@@ -278,11 +387,15 @@ impl Endpoint {
         Self {
             clients: HashMap::new(),
             client_id_gen: 0,
+            groups: HashMap::new(),
+            blob_id_gen: 0,
             opened_channels: HashMap::new(),
             default_channel: None,
             available_channel_ids: (0..255).collect(),
             close_sender: endpoint_close_send,
             from_async_server_recv,
+            admission_validator: None,
+            capture: None,
             stats: default(),
         }
     }
@@ -339,6 +452,10 @@ impl Endpoint {
             Some(client) => match client.bytes_from_client_recv.try_recv() {
                 Ok(msg) => {
                     self.stats.received_messages_count += 1;
+                    client.channels_stats.record_received(msg.0, msg.1.len());
+                    if let Some(capture) = &self.capture {
+                        capture.record(CaptureDirection::Received, Some(client_id), msg.0, &msg.1);
+                    }
                     Ok(Some(msg))
                 }
                 Err(err) => match err {
@@ -413,6 +530,57 @@ impl Endpoint {
         }
     }
 
+    /// Sends a [`RoutedMessage`] to the specified client, on the channel of its route
+    ///
+    /// Will return an [`Err`] if:
+    /// - the message can not be sent by the server
+    /// - or if the message is larger than the maximum size of its route
+    /// - or if the channel does not exist/is closed
+    /// - or if the client is disconnected
+    /// - or if a serialization error occurs
+    /// - (or if the message queue is full)
+    pub fn send_typed<T: RoutedMessage>(
+        &self,
+        client_id: ClientId,
+        message: T,
+    ) -> Result<(), QuintetError> {
+        let (channel_id, payload) = serialize_routed(&message, MessageDirection::ServerToClient)?;
+        self.send_payload_on(client_id, channel_id, payload)
+    }
+
+    /// [`Endpoint::send_typed`] that logs the error instead of returning a result.
+    pub fn try_send_typed<T: RoutedMessage>(&self, client_id: ClientId, message: T) {
+        match self.send_typed(client_id, message) {
+            Ok(_) => {}
+            Err(err) => error!("try_send_typed: {}", err),
+        }
+    }
+
+    /// Sends a [`RoutedMessage`] to all the provided clients, on the channel of its route. See [Endpoint::send_typed]
+    pub fn send_group_typed<'a, I: Iterator<Item = &'a ClientId>, T: RoutedMessage>(
+        &self,
+        client_ids: I,
+        message: T,
+    ) -> Result<(), QuintetError> {
+        let (channel_id, payload) = serialize_routed(&message, MessageDirection::ServerToClient)?;
+        for id in client_ids {
+            self.send_payload_on(*id, channel_id, payload.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Same as [Endpoint::send_group_typed] but will log the error instead of returning it
+    pub fn try_send_group_typed<'a, I: Iterator<Item = &'a ClientId>, T: RoutedMessage>(
+        &self,
+        client_ids: I,
+        message: T,
+    ) {
+        match self.send_group_typed(client_ids, message) {
+            Ok(_) => {}
+            Err(err) => error!("try_send_group_typed: {}", err),
+        }
+    }
+
     /// Same as [Endpoint::send_group_message_on] but on the default channel
     pub fn send_group_message<'a, I: Iterator<Item = &'a ClientId>, T: BorshSerialize>(
         &self,
@@ -486,6 +654,139 @@ impl Endpoint {
         }
     }
 
+    /// Creates an empty named group of clients.
+    ///
+    /// Clients are added to groups with [Endpoint::add_client_to_group], and removed from all their groups when they disconnect. Will return an [`Err`] if a group with the same name already exists.
+    pub fn create_group<S: Into<String>>(&mut self, group: S) -> Result<(), QuintetError> {
+        let group = group.into();
+        if self.groups.contains_key(&group) {
+            return Err(QuintetError::GroupAlreadyExists(group));
+        }
+        self.groups.insert(group, HashSet::new());
+        Ok(())
+    }
+
+    /// Removes a group, without disconnecting its clients. Returns the clients which were in the group.
+    pub fn remove_group(&mut self, group: &str) -> Result<HashSet<ClientId>, QuintetError> {
+        self.groups
+            .remove(group)
+            .ok_or_else(|| QuintetError::UnknownGroup(group.to_string()))
+    }
+
+    /// Adds a connected client to a group. Returns false if the client already was in the group.
+    ///
+    /// Will return an [`Err`] if the group does not exist or if the client is not connected.
+    pub fn add_client_to_group(
+        &mut self,
+        group: &str,
+        client_id: ClientId,
+    ) -> Result<bool, QuintetError> {
+        if !self.clients.contains_key(&client_id) {
+            return Err(QuintetError::UnknownClient(client_id));
+        }
+        match self.groups.get_mut(group) {
+            Some(members) => Ok(members.insert(client_id)),
+            None => Err(QuintetError::UnknownGroup(group.to_string())),
+        }
+    }
+
+    /// Removes a client from a group. Returns false if the client was not in the group.
+    pub fn remove_client_from_group(
+        &mut self,
+        group: &str,
+        client_id: ClientId,
+    ) -> Result<bool, QuintetError> {
+        match self.groups.get_mut(group) {
+            Some(members) => Ok(members.remove(&client_id)),
+            None => Err(QuintetError::UnknownGroup(group.to_string())),
+        }
+    }
+
+    /// Returns the clients in a group, or [`None`] if the group does not exist
+    pub fn group_members(&self, group: &str) -> Option<&HashSet<ClientId>> {
+        self.groups.get(group)
+    }
+
+    /// Returns the names of all the groups
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.keys().map(|group| group.as_str())
+    }
+
+    /// Returns the names of the groups a client is in
+    pub fn client_groups(&self, client_id: ClientId) -> impl Iterator<Item = &str> {
+        self.groups
+            .iter()
+            .filter(move |(_, members)| members.contains(&client_id))
+            .map(|(group, _)| group.as_str())
+    }
+
+    /// Same as [Endpoint::broadcast_group_message_on] but on the default channel
+    pub fn broadcast_group_message<T: BorshSerialize>(
+        &self,
+        group: &str,
+        message: T,
+    ) -> Result<(), QuintetError> {
+        match self.default_channel {
+            Some(channel) => self.broadcast_group_message_on(group, channel, message),
+            None => Err(QuintetError::NoDefaultChannel),
+        }
+    }
+
+    /// Sends the message to all the clients in a group on the specified channel.
+    ///
+    /// Will return an [`Err`] if:
+    /// - the group does not exist
+    /// - or if the channel does not exist/is closed
+    /// - or if a serialization error occurs
+    /// - (or if a message queue is full)
+    pub fn broadcast_group_message_on<T: BorshSerialize, C: Into<ChannelId>>(
+        &self,
+        group: &str,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), QuintetError> {
+        match borsh::to_vec(&message) {
+            Ok(payload) => self.broadcast_group_payload_on(group, channel_id, payload),
+            Err(_) => Err(QuintetError::Serialization),
+        }
+    }
+
+    /// Same as [Endpoint::broadcast_group_message_on] but will log the error instead of returning it
+    pub fn try_broadcast_group_message_on<T: BorshSerialize, C: Into<ChannelId>>(
+        &self,
+        group: &str,
+        channel_id: C,
+        message: T,
+    ) {
+        match self.broadcast_group_message_on(group, channel_id, message) {
+            Ok(_) => {}
+            Err(err) => error!("try_broadcast_group_message_on: {}", err),
+        }
+    }
+
+    /// Sends the payload to all the clients in a group on the specified channel.
+    ///
+    /// Will return an [`Err`] if:
+    /// - the group does not exist
+    /// - or if the channel does not exist/is closed
+    /// - (or if a message queue is full)
+    pub fn broadcast_group_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &self,
+        group: &str,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), QuintetError> {
+        let Some(members) = self.groups.get(group) else {
+            return Err(QuintetError::UnknownGroup(group.to_string()));
+        };
+        let payload: Bytes = payload.into();
+        let channel_id = channel_id.into();
+        for client_id in members {
+            self.send_payload_on(*client_id, channel_id, payload.clone())?;
+        }
+        Ok(())
+    }
+
     /// Same as [Endpoint::broadcast_message_on] but on the default channel
     pub fn broadcast_message<T: BorshSerialize>(&self, message: T) -> Result<(), QuintetError> {
         match self.default_channel {
@@ -551,12 +852,15 @@ impl Endpoint {
     ) -> Result<(), QuintetError> {
         let payload: Bytes = payload.into();
         let channel_id = channel_id.into();
-        for client_connection in self.clients.values() {
+        for (client_id, client_connection) in self.clients.iter() {
             match client_connection.channels.get(channel_id as usize) {
                 Some(Some(channel)) => channel.send_payload(payload.clone())?,
                 Some(None) => return Err(QuintetError::ChannelClosed),
                 None => return Err(QuintetError::UnknownChannel(channel_id)),
             };
+            if let Some(capture) = &self.capture {
+                capture.record(CaptureDirection::Sent, Some(*client_id), channel_id, &payload);
+            }
         }
         Ok(())
     }
@@ -608,7 +912,14 @@ impl Endpoint {
         let channel_id = channel_id.into();
         if let Some(client_connection) = self.clients.get(&client_id) {
             match client_connection.channels.get(channel_id as usize) {
-                Some(Some(channel)) => channel.send_payload(payload.into()),
+                Some(Some(channel)) => {
+                    let payload: Bytes = payload.into();
+                    channel.send_payload(payload.clone())?;
+                    if let Some(capture) = &self.capture {
+                        capture.record(CaptureDirection::Sent, Some(client_id), channel_id, &payload);
+                    }
+                    Ok(())
+                }
                 Some(None) => return Err(QuintetError::ChannelClosed),
                 None => return Err(QuintetError::UnknownChannel(channel_id)),
             }
@@ -638,14 +949,61 @@ impl Endpoint {
         }
     }
 
+    /// Sends a large payload to the specified client, in chunks, on a dedicated stream.
+    ///
+    /// Unlike a message sent on a channel, a blob does not delay the channels messages sent after it. The client gets a [`crate::client::connection::BlobProgressEvent`] as the blob is received, then a [`crate::client::connection::BlobReceivedEvent`] with its content. `tag` is not interpreted by Quintet and can be used to tell blobs apart.
+    ///
+    /// Returns the id of the blob. Will return an [`Err`] if:
+    /// - the client is disconnected
+    /// - the payload is larger than [`MAX_BLOB_LEN`]
+    /// - (or if the connection internal queue is full)
+    pub fn send_blob<T: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        tag: u32,
+        payload: T,
+    ) -> Result<BlobId, QuintetError> {
+        let payload: Bytes = payload.into();
+        if payload.len() > MAX_BLOB_LEN {
+            return Err(QuintetError::BlobTooLarge(payload.len()));
+        }
+        let Some(client_connection) = self.clients.get(&client_id) else {
+            return Err(QuintetError::UnknownClient(client_id));
+        };
+        let blob_id = self.blob_id_gen;
+        match client_connection
+            .to_channels_send
+            .try_send(ChannelSyncMessage::SendBlob {
+                blob_id,
+                tag,
+                payload,
+            }) {
+            Ok(_) => {
+                self.blob_id_gen += 1;
+                Ok(blob_id)
+            }
+            Err(TrySendError::Full(_)) => Err(QuintetError::FullQueue),
+            Err(TrySendError::Closed(_)) => Err(QuintetError::InternalChannelClosed),
+        }
+    }
+
     /// Disconnect a specific client. Removes it from the server.
     ///
     /// Disconnecting a client immediately prevents new messages from being sent on its connection and signal the underlying connection to closes all its background tasks. Before trully closing, the connection will wait for all buffered messages in all its opened channels to be properly sent according to their respective channel type.
     ///
+    /// The `reason` code and message are sent to the client in the QUIC close frame, and received in its [`crate::client::connection::ConnectionLostEvent`].
+    ///
     /// This may fail if no client if found for client_id, or if the client is already disconnected.
-    pub fn disconnect_client(&mut self, client_id: ClientId) -> Result<(), QuintetError> {
+    pub fn disconnect_client(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+    ) -> Result<(), QuintetError> {
+        for members in self.groups.values_mut() {
+            members.remove(&client_id);
+        }
         match self.clients.remove(&client_id) {
-            Some(client_connection) => match client_connection.close_sender.send(()) {
+            Some(client_connection) => match client_connection.close_sender.send(reason) {
                 Ok(_) => Ok(()),
                 Err(_) => Err(QuintetError::ClientAlreadyDisconnected(client_id)),
             },
@@ -654,8 +1012,8 @@ impl Endpoint {
     }
 
     /// Same as [Endpoint::disconnect_client] but errors are logged instead of returned
-    pub fn try_disconnect_client(&mut self, client_id: ClientId) {
-        match self.disconnect_client(client_id) {
+    pub fn try_disconnect_client(&mut self, client_id: ClientId, reason: DisconnectReason) {
+        match self.disconnect_client(client_id, reason) {
             Ok(_) => (),
             Err(err) => error!(
                 "Failed to properly disconnect client {}: {}",
@@ -664,14 +1022,34 @@ impl Endpoint {
         }
     }
 
-    /// Calls [Endpoint::disconnect_client] on all connected clients
-    pub fn disconnect_all_clients(&mut self) -> Result<(), QuintetError> {
+    /// Calls [Endpoint::disconnect_client] on all connected clients, with the same `reason`
+    pub fn disconnect_all_clients(&mut self, reason: DisconnectReason) -> Result<(), QuintetError> {
         for client_id in self.clients.keys().cloned().collect::<Vec<ClientId>>() {
-            self.disconnect_client(client_id)?;
+            self.disconnect_client(client_id, reason.clone())?;
         }
         Ok(())
     }
 
+    /// Sets the validator deciding whether joining clients are admitted, based on the [`JoinRequest`] they sent.
+    ///
+    /// Rejected clients never get a [`ConnectionEvent`] and receive the rejection reason in their [`crate::client::connection::ConnectionFailedEvent`]. Without a validator, every client is admitted.
+    pub fn set_admission_validator<F>(&mut self, validator: F)
+    where
+        F: Fn(&JoinRequest, &AdmissionContext) -> Admission + Send + Sync + 'static,
+    {
+        self.admission_validator = Some(Arc::new(validator));
+    }
+
+    /// Removes the admission validator, every client will be admitted
+    pub fn clear_admission_validator(&mut self) {
+        self.admission_validator = None;
+    }
+
+    /// Returns the [`JoinRequest`] sent by a client when it connected, if connected.
+    pub fn join_request(&self, client_id: ClientId) -> Option<&JoinRequest> {
+        self.clients.get(&client_id).map(|client| &client.join_request)
+    }
+
     /// Returns statistics about a client if connected.
     pub fn connection_stats(&self, client_id: ClientId) -> Option<ConnectionStats> {
         match &self.clients.get(&client_id) {
@@ -680,22 +1058,76 @@ impl Endpoint {
         }
     }
 
+    /// Returns how many messages received from a client were dropped by [`ChannelType::UnreliableSequenced`](crate::shared::channels::ChannelType::UnreliableSequenced) channels because they were older than an already received message.
+    ///
+    /// Returns [`None`] if the client is not connected.
+    pub fn dropped_stale_messages_count(&self, client_id: ClientId) -> Option<u64> {
+        self.clients.get(&client_id).map(|client| {
+            client
+                .recv_counters
+                .dropped_stale_messages
+                .load(Ordering::Relaxed)
+        })
+    }
+
+    /// Returns the per channel [`TrafficStats`] of a client, and its round-trip time.
+    ///
+    /// Returns [`None`] if the client is not connected.
+    pub fn traffic_stats(&self, client_id: ClientId) -> Option<TrafficStats> {
+        self.clients.get(&client_id).map(|client| {
+            client
+                .channels_stats
+                .traffic_stats(Some(client.connection_handle.rtt()))
+        })
+    }
+
+    /// Returns the [`ChannelStats`] of a channel of a client, if the client is connected and there was some traffic on this channel.
+    pub fn channel_stats(&self, client_id: ClientId, channel_id: ChannelId) -> Option<ChannelStats> {
+        self.clients
+            .get(&client_id)
+            .and_then(|client| client.channels_stats.channel(channel_id))
+    }
+
+    /// Starts recording every payload sent and received by this endpoint to a capture file at `path`.
+    ///
+    /// Replaces (and flushes) the capture in progress, if any. The capture can be read back with [`crate::shared::capture::read_capture`].
+    pub fn start_capture<P: AsRef<Path>>(&mut self, path: P) -> Result<(), QuintetError> {
+        self.capture = Some(CaptureRecorder::create(path)?);
+        Ok(())
+    }
+
+    /// Stops the capture in progress, if any, and flushes it to its file
+    pub fn stop_capture(&mut self) -> Result<(), QuintetError> {
+        match self.capture.take() {
+            Some(capture) => capture.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if this endpoint is recording a capture
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
     /// Returns statistics about the server's endpoint
     pub fn endpoint_stats(&self) -> &EndpointStats {
         &self.stats
     }
 
-    /// Opens a channel of the requested [ChannelType] and returns its [ChannelId].
+    /// Opens a channel of the requested [ChannelType](crate::shared::channels::ChannelType) and returns its [ChannelId]. A [ChannelConfig] can be given instead to also set the channel's priority.
     ///
     /// If no channels were previously opened, the opened channel will be the new default channel.
     ///
     /// Can fail if the Endpoint is closed.
-    pub fn open_channel(&mut self, channel_type: ChannelType) -> Result<ChannelId, QuintetError> {
+    pub fn open_channel(
+        &mut self,
+        channel: impl Into<ChannelConfig>,
+    ) -> Result<ChannelId, QuintetError> {
         let channel_id = match self.available_channel_ids.pop_first() {
             Some(channel_id) => channel_id,
             None => return Err(QuintetError::MaxChannelsCountReached),
         };
-        match self.create_channel(channel_id, channel_type) {
+        match self.create_channel(channel_id, channel.into()) {
             Ok(channel_id) => Ok(channel_id),
             Err(err) => {
                 // Reinsert the popped channel id
@@ -707,7 +1139,7 @@ impl Endpoint {
 
     /// Closes the channel with the corresponding [ChannelId].
     ///
-    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [ChannelType], before fully closing.
+    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [ChannelType](crate::shared::channels::ChannelType), before fully closing.
     ///
     /// If the closed channel is the current default channel, the default channel gets set to `None`.
     ///
@@ -742,12 +1174,12 @@ impl Endpoint {
     fn create_channel(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelType,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, QuintetError> {
         for (_, client_connection) in self.clients.iter_mut() {
-            client_connection.create_channel(channel_id, channel_type)?;
+            client_connection.create_channel(channel_id, channel_config)?;
         }
-        self.opened_channels.insert(channel_id, channel_type);
+        self.opened_channels.insert(channel_id, channel_config);
         if self.default_channel.is_none() {
             self.default_channel = Some(channel_id);
         }
@@ -765,8 +1197,21 @@ impl Endpoint {
         &mut self,
         mut connection: ClientConnection,
     ) -> Result<ClientId, QuintetError> {
-        for (channel_id, channel_type) in self.opened_channels.iter() {
-            if let Err(err) = connection.create_channel(*channel_id, *channel_type) {
+        if let Some(validator) = &self.admission_validator {
+            let context = AdmissionContext {
+                remote_address: connection.connection_handle.remote_address(),
+                connected_clients: self.clients.len(),
+            };
+            if let Admission::Reject(reason) = validator(&connection.join_request, &context) {
+                let _ = connection
+                    .to_connection_send
+                    .try_send(ServerSyncMessage::ClientRejected(reason.clone()));
+                return Err(QuintetError::ClientRejected(reason));
+            }
+        }
+
+        for (channel_id, channel_config) in self.opened_channels.iter() {
+            if let Err(err) = connection.create_channel(*channel_id, *channel_config) {
                 connection.try_close();
                 return Err(err);
             };
@@ -862,12 +1307,13 @@ impl QuintetServer {
             server_cert.cert_chain.clone(),
             server_cert.priv_key.clone(),
         )?;
-        Arc::get_mut(&mut server_config.transport)
-            .ok_or(QuintetError::LockAcquisitionFailure)?
-            .keep_alive_interval(Some(DEFAULT_KEEP_ALIVE_INTERVAL_S));
+        server_config.transport_config(Arc::new(
+            config.transport_config.to_quinn_transport_config()?,
+        ));
 
-        let (to_sync_server_send, from_async_server_recv) =
-            mpsc::channel::<ServerAsyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        let (to_sync_server_send, from_async_server_recv) = mpsc::channel::<ServerAsyncMessage>(
+            config.transport_config.internal_message_channel_size,
+        );
         let (endpoint_close_send, endpoint_close_recv) =
             broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
 
@@ -877,6 +1323,9 @@ impl QuintetServer {
             endpoint_task(
                 server_config,
                 config.local_bind_addr,
+                config.transport,
+                config.link_conditioner,
+                config.transport_config,
                 to_sync_server_send.clone(),
                 endpoint_close_recv,
             )
@@ -884,8 +1333,8 @@ impl QuintetServer {
         });
 
         let mut endpoint = Endpoint::new(endpoint_close_send, from_async_server_recv);
-        for channel_type in channels_config.configs() {
-            endpoint.open_channel(*channel_type)?;
+        for channel_config in channels_config.configs() {
+            endpoint.open_channel(*channel_config)?;
         }
 
         self.endpoint = Some(endpoint);
@@ -900,7 +1349,7 @@ impl QuintetServer {
         match self.endpoint.take() {
             Some(mut endpoint) => {
                 endpoint.close_incoming_connections_handler()?;
-                endpoint.disconnect_all_clients()
+                endpoint.disconnect_all_clients(DisconnectReason::new(0, "server stopped"))
             }
             None => Err(QuintetError::EndpointAlreadyClosed),
         }
@@ -918,10 +1367,13 @@ impl QuintetServer {
 async fn endpoint_task(
     endpoint_config: ServerConfig,
     endpoint_adr: SocketAddr,
+    transport: EndpointTransport,
+    link_conditioner: Option<LinkConditionerConfig>,
+    transport_config: TransportConfig,
     to_sync_server_send: mpsc::Sender<ServerAsyncMessage>,
     mut endpoint_close_recv: broadcast::Receiver<()>,
 ) {
-    let endpoint = QuinnEndpoint::server(endpoint_config, endpoint_adr)
+    let endpoint = bind_endpoint(transport, endpoint_adr, Some(endpoint_config))
         .expect("Failed to create the endpoint");
     // Handle incoming connections/clients.
     tokio::select! {
@@ -934,10 +1386,14 @@ async fn endpoint_task(
                     Err(err) => error!("An incoming connection failed: {}", err),
                     Ok(connection) => {
                         let to_sync_server_send = to_sync_server_send.clone();
+                        let link_conditioner = link_conditioner.clone();
+                        let transport_config = transport_config.clone();
                         tokio::spawn(async move {
                             client_connection_task(
                                 connection,
-                                to_sync_server_send
+                                to_sync_server_send,
+                                link_conditioner,
+                                transport_config
                             )
                             .await
                         });
@@ -951,17 +1407,47 @@ async fn endpoint_task(
 async fn client_connection_task(
     connection_handle: quinn::Connection,
     to_sync_server_send: mpsc::Sender<ServerAsyncMessage>,
+    link_conditioner: Option<LinkConditionerConfig>,
+    transport_config: TransportConfig,
 ) {
     let (client_close_send, client_close_recv) =
         broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
     let (bytes_from_client_send, bytes_from_client_recv) =
-        mpsc::channel::<(ChannelId, Bytes)>(DEFAULT_MESSAGE_QUEUE_SIZE);
+        mpsc::channel::<(ChannelId, Bytes)>(transport_config.message_queue_size);
     let (to_connection_send, mut from_sync_server_recv) =
-        mpsc::channel::<ServerSyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        mpsc::channel::<ServerSyncMessage>(transport_config.internal_message_channel_size);
     let (from_channels_send, from_channels_recv) =
-        mpsc::channel::<ChannelAsyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        mpsc::channel::<ChannelAsyncMessage>(transport_config.internal_message_channel_size);
     let (to_channels_send, to_channels_recv) =
-        mpsc::channel::<ChannelSyncMessage>(DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE);
+        mpsc::channel::<ChannelSyncMessage>(transport_config.internal_message_channel_size);
+    let recv_counters = Arc::new(ChannelsRecvCounters::default());
+
+    // The client starts by sending its join request, before being admitted
+    let join_request = tokio::time::timeout(
+        transport_config.join_request_timeout,
+        receive_join_request(&connection_handle),
+    )
+    .await;
+    let (join_request, join_response_send) = match join_request {
+        Ok(Some(join_request)) => join_request,
+        Ok(None) => {
+            info!(
+                "Connection from {} did not send a valid join request",
+                connection_handle.remote_address()
+            );
+            connection_handle.close(VarInt::from_u32(0), "invalid join request".as_bytes());
+            return;
+        }
+        Err(_) => {
+            info!(
+                "Connection from {} did not send its join request in time",
+                connection_handle.remote_address()
+            );
+            let reason = DisconnectReason::new(0, "join request timed out");
+            connection_handle.close(VarInt::from_u32(reason.code), reason.message.as_bytes());
+            return;
+        }
+    };
 
     // Signal the sync server of this new connection
     to_sync_server_send
@@ -970,6 +1456,10 @@ async fn client_connection_task(
             channels: Vec::new(),
             bytes_from_client_recv,
             close_sender: client_close_send.clone(),
+            recv_counters: recv_counters.clone(),
+            channels_stats: Arc::new(ChannelsStats::default()),
+            join_request,
+            message_queue_size: transport_config.message_queue_size,
             to_connection_send,
             from_channels_recv,
             to_channels_send,
@@ -980,6 +1470,12 @@ async fn client_connection_task(
     // Wait for the sync server response before spawning connection tasks.
     match from_sync_server_recv.recv().await {
         Some(ServerSyncMessage::ClientConnectedAck(client_id)) => {
+            if !send_join_response(join_response_send, JoinResponse::Accepted).await {
+                error!(
+                    "Failed to send join response to client {}",
+                    client_id
+                );
+            }
             info!(
                 "New connection from {}, client_id: {}",
                 connection_handle.remote_address(),
@@ -998,12 +1494,13 @@ async fn client_connection_task(
                 let conn = connection_handle.clone();
                 let to_sync_server = to_sync_server_send.clone();
                 tokio::spawn(async move {
-                    let _conn_err = conn.closed().await;
-                    info!("Connection {} closed: {}", client_id, _conn_err);
+                    let conn_err = conn.closed().await;
+                    info!("Connection {} closed: {}", client_id, conn_err);
+                    let reason = DisconnectReason::from_connection_error(&conn_err);
                     // If we requested the connection to close, channel may have been closed already.
                     if !to_sync_server.is_closed() {
                         to_sync_server
-                            .send(ServerAsyncMessage::ClientConnectionClosed(client_id))
+                            .send(ServerAsyncMessage::ClientConnectionClosed(client_id, reason))
                             .await
                             .expect("Failed to signal connection lost in async connection");
                     }
@@ -1015,6 +1512,9 @@ async fn client_connection_task(
                 client_id,
                 client_close_recv.resubscribe(),
                 bytes_from_client_send,
+                from_channels_send.clone(),
+                recv_counters,
+                transport_config.max_reliable_frame_len,
             );
 
             spawn_send_channels_tasks(
@@ -1022,15 +1522,120 @@ async fn client_connection_task(
                 client_close_recv,
                 to_channels_recv,
                 from_channels_send,
+                link_conditioner.map(|config| config.for_connection(client_id)),
+                transport_config.max_reliable_frame_len,
+            );
+        }
+        Some(ServerSyncMessage::ClientRejected(reason)) => {
+            info!(
+                "Connection from {} refused: {}",
+                connection_handle.remote_address(),
+                reason
             );
+            send_join_response(join_response_send, JoinResponse::Rejected(reason)).await;
+            connection_handle.close(VarInt::from_u32(0), "rejected".as_bytes());
         }
-        _ => info!(
+        None => info!(
             "Connection from {} refused",
             connection_handle.remote_address()
         ),
     }
 }
 
+/// Event raised by a server, as returned by [`handle::ServerHandle::poll`]. The Bevy plugin sends each variant as its own Bevy event
+#[derive(Debug, Clone)]
+pub enum ServerEvent {
+    /// A client just connected
+    Connection(ConnectionEvent),
+    /// A client is considered disconnected
+    ConnectionLost(ConnectionLostEvent),
+    /// A blob sent by a client is being received
+    BlobProgress(BlobProgressEvent),
+    /// A blob sent by a client was fully received
+    BlobReceived(BlobReceivedEvent),
+}
+
+/// Receive messages from the async server tasks and update the sync endpoint, passing each raised event to `on_event`
+pub(crate) fn update_endpoint(endpoint: &mut Endpoint, mut on_event: impl FnMut(ServerEvent)) {
+    while let Ok(message) = endpoint.from_async_server_recv.try_recv() {
+        match message {
+            ServerAsyncMessage::ClientConnected(connection) => {
+                match endpoint.handle_connection(connection) {
+                    Ok(client_id) => {
+                        endpoint.stats.connect_count += 1;
+                        on_event(ServerEvent::Connection(ConnectionEvent { id: client_id }));
+                    }
+                    Err(QuintetError::ClientRejected(reason)) => {
+                        info!("A client was refused: {}", reason);
+                    }
+                    Err(err) => {
+                        error!("Failed to handle connection of a client: {}", err);
+                    }
+                };
+            }
+            ServerAsyncMessage::ClientConnectionClosed(client_id, reason) => {
+                match endpoint.clients.contains_key(&client_id) {
+                    true => {
+                        endpoint.stats.disconnect_count += 1;
+                        endpoint.try_disconnect_client(client_id, DisconnectReason::default());
+                        on_event(ServerEvent::ConnectionLost(ConnectionLostEvent {
+                            id: client_id,
+                            reason,
+                        }));
+                    }
+                    false => (),
+                }
+            }
+        }
+    }
+
+    let mut lost_clients = HashSet::new();
+    for (client_id, connection) in endpoint.clients.iter_mut() {
+        while let Ok(message) = connection.from_channels_recv.try_recv() {
+            match message {
+                ChannelAsyncMessage::LostConnection => {
+                    if !lost_clients.contains(client_id) {
+                        lost_clients.insert(*client_id);
+                        on_event(ServerEvent::ConnectionLost(ConnectionLostEvent {
+                            id: *client_id,
+                            reason: None,
+                        }));
+                    }
+                }
+                ChannelAsyncMessage::BlobProgress {
+                    blob_id,
+                    tag,
+                    received,
+                    total,
+                } => {
+                    on_event(ServerEvent::BlobProgress(BlobProgressEvent {
+                        id: *client_id,
+                        blob_id,
+                        tag,
+                        received,
+                        total,
+                    }));
+                }
+                ChannelAsyncMessage::BlobReceived {
+                    blob_id,
+                    tag,
+                    payload,
+                } => {
+                    on_event(ServerEvent::BlobReceived(BlobReceivedEvent {
+                        id: *client_id,
+                        blob_id,
+                        tag,
+                        payload,
+                    }));
+                }
+            }
+        }
+    }
+    for client_id in lost_clients {
+        endpoint.try_disconnect_client(client_id, DisconnectReason::default());
+    }
+}
+
 /// Receive messages from the async server tasks and update the sync server.
 ///
 /// This system generates the server's bevy events
@@ -1038,50 +1643,24 @@ pub fn update_sync_server(
     mut server: ResMut<QuintetServer>,
     mut connection_events: EventWriter<ConnectionEvent>,
     mut connection_lost_events: EventWriter<ConnectionLostEvent>,
+    mut blob_progress_events: EventWriter<BlobProgressEvent>,
+    mut blob_received_events: EventWriter<BlobReceivedEvent>,
 ) {
     if let Some(endpoint) = server.get_endpoint_mut() {
-        while let Ok(message) = endpoint.from_async_server_recv.try_recv() {
-            match message {
-                ServerAsyncMessage::ClientConnected(connection) => {
-                    match endpoint.handle_connection(connection) {
-                        Ok(client_id) => {
-                            endpoint.stats.connect_count += 1;
-                            connection_events.send(ConnectionEvent { id: client_id });
-                        }
-                        Err(_) => {
-                            error!("Failed to handle connection of a client");
-                        }
-                    };
-                }
-                ServerAsyncMessage::ClientConnectionClosed(client_id) => {
-                    match endpoint.clients.contains_key(&client_id) {
-                        true => {
-                            endpoint.stats.disconnect_count += 1;
-                            endpoint.try_disconnect_client(client_id);
-                            connection_lost_events.send(ConnectionLostEvent { id: client_id });
-                        }
-                        false => (),
-                    }
-                }
+        update_endpoint(endpoint, |event| match event {
+            ServerEvent::Connection(event) => {
+                connection_events.send(event);
             }
-        }
-
-        let mut lost_clients = HashSet::new();
-        for (client_id, connection) in endpoint.clients.iter_mut() {
-            while let Ok(message) = connection.from_channels_recv.try_recv() {
-                match message {
-                    ChannelAsyncMessage::LostConnection => {
-                        if !lost_clients.contains(client_id) {
-                            lost_clients.insert(*client_id);
-                            connection_lost_events.send(ConnectionLostEvent { id: *client_id });
-                        }
-                    }
-                }
+            ServerEvent::ConnectionLost(event) => {
+                connection_lost_events.send(event);
             }
-        }
-        for client_id in lost_clients {
-            endpoint.try_disconnect_client(client_id);
-        }
+            ServerEvent::BlobProgress(event) => {
+                blob_progress_events.send(event);
+            }
+            ServerEvent::BlobReceived(event) => {
+                blob_received_events.send(event);
+            }
+        });
     }
 }
 
@@ -1106,7 +1685,9 @@ impl Default for QuintetServerPlugin {
 impl Plugin for QuintetServerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ConnectionEvent>()
-            .add_event::<ConnectionLostEvent>();
+            .add_event::<ConnectionLostEvent>()
+            .add_event::<BlobProgressEvent>()
+            .add_event::<BlobReceivedEvent>();
 
         if !self.initialize_later {
             app.init_resource::<QuintetServer>();
@@ -1,6 +1,6 @@
 use std::{
     fs::{self, File},
-    io::BufReader,
+    io::{BufRead, BufReader},
     path::Path,
 };
 
@@ -16,7 +16,7 @@ pub enum CertOrigin {
         /// Contains the hostname used when generating the certificate.
         server_hostname: String,
     },
-    /// Indicates that the certificate was loaded from a file.
+    /// Indicates that the certificate was loaded from a file or from PEM strings.
     Loaded,
 }
 
@@ -47,6 +47,13 @@ pub enum CertificateRetrievalMode {
         /// Used as the subject of the certificate.
         server_hostname: String,
     },
+    /// Load cert & key directly from their PEM form, for example from a configuration instead of files.
+    FromPem {
+        /// The certificate chain in PEM form
+        cert: String,
+        /// The private key in PEM form
+        key: String,
+    },
 }
 
 /// Represents a server certificate.
@@ -59,23 +66,27 @@ pub struct ServerCertificate {
     pub fingerprint: CertificateFingerprint,
 }
 
-fn read_certs_from_files(
-    cert_file: &String,
-    key_file: &String,
+fn read_certs<C: BufRead, K: BufRead>(
+    cert_chain_reader: &mut C,
+    key_reader: &mut K,
 ) -> Result<ServerCertificate, QuintetError> {
-    let mut cert_chain_reader = BufReader::new(File::open(cert_file)?);
-    let cert_chain: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut cert_chain_reader)?
+    let cert_chain: Vec<rustls::Certificate> = rustls_pemfile::certs(cert_chain_reader)?
         .into_iter()
         .map(rustls::Certificate)
         .collect();
+    if cert_chain.is_empty() {
+        return Err(QuintetError::InvalidPem("no certificate found".to_string()));
+    }
 
-    let mut key_reader = BufReader::new(File::open(key_file)?);
-    let mut keys = rustls_pemfile::pkcs8_private_keys(&mut key_reader)?;
-
-    assert_eq!(keys.len(), 1);
+    let mut keys = rustls_pemfile::pkcs8_private_keys(key_reader)?;
+    if keys.len() != 1 {
+        return Err(QuintetError::InvalidPem(format!(
+            "expected 1 PKCS8 private key, found {}",
+            keys.len()
+        )));
+    }
     let priv_key = rustls::PrivateKey(keys.remove(0));
 
-    assert!(cert_chain.len() >= 1);
     let fingerprint = CertificateFingerprint::from(&cert_chain[0]);
 
     Ok(ServerCertificate {
@@ -85,6 +96,15 @@ fn read_certs_from_files(
     })
 }
 
+fn read_certs_from_files(
+    cert_file: &String,
+    key_file: &String,
+) -> Result<ServerCertificate, QuintetError> {
+    let mut cert_chain_reader = BufReader::new(File::open(cert_file)?);
+    let mut key_reader = BufReader::new(File::open(key_file)?);
+    read_certs(&mut cert_chain_reader, &mut key_reader)
+}
+
 fn write_certs_to_files(
     cert: &rcgen::Certificate,
     cert_file: &String,
@@ -163,5 +183,10 @@ pub(crate) fn retrieve_certificate(
                 Ok(server_cert)
             }
         }
+        CertificateRetrievalMode::FromPem { cert, key } => {
+            let server_cert = read_certs(&mut cert.as_bytes(), &mut key.as_bytes())?;
+            trace!("Successfuly loaded cert and key from PEM");
+            Ok(server_cert)
+        }
     }
 }
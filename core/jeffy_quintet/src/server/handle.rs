@@ -0,0 +1,62 @@
+use std::ops::{Deref, DerefMut};
+
+use tokio::runtime::Runtime;
+
+use crate::server::{update_endpoint, QuintetServer, ServerEvent};
+
+/// A [`QuintetServer`] usable without Bevy, for headless tools, bots or a dedicated server with its own main loop.
+///
+/// It owns the async runtime running the server's tasks. Messages received from the clients and the server's events are only processed during [`ServerHandle::poll`], which should be called regularly. All the [`QuintetServer`] methods (and through it, the [`crate::server::Endpoint`] send/receive methods) are available on the handle.
+pub struct ServerHandle {
+    // Declared before the runtime, to be dropped while the runtime still exists
+    server: QuintetServer,
+    runtime: Runtime,
+}
+
+impl ServerHandle {
+    /// Creates a server handle with its own multi-threaded async runtime. The endpoint still needs to be started with [`QuintetServer::start_endpoint`]
+    pub fn new() -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self::with_runtime(runtime))
+    }
+
+    /// Creates a server handle using the given async runtime
+    pub fn with_runtime(runtime: Runtime) -> Self {
+        Self {
+            server: QuintetServer::new(runtime.handle().clone()),
+            runtime,
+        }
+    }
+
+    /// Returns the async runtime used by the server
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// Receive messages from the async server tasks and update the server, the equivalent of the Bevy plugin's system.
+    ///
+    /// Returns the events raised since the previous poll, in order. Returns no events if the endpoint is not opened.
+    pub fn poll(&mut self) -> Vec<ServerEvent> {
+        let mut events = Vec::new();
+        if let Some(endpoint) = self.server.get_endpoint_mut() {
+            update_endpoint(endpoint, |event| events.push(event));
+        }
+        events
+    }
+}
+
+impl Deref for ServerHandle {
+    type Target = QuintetServer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.server
+    }
+}
+
+impl DerefMut for ServerHandle {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.server
+    }
+}
@@ -1,4 +1,9 @@
-use std::{mem::size_of, time::Duration};
+use std::{
+    fmt,
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
 
 use bevy::{
     ecs::schedule::SystemSet,
@@ -6,12 +11,29 @@ use bevy::{
 };
 use tokio::runtime::Runtime;
 
+/// Admission handshake shared by client & server
+pub mod admission;
+/// Packet capture shared by client & server
+pub mod capture;
 /// Certificate features shared by client & server
 pub mod certificate;
 /// Channel features shared by client & server
 pub mod channels;
 /// Shared error types
 pub mod error;
+/// Network condition simulation shared by client & server
+pub mod link_conditioner;
+/// In-process loopback transport shared by client & server
+pub mod loopback;
+/// Offline replay of packet captures, driving a server or a client endpoint
+#[cfg(all(feature = "client", feature = "server"))]
+pub mod replay;
+/// Typed message routing shared by client & server
+pub mod routing;
+/// Traffic statistics shared by client & server
+pub mod stats;
+/// Transport parameters shared by client & server
+pub mod transport;
 
 /// Default max size of async channels used to hold network messages. 1 async channel per connection.
 pub const DEFAULT_MESSAGE_QUEUE_SIZE: usize = 150;
@@ -25,10 +47,55 @@ pub const DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE: usize = 100;
 /// Default max size of the queues used to transmit close messages for async tasks
 pub(crate) const DEFAULT_KILL_MESSAGE_QUEUE_SIZE: usize = 10;
 
+/// IP address used by endpoints on the [`loopback::EndpointTransport::Loopback`] transport
+pub const LOOPBACK_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
 /// Represents the id of a client on the server.
 pub type ClientId = u64;
 pub(crate) const CLIENT_ID_LEN: usize = size_of::<ClientId>();
 
+/// Application reason given when closing a connection, transmitted to the remote side in the QUIC close frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisconnectReason {
+    /// Application defined reason code
+    pub code: u32,
+    /// Human readable reason
+    pub message: String,
+}
+
+impl DisconnectReason {
+    /// Creates a new reason from an application code and a message
+    pub fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the reason sent by the remote side, if the connection was closed by its application
+    pub(crate) fn from_connection_error(error: &quinn::ConnectionError) -> Option<Self> {
+        match error {
+            quinn::ConnectionError::ApplicationClosed(close) => Some(Self {
+                code: close.error_code.into_inner().try_into().unwrap_or(u32::MAX),
+                message: String::from_utf8_lossy(&close.reason).into_owned(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Default for DisconnectReason {
+    fn default() -> Self {
+        Self::new(0, "closed")
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
 /// Async runtime newtype wrapping the tokio runtime handle. used by both Quintet client and server's async back-ends.
 #[derive(Resource, Deref, DerefMut)]
 pub struct AsyncRuntime(pub(crate) Runtime);
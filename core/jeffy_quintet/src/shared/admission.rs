@@ -0,0 +1,91 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+
+/// Payload sent by a client when connecting, before the server admits it
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct JoinRequest {
+    /// Version of the application protocol spoken by the client
+    pub protocol_version: u32,
+    /// Version of the application build of the client, for display in rejection reasons
+    pub game_version: String,
+    /// Bitset of the optional application features supported by the client
+    pub features: u64,
+    /// Persistent identifier of the player (usually a UUID as `u128`)
+    pub player_id: u128,
+    /// Optional authentication token
+    pub token: Option<String>,
+}
+
+/// Decision of an [`AdmissionValidator`] about a joining client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// The client is admitted and gets a client id
+    Accept,
+    /// The client is refused, with a reason sent back to it
+    Reject(String),
+}
+
+/// Information available to an [`AdmissionValidator`] besides the [`JoinRequest`]
+#[derive(Debug, Clone)]
+pub struct AdmissionContext {
+    /// Address of the joining client
+    pub remote_address: SocketAddr,
+    /// Number of clients currently connected to the endpoint
+    pub connected_clients: usize,
+}
+
+/// Server-provided hook deciding whether a joining client is admitted
+pub type AdmissionValidator = Arc<dyn Fn(&JoinRequest, &AdmissionContext) -> Admission + Send + Sync>;
+
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub(crate) enum JoinResponse {
+    Accepted,
+    Rejected(String),
+}
+
+/// Client side of the join handshake: sends the join request on a new stream and waits for the server decision
+pub(crate) async fn request_join(
+    connection: &quinn::Connection,
+    join_request: &JoinRequest,
+) -> Option<JoinResponse> {
+    let (send, recv) = connection.open_bi().await.ok()?;
+    let mut frame_send = FramedWrite::new(send, LengthDelimitedCodec::new());
+    let payload = borsh::to_vec(join_request).ok()?;
+    frame_send.send(Bytes::from(payload)).await.ok()?;
+
+    let mut frame_recv = FramedRead::new(recv, LengthDelimitedCodec::new());
+    let response_bytes = frame_recv.next().await?.ok()?;
+    borsh::from_slice(&response_bytes).ok()
+}
+
+/// Server side of the join handshake: receives the join request of a new connection
+pub(crate) async fn receive_join_request(
+    connection: &quinn::Connection,
+) -> Option<(JoinRequest, FramedWrite<quinn::SendStream, LengthDelimitedCodec>)> {
+    let (send, recv) = connection.accept_bi().await.ok()?;
+    let mut frame_recv = FramedRead::new(recv, LengthDelimitedCodec::new());
+    let request_bytes = frame_recv.next().await?.ok()?;
+    let join_request = borsh::from_slice(&request_bytes).ok()?;
+    Some((
+        join_request,
+        FramedWrite::new(send, LengthDelimitedCodec::new()),
+    ))
+}
+
+/// Server side of the join handshake: sends the admission decision and waits for it to be delivered
+pub(crate) async fn send_join_response(
+    mut frame_send: FramedWrite<quinn::SendStream, LengthDelimitedCodec>,
+    response: JoinResponse,
+) -> bool {
+    let Ok(payload) = borsh::to_vec(&response) else {
+        return false;
+    };
+    if frame_send.send(Bytes::from(payload)).await.is_err() {
+        return false;
+    }
+    frame_send.into_inner().finish().await.is_ok()
+}
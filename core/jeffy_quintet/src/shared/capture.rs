@@ -0,0 +1,147 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use bevy::log::error;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::shared::{channels::ChannelId, error::QuintetError, ClientId};
+
+/// Bytes written at the start of every capture file
+pub const CAPTURE_MAGIC: [u8; 4] = *b"JQCP";
+/// Version of the capture file format
+pub const CAPTURE_VERSION: u16 = 1;
+
+/// Direction of a captured payload, from the point of view of the recording side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum CaptureDirection {
+    /// The payload was sent by the recording side
+    Sent,
+    /// The payload was received (read) by the recording side
+    Received,
+}
+
+/// A payload captured by a [`CaptureRecorder`]
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CaptureRecord {
+    /// Time elapsed since the start of the capture, in microseconds
+    pub timestamp_us: u64,
+    /// Whether the payload was sent or received
+    pub direction: CaptureDirection,
+    /// Id of the client on the server. On a client, [`None`] until the server assigned an id
+    pub client_id: Option<ClientId>,
+    /// Channel the payload was sent or received on
+    pub channel_id: ChannelId,
+    /// Raw payload, as given to or returned by the send/receive functions
+    pub payload: Vec<u8>,
+}
+
+/// Writes every payload sent and received by an endpoint or a connection to a capture file.
+///
+/// Records are appended as they happen and can be read back with a [`CaptureReader`].
+#[derive(Debug)]
+pub struct CaptureRecorder {
+    start: Instant,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl CaptureRecorder {
+    /// Creates (or truncates) the capture file at `path` and writes its header
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, QuintetError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&CAPTURE_MAGIC)?;
+        CAPTURE_VERSION.serialize(&mut writer)?;
+        Ok(Self {
+            start: Instant::now(),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub(crate) fn record(
+        &self,
+        direction: CaptureDirection,
+        client_id: Option<ClientId>,
+        channel_id: ChannelId,
+        payload: &[u8],
+    ) {
+        let timestamp_us = self.start.elapsed().as_micros() as u64;
+        let result = self.writer.lock().map_err(QuintetError::from).and_then(|mut writer| {
+            // Same layout as a serialized CaptureRecord, without copying the payload
+            timestamp_us.serialize(&mut *writer)?;
+            direction.serialize(&mut *writer)?;
+            client_id.serialize(&mut *writer)?;
+            channel_id.serialize(&mut *writer)?;
+            payload.serialize(&mut *writer)?;
+            Ok(())
+        });
+        if let Err(err) = result {
+            error!("Failed to write capture record: {}", err);
+        }
+    }
+
+    /// Writes the buffered records to the capture file
+    pub fn flush(&self) -> Result<(), QuintetError> {
+        Ok(self.writer.lock()?.flush()?)
+    }
+}
+
+impl Drop for CaptureRecorder {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            error!("Failed to flush capture file: {}", err);
+        }
+    }
+}
+
+/// Reads the [`CaptureRecord`]s of a capture file written by a [`CaptureRecorder`]
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Opens the capture file at `path` and checks its header
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, QuintetError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Reads and checks the capture header from `reader`
+    pub fn new(mut reader: R) -> Result<Self, QuintetError> {
+        let mut magic = [0; CAPTURE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        let version = u16::deserialize_reader(&mut reader)?;
+        if magic != CAPTURE_MAGIC || version != CAPTURE_VERSION {
+            return Err(QuintetError::Deserialization);
+        }
+        Ok(Self { reader })
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CaptureRecord, QuintetError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A clean end of file can only happen between two records
+        let mut timestamp = [0; 8];
+        match self.reader.read(&mut timestamp[..1]) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(err) => return Some(Err(err.into())),
+        }
+        if let Err(err) = self.reader.read_exact(&mut timestamp[1..]) {
+            return Some(Err(err.into()));
+        }
+        let mut record = io::Cursor::new(timestamp).chain(&mut self.reader);
+        Some(CaptureRecord::deserialize_reader(&mut record).map_err(QuintetError::from))
+    }
+}
+
+/// Reads all the records of the capture file at `path`
+pub fn read_capture<P: AsRef<Path>>(path: P) -> Result<Vec<CaptureRecord>, QuintetError> {
+    CaptureReader::open(path)?.collect()
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use super::error::QuintetError;
+
 /// SHA-256 hash of the certificate data in DER form
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CertificateFingerprint([u8; 32]);
@@ -10,6 +12,16 @@ impl CertificateFingerprint {
         CertificateFingerprint(buf)
     }
 
+    /// Decodes a fingerprint from its base64 form, as given by [`CertificateFingerprint::to_base64`]
+    pub fn from_base64(fingerprint_b64: &str) -> Result<Self, QuintetError> {
+        let bytes =
+            base64::decode(fingerprint_b64).map_err(|_| QuintetError::InvalidFingerprint)?;
+        match bytes.try_into() {
+            Ok(buf) => Ok(CertificateFingerprint(buf)),
+            Err(_) => Err(QuintetError::InvalidFingerprint),
+        }
+    }
+
     /// Encodes the wrapped buffer content to base64
     pub fn to_base64(&self) -> String {
         base64::encode(&self.0)
@@ -1,7 +1,10 @@
 use bevy::log::trace;
 use bytes::Bytes;
 use quinn::VarInt;
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    sync::{atomic::AtomicU64, Arc},
+};
 use tokio::sync::{
     broadcast,
     mpsc::{self, error::TrySendError},
@@ -9,10 +12,17 @@ use tokio::sync::{
 
 use crate::shared::{
     channels::{
+        blob::blob_send_task,
         reliable::send::{ordered_reliable_channel_task, unordered_reliable_channel_task},
-        unreliable::send::unreliable_channel_task,
+        unreliable::send::{
+            unreliable_channel_task, unreliable_fragmented_channel_task,
+            unreliable_sequenced_channel_task, DatagramSink,
+        },
     },
     error::QuintetError,
+    link_conditioner::{condition_channel, condition_datagrams, LinkConditionerConfig},
+    stats::ChannelsStats,
+    DisconnectReason,
 };
 
 use self::{
@@ -20,9 +30,16 @@ use self::{
     unreliable::recv::unreliable_channel_receiver_task,
 };
 
+mod blob;
 mod reliable;
 mod unreliable;
 
+pub use blob::{BlobId, DEFAULT_BLOB_CHUNK_SIZE, MAX_BLOB_LEN};
+pub use reliable::DEFAULT_MAX_RELIABLE_FRAME_LEN;
+pub use unreliable::{
+    DEFAULT_FRAGMENT_REASSEMBLY_TIMEOUT, MAX_FRAGMENT_COUNT, MAX_PARTIAL_MESSAGES_PER_CHANNEL,
+};
+
 /// Id of an opened channel
 pub type ChannelId = u8;
 /// Maximum number of channels that can be opened simultaneously
@@ -32,7 +49,7 @@ pub(crate) const CHANNEL_ID_LEN: usize = 1;
 pub(crate) const PROTOCOL_HEADER_LEN: usize = CHANNEL_ID_LEN;
 
 /// Type of a channel, offering different delivery guarantees.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ChannelType {
     /// An OrderedReliable channel ensures that messages sent are delivered, and are processed by the receiving end in the same order as they were sent.
     OrderedReliable,
@@ -42,11 +59,69 @@ pub enum ChannelType {
     ///
     /// The maximum allowed size of a datagram may change over the lifetime of a connection according to variation in the path MTU estimate. This is guaranteed to be a little over a kilobyte at minimum.
     Unreliable,
+    /// Channel which transmits messages as unreliable and unordered datagrams, like [`ChannelType::Unreliable`], but splits messages larger than a datagram into fragments.
+    ///
+    /// Fragments are reassembled by the receiving end. If any fragment is lost, the whole message is dropped after [`DEFAULT_FRAGMENT_REASSEMBLY_TIMEOUT`]. A message can be split into at most [`MAX_FRAGMENT_COUNT`] fragments.
+    UnreliableFragmented,
+    /// Channel which transmits messages as unreliable datagrams, like [`ChannelType::Unreliable`], but where the receiving end only processes the most recent message: out-of-order or duplicate messages are silently dropped.
+    ///
+    /// Useful for state streams (such as positions) where an older message is useless once a newer one has been applied.
+    UnreliableSequenced,
+}
+
+impl ChannelType {
+    /// Returns the configuration of a channel of this type with the given priority. See [`ChannelPriority`]
+    pub const fn with_priority(self, priority: ChannelPriority) -> ChannelConfig {
+        ChannelConfig {
+            channel_type: self,
+            priority,
+        }
+    }
+}
+
+/// Priority of the outgoing traffic of a channel.
+///
+/// Each reliable channel sends on its own QUIC streams, which get the priority of the channel: when the connection is congested, pending data of higher priority channels is sent before the data of lower priority channels. Unreliable channels are sent as datagrams and are not affected.
+pub type ChannelPriority = i32;
+/// Priority of the channels opened without an explicit priority
+pub const DEFAULT_CHANNEL_PRIORITY: ChannelPriority = 0;
+
+/// Configuration of a channel to open: its [`ChannelType`] and its [`ChannelPriority`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelConfig {
+    /// Type of the channel
+    pub channel_type: ChannelType,
+    /// Priority of the outgoing traffic of the channel
+    pub priority: ChannelPriority,
+}
+
+impl From<ChannelType> for ChannelConfig {
+    fn from(channel_type: ChannelType) -> Self {
+        channel_type.with_priority(DEFAULT_CHANNEL_PRIORITY)
+    }
+}
+
+/// Receive-side counters of a connection, updated by its async channel tasks
+#[derive(Debug, Default)]
+pub(crate) struct ChannelsRecvCounters {
+    /// Messages dropped by sequenced channels because a more recent one was already received
+    pub(crate) dropped_stale_messages: AtomicU64,
 }
 
 #[derive(Debug)]
 pub(crate) enum ChannelAsyncMessage {
     LostConnection,
+    BlobProgress {
+        blob_id: BlobId,
+        tag: u32,
+        received: usize,
+        total: usize,
+    },
+    BlobReceived {
+        blob_id: BlobId,
+        tag: u32,
+        payload: Bytes,
+    },
 }
 
 #[derive(Debug)]
@@ -54,30 +129,52 @@ pub(crate) enum ChannelSyncMessage {
     CreateChannel {
         channel_id: ChannelId,
         channel_type: ChannelType,
+        priority: ChannelPriority,
         bytes_to_channel_recv: mpsc::Receiver<Bytes>,
         channel_close_recv: mpsc::Receiver<()>,
     },
+    SendBlob {
+        blob_id: BlobId,
+        tag: u32,
+        payload: Bytes,
+    },
 }
 
 #[derive(Debug)]
 pub(crate) struct Channel {
+    id: ChannelId,
     sender: mpsc::Sender<Bytes>,
     close_sender: mpsc::Sender<()>,
+    stats: Arc<ChannelsStats>,
 }
 
 impl Channel {
-    pub(crate) fn new(sender: mpsc::Sender<Bytes>, close_sender: mpsc::Sender<()>) -> Self {
+    pub(crate) fn new(
+        id: ChannelId,
+        sender: mpsc::Sender<Bytes>,
+        close_sender: mpsc::Sender<()>,
+        stats: Arc<ChannelsStats>,
+    ) -> Self {
         Self {
+            id,
             sender,
             close_sender,
+            stats,
         }
     }
 
     pub(crate) fn send_payload(&self, payload: Bytes) -> Result<(), QuintetError> {
+        let len = payload.len();
         match self.sender.try_send(payload) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.stats.record_sent(self.id, len);
+                Ok(())
+            }
             Err(err) => match err {
-                TrySendError::Full(_) => Err(QuintetError::FullQueue),
+                TrySendError::Full(_) => {
+                    self.stats.record_full_queue(self.id);
+                    Err(QuintetError::FullQueue)
+                }
                 TrySendError::Closed(_) => Err(QuintetError::InternalChannelClosed),
             },
         }
@@ -97,13 +194,13 @@ impl Channel {
 /// Stores a configuration that represents multiple channels to be opened by a [`crate::client::connection::Connection`] or [`crate::server::Endpoint`]
 #[derive(Debug, Clone)]
 pub struct ChannelsConfiguration {
-    channels: Vec<ChannelType>,
+    channels: Vec<ChannelConfig>,
 }
 
 impl Default for ChannelsConfiguration {
     fn default() -> Self {
         Self {
-            channels: vec![ChannelType::OrderedReliable],
+            channels: vec![ChannelType::OrderedReliable.into()],
         }
     }
 }
@@ -120,35 +217,44 @@ impl ChannelsConfiguration {
     pub fn from_types(
         channel_types: Vec<ChannelType>,
     ) -> Result<ChannelsConfiguration, QuintetError> {
-        if channel_types.len() > MAX_CHANNEL_COUNT {
+        Self::from_configs(channel_types.into_iter().map(ChannelConfig::from).collect())
+    }
+
+    /// New configuration from a list of [`ChannelConfig`], to give priorities to the channels. Opened channels (and their [`ChannelId`]) will have the same order as in this collection
+    pub fn from_configs(
+        channel_configs: Vec<ChannelConfig>,
+    ) -> Result<ChannelsConfiguration, QuintetError> {
+        if channel_configs.len() > MAX_CHANNEL_COUNT {
             Err(QuintetError::MaxChannelsCountReached)
         } else {
             Ok(Self {
-                channels: channel_types,
+                channels: channel_configs,
             })
         }
     }
 
-    /// Adds one element to the configuration from a [`ChannelType`]. Opened channels (and their [`ChannelId`]) will have the same order as their insertion order.
-    pub fn add(&mut self, channel_type: ChannelType) -> Option<ChannelId> {
+    /// Adds one element to the configuration from a [`ChannelType`] or a [`ChannelConfig`]. Opened channels (and their [`ChannelId`]) will have the same order as their insertion order.
+    pub fn add(&mut self, channel: impl Into<ChannelConfig>) -> Option<ChannelId> {
         if self.channels.len() < MAX_CHANNEL_COUNT {
-            self.channels.push(channel_type);
+            self.channels.push(channel.into());
             Some((self.channels.len() - 1) as u8)
         } else {
             None
         }
     }
 
-    pub(crate) fn configs(&self) -> &Vec<ChannelType> {
+    pub(crate) fn configs(&self) -> &Vec<ChannelConfig> {
         &self.channels
     }
 }
 
 pub(crate) fn spawn_send_channels_tasks(
     connection_handle: quinn::Connection,
-    close_recv: broadcast::Receiver<()>,
+    close_recv: broadcast::Receiver<DisconnectReason>,
     to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    link_conditioner: Option<LinkConditionerConfig>,
+    max_frame_len: usize,
 ) {
     // Spawn a task to handle send channels creation for this connection
     tokio::spawn(async move {
@@ -157,6 +263,8 @@ pub(crate) fn spawn_send_channels_tasks(
             close_recv,
             to_channels_recv,
             from_channels_send,
+            link_conditioner,
+            max_frame_len,
         )
         .await
     });
@@ -164,21 +272,43 @@ pub(crate) fn spawn_send_channels_tasks(
 
 pub(crate) async fn send_channel_task_spawner(
     connection: quinn::Connection,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     mut to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    link_conditioner: Option<LinkConditionerConfig>,
+    max_frame_len: usize,
 ) {
     // Use an mpsc channel where, instead of sending messages, we wait for the channel to be closed, which happens when every sender has been dropped. We can't use a JoinSet as simply here since we would also need to drain closed channels from it.
     let (channel_tasks_keepalive, mut channel_tasks_waiter) = mpsc::channel::<()>(1);
 
     let close_receiver_clone = close_recv.resubscribe();
+    let mut close_reason = DisconnectReason::default();
     tokio::select! {
-        _ = close_recv.recv() => {
-            trace!("Connection Channels listener received a close signal")
+        // Closing a connection also drops its channels sender: the close signal must be read first to get its reason
+        biased;
+        reason = close_recv.recv() => {
+            trace!("Connection Channels listener received a close signal");
+            if let Ok(reason) = reason {
+                close_reason = reason;
+            }
         }
         _ = async {
             while let Some(sync_message) = to_channels_recv.recv().await {
-                let ChannelSyncMessage::CreateChannel{ channel_id,  channel_type,bytes_to_channel_recv, channel_close_recv } = sync_message;
+                let (channel_id, channel_type, priority, bytes_to_channel_recv, channel_close_recv) = match sync_message {
+                    ChannelSyncMessage::CreateChannel{ channel_id, channel_type, priority, bytes_to_channel_recv, channel_close_recv } => (channel_id, channel_type, priority, bytes_to_channel_recv, channel_close_recv),
+                    ChannelSyncMessage::SendBlob { blob_id, tag, payload } => {
+                        tokio::spawn(blob_send_task(
+                            connection.clone(),
+                            blob_id,
+                            tag,
+                            payload,
+                            channel_tasks_keepalive.clone(),
+                        ));
+                        continue;
+                    }
+                };
+                let bytes_to_channel_recv = condition_channel(link_conditioner.as_ref(), channel_id, channel_type, bytes_to_channel_recv);
+                let conditioned_datagrams = condition_datagrams(link_conditioner.as_ref(), channel_id, channel_type, connection.clone(), channel_tasks_keepalive.clone());
 
                 let close_receiver = close_receiver_clone.resubscribe();
                 let connection_handle = connection.clone();
@@ -191,11 +321,13 @@ pub(crate) async fn send_channel_task_spawner(
                             ordered_reliable_channel_task(
                                 connection_handle,
                                 channel_id,
+                                priority,
                                 channels_keepalive_clone,
                                 from_channels_send,
                                 close_receiver,
                                 channel_close_recv,
-                                bytes_to_channel_recv
+                                bytes_to_channel_recv,
+                                max_frame_len
                             )
                             .await
                         });
@@ -205,11 +337,13 @@ pub(crate) async fn send_channel_task_spawner(
                             unordered_reliable_channel_task(
                                 connection_handle,
                                 channel_id,
+                                priority,
                                 channels_keepalive_clone,
                                 from_channels_send,
                                 close_receiver,
                                 channel_close_recv,
-                                bytes_to_channel_recv
+                                bytes_to_channel_recv,
+                                max_frame_len
                             )
                             .await
                         });
@@ -217,7 +351,35 @@ pub(crate) async fn send_channel_task_spawner(
                     ChannelType::Unreliable => {
                         tokio::spawn(async move {
                             unreliable_channel_task(
-                                connection_handle,
+                                DatagramSink::new(connection_handle, conditioned_datagrams),
+                                channel_id,
+                                channels_keepalive_clone,
+                                from_channels_send,
+                                close_receiver,
+                                channel_close_recv,
+                                bytes_to_channel_recv
+                            )
+                            .await
+                        });
+                    },
+                    ChannelType::UnreliableFragmented => {
+                        tokio::spawn(async move {
+                            unreliable_fragmented_channel_task(
+                                DatagramSink::new(connection_handle, conditioned_datagrams),
+                                channel_id,
+                                channels_keepalive_clone,
+                                from_channels_send,
+                                close_receiver,
+                                channel_close_recv,
+                                bytes_to_channel_recv
+                            )
+                            .await
+                        });
+                    },
+                    ChannelType::UnreliableSequenced => {
+                        tokio::spawn(async move {
+                            unreliable_sequenced_channel_task(
+                                DatagramSink::new(connection_handle, conditioned_datagrams),
                                 channel_id,
                                 channels_keepalive_clone,
                                 from_channels_send,
@@ -241,16 +403,22 @@ pub(crate) async fn send_channel_task_spawner(
     drop(channel_tasks_keepalive);
     let _ = channel_tasks_waiter.recv().await;
 
-    connection.close(VarInt::from_u32(0), "closed".as_bytes());
+    connection.close(
+        VarInt::from_u32(close_reason.code),
+        close_reason.message.as_bytes(),
+    );
 }
 
 pub(crate) fn spawn_recv_channels_tasks(
     connection_handle: quinn::Connection,
     connection_id: u64,
-    close_recv: broadcast::Receiver<()>,
+    close_recv: broadcast::Receiver<DisconnectReason>,
     bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    recv_counters: Arc<ChannelsRecvCounters>,
+    max_frame_len: usize,
 ) {
-    // Spawn a task to listen for reliable messages
+    // Spawn a task to listen for reliable messages and blobs
     {
         let connection_handle = connection_handle.clone();
         let close_recv = close_recv.resubscribe();
@@ -261,6 +429,8 @@ pub(crate) fn spawn_recv_channels_tasks(
                 connection_handle,
                 close_recv,
                 bytes_incoming_send,
+                from_channels_send,
+                max_frame_len,
             )
             .await
         });
@@ -277,6 +447,7 @@ pub(crate) fn spawn_recv_channels_tasks(
                 connection_handle,
                 close_recv,
                 bytes_incoming_send,
+                recv_counters,
             )
             .await
         });
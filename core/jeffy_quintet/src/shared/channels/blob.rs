@@ -0,0 +1,125 @@
+use bevy::{
+    log::warn,
+    utils::tracing::{error, trace},
+};
+use bytes::{Bytes, BytesMut};
+use quinn::{RecvStream, VarInt};
+use tokio::sync::mpsc;
+
+use crate::shared::channels::{
+    reliable::UniStreamKind, ChannelAsyncMessage, ChannelPriority, DEFAULT_CHANNEL_PRIORITY,
+};
+
+/// Id of a blob, unique for each sending side of a connection
+pub type BlobId = u64;
+
+/// Size of the chunks a blob is written in. The receiving end reports its progress at most once per chunk
+pub const DEFAULT_BLOB_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum length of a blob, larger blobs are refused by both ends
+pub const MAX_BLOB_LEN: usize = 1024 * 1024 * 1024;
+
+// Blob stream layout:
+// STREAM KIND | BLOB ID (u64) | TAG (u32) | TOTAL LENGTH (u64) | PAYLOAD
+const BLOB_HEADER_LEN: usize = 8 + 4 + 8;
+
+// Lower than the default priority of the channels streams: a blob never delays channel messages
+const BLOB_STREAM_PRIORITY: ChannelPriority = DEFAULT_CHANNEL_PRIORITY - 1;
+
+pub(crate) async fn blob_send_task(
+    connection: quinn::Connection,
+    blob_id: BlobId,
+    tag: u32,
+    payload: Bytes,
+    _: mpsc::Sender<()>,
+) {
+    let mut send = match connection.open_uni().await {
+        Ok(send) => send,
+        Err(err) => {
+            error!("Failed to open the stream of blob {}: {}", blob_id, err);
+            return;
+        }
+    };
+    if let Err(err) = send.set_priority(BLOB_STREAM_PRIORITY) {
+        warn!("Failed to lower the priority of blob {}: {}", blob_id, err);
+    }
+
+    let mut header = Vec::with_capacity(1 + BLOB_HEADER_LEN);
+    header.push(UniStreamKind::Blob as u8);
+    header.extend_from_slice(&blob_id.to_be_bytes());
+    header.extend_from_slice(&tag.to_be_bytes());
+    header.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+
+    let result = async {
+        send.write_all(&header).await?;
+        for chunk in payload.chunks(DEFAULT_BLOB_CHUNK_SIZE) {
+            send.write_all(chunk).await?;
+        }
+        send.finish().await
+    }
+    .await;
+    match result {
+        Ok(_) => trace!("Blob {} of {} bytes sent", blob_id, payload.len()),
+        Err(err) => error!("Error while sending blob {}: {}", blob_id, err),
+    }
+}
+
+pub(crate) async fn blob_receiver_task(
+    mut recv: RecvStream,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+) {
+    let mut header = [0; BLOB_HEADER_LEN];
+    if let Err(err) = recv.read_exact(&mut header).await {
+        error!("Failed to read a blob header: {}", err);
+        return;
+    }
+    let blob_id = u64::from_be_bytes(header[0..8].try_into().unwrap());
+    let tag = u32::from_be_bytes(header[8..12].try_into().unwrap());
+    let total = u64::from_be_bytes(header[12..20].try_into().unwrap()) as usize;
+    if total > MAX_BLOB_LEN {
+        warn!("Refused blob {} of {} bytes, too large", blob_id, total);
+        let _ = recv.stop(VarInt::from_u32(0));
+        return;
+    }
+
+    let mut payload = BytesMut::with_capacity(total);
+    let mut reported = 0;
+    while payload.len() < total {
+        match recv.read_chunk(total - payload.len(), true).await {
+            Ok(Some(chunk)) => payload.extend_from_slice(&chunk.bytes),
+            Ok(None) => break,
+            Err(err) => {
+                warn!("Error while receiving blob {}: {}", blob_id, err);
+                return;
+            }
+        }
+        if payload.len() - reported >= DEFAULT_BLOB_CHUNK_SIZE && payload.len() < total {
+            reported = payload.len();
+            // Progress is cumulative: a report dropped because the queue is full is caught up by the next one
+            let _ = from_channels_send.try_send(ChannelAsyncMessage::BlobProgress {
+                blob_id,
+                tag,
+                received: reported,
+                total,
+            });
+        }
+    }
+    if payload.len() < total {
+        warn!(
+            "Blob {} ended after {} of its {} bytes",
+            blob_id,
+            payload.len(),
+            total
+        );
+        return;
+    }
+
+    trace!("Blob {} of {} bytes received", blob_id, total);
+    let _ = from_channels_send
+        .send(ChannelAsyncMessage::BlobReceived {
+            blob_id,
+            tag,
+            payload: payload.freeze(),
+        })
+        .await;
+}
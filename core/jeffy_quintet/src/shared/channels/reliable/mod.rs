@@ -4,9 +4,33 @@ pub(crate) mod codec;
 pub(crate) mod recv;
 pub(crate) mod send;
 
-// Default max frame length of 8MB
+/// Default max length of a message on a reliable channel, 8MB. See [`crate::shared::transport::TransportConfig::max_reliable_frame_len`]
 pub const DEFAULT_MAX_RELIABLE_FRAME_LEN: usize = 8 * 1_024 * 1_024;
 
+// Every unidirectional stream starts with its kind:
+// STREAM KIND | STREAM CONTENT
+pub(crate) const UNI_STREAM_KIND_LEN: usize = 1;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum UniStreamKind {
+    /// The stream carries the frames of a reliable channel
+    Channel = 0,
+    /// The stream carries a single blob
+    Blob = 1,
+}
+
+impl UniStreamKind {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(UniStreamKind::Channel),
+            1 => Some(UniStreamKind::Blob),
+            _ => None,
+        }
+    }
+}
+
+// Channel stream frames layout:
 // PAYLOAD LENGTH | CHANNEL ID | PAYLOAD
 pub(crate) const RELIABLE_FRAME_LENGTH_FIELD_LEN: usize = 4;
 pub(crate) const RELIABLE_FRAME_TOTAL_HEADER_LEN: usize =
@@ -1,4 +1,4 @@
-use bevy::utils::tracing::trace;
+use bevy::{log::warn, utils::tracing::trace};
 use bytes::{Buf, Bytes, BytesMut};
 use futures::StreamExt;
 use quinn::RecvStream;
@@ -9,16 +9,24 @@ use tokio::sync::{
 };
 use tokio_util::codec::FramedRead;
 
-use crate::shared::channels::{
-    reliable::{codec::QuintetProtocolCodecDecoder, DEFAULT_MAX_RELIABLE_FRAME_LEN},
-    ChannelId, CHANNEL_ID_LEN,
+use crate::shared::{
+    channels::{
+        blob::blob_receiver_task,
+        reliable::{
+            codec::QuintetProtocolCodecDecoder, UniStreamKind, UNI_STREAM_KIND_LEN,
+        },
+        ChannelAsyncMessage, ChannelId, CHANNEL_ID_LEN,
+    },
+    DisconnectReason,
 };
 
 pub(crate) async fn reliable_channels_receiver_task<T: Display>(
     task_id: T,
     connection: quinn::Connection,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    max_frame_len: usize,
 ) {
     let close_recv_clone = close_recv.resubscribe();
     tokio::select! {
@@ -28,12 +36,15 @@ pub(crate) async fn reliable_channels_receiver_task<T: Display>(
         _ = async {
             while let Ok(recv) = connection.accept_uni().await {
                 let bytes_incoming_send_clone = bytes_incoming_send.clone();
+                let from_channels_send_clone = from_channels_send.clone();
                 let close_recv_clone = close_recv_clone.resubscribe();
                 tokio::spawn(async move {
-                    reliable_stream_receiver_task(
+                    uni_stream_receiver_task(
                         recv,
                         close_recv_clone,
-                        bytes_incoming_send_clone
+                        bytes_incoming_send_clone,
+                        from_channels_send_clone,
+                        max_frame_len
                     ).await;
                 });
             }
@@ -43,15 +54,37 @@ pub(crate) async fn reliable_channels_receiver_task<T: Display>(
     };
 }
 
+async fn uni_stream_receiver_task(
+    mut recv: RecvStream,
+    close_recv: broadcast::Receiver<DisconnectReason>,
+    bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    max_frame_len: usize,
+) {
+    let mut kind = [0; UNI_STREAM_KIND_LEN];
+    if recv.read_exact(&mut kind).await.is_err() {
+        return;
+    }
+    match UniStreamKind::from_u8(kind[0]) {
+        Some(UniStreamKind::Channel) => {
+            reliable_stream_receiver_task(recv, close_recv, bytes_incoming_send, max_frame_len)
+                .await
+        }
+        Some(UniStreamKind::Blob) => blob_receiver_task(recv, from_channels_send).await,
+        None => warn!("Received a stream of unknown kind {}", kind[0]),
+    }
+}
+
 async fn reliable_stream_receiver_task(
     recv: RecvStream,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    max_frame_len: usize,
 ) {
     tokio::select! {
         _ = close_recv.recv() => {}
         _ = async {
-            let mut frame_recv = FramedRead::new(recv, QuintetProtocolCodecDecoder::new(DEFAULT_MAX_RELIABLE_FRAME_LEN));
+            let mut frame_recv = FramedRead::new(recv, QuintetProtocolCodecDecoder::new(max_frame_len));
             while let Some(Ok(msg_bytes)) = frame_recv.next().await {
                 // TODO Clean: error handling
                 bytes_incoming_send
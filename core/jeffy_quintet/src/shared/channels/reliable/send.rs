@@ -8,34 +8,54 @@ use quinn::SendStream;
 use tokio::sync::{broadcast, mpsc};
 use tokio_util::codec::FramedWrite;
 
-use crate::shared::channels::{ChannelAsyncMessage, ChannelId};
+use crate::shared::{
+    channels::{ChannelAsyncMessage, ChannelId, ChannelPriority},
+    DisconnectReason,
+};
 
-use super::{codec::QuintetProtocolCodecEncoder, DEFAULT_MAX_RELIABLE_FRAME_LEN};
+use super::{codec::QuintetProtocolCodecEncoder, UniStreamKind};
 
 async fn new_uni_frame_sender(
     connection: &quinn::Connection,
     raw_channel_id: ChannelId,
+    priority: ChannelPriority,
+    max_frame_len: usize,
 ) -> FramedWrite<SendStream, QuintetProtocolCodecEncoder> {
-    let uni_sender = connection
+    let mut uni_sender = connection
         .open_uni()
         .await
         .expect("Failed to open send stream");
+    if let Err(err) = uni_sender.set_priority(priority) {
+        warn!(
+            "Failed to set the priority of a stream of channel {}: {}",
+            raw_channel_id, err
+        );
+    }
+    uni_sender
+        .write_all(&[UniStreamKind::Channel as u8])
+        .await
+        .expect("Failed to write send stream kind");
     FramedWrite::new(
         uni_sender,
-        QuintetProtocolCodecEncoder::new(raw_channel_id, DEFAULT_MAX_RELIABLE_FRAME_LEN),
+        QuintetProtocolCodecEncoder::new(raw_channel_id, max_frame_len),
     )
 }
 
+// Each argument is one end of the channels wiring this task sits in, bundling them would only move the list
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn ordered_reliable_channel_task(
     connection: quinn::Connection,
     raw_channel_id: ChannelId,
+    priority: ChannelPriority,
     _: mpsc::Sender<()>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     mut channel_close_recv: mpsc::Receiver<()>,
     mut bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+    max_frame_len: usize,
 ) {
-    let mut frame_sender = new_uni_frame_sender(&connection, raw_channel_id).await;
+    let mut frame_sender =
+        new_uni_frame_sender(&connection, raw_channel_id, priority, max_frame_len).await;
 
     tokio::select! {
         _ = close_recv.recv() => {
@@ -81,14 +101,18 @@ pub(crate) async fn ordered_reliable_channel_task(
     }
 }
 
+// Each argument is one end of the channels wiring this task sits in, bundling them would only move the list
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn unordered_reliable_channel_task(
     connection: quinn::Connection,
     raw_channel_id: ChannelId,
+    priority: ChannelPriority,
     channel_tasks_keepalive: mpsc::Sender<()>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     mut channel_close_recv: mpsc::Receiver<()>,
     mut bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+    max_frame_len: usize,
 ) {
     tokio::select! {
         _ = close_recv.recv() => {
@@ -103,7 +127,7 @@ pub(crate) async fn unordered_reliable_channel_task(
                 let from_channels_send_clone = from_channels_send.clone();
                 let channels_keepalive_clone = channel_tasks_keepalive.clone();
                 tokio::spawn(async move {
-                    let mut frame_sender = new_uni_frame_sender(&conn,raw_channel_id).await;
+                    let mut frame_sender = new_uni_frame_sender(&conn, raw_channel_id, priority, max_frame_len).await;
                     if let Err(err) = frame_sender.send(msg_bytes).await {
                         error!("Error while sending on Unordered Reliable Channel, {}", err);
                         from_channels_send_clone.send(
@@ -125,7 +149,8 @@ pub(crate) async fn unordered_reliable_channel_task(
         let conn = connection.clone();
         let channels_keepalive_clone = channel_tasks_keepalive.clone();
         tokio::spawn(async move {
-            let mut frame_sender = new_uni_frame_sender(&conn, raw_channel_id).await;
+            let mut frame_sender =
+                new_uni_frame_sender(&conn, raw_channel_id, priority, max_frame_len).await;
             if let Err(err) = frame_sender.send(msg_bytes).await {
                 warn!(
                     "Failed to send a remaining message on Unordered Reliable Channel, {}",
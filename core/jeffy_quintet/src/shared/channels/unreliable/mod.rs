@@ -1,2 +1,53 @@
+use std::time::Duration;
+
 pub(crate) mod recv;
 pub(crate) mod send;
+
+// Unreliable datagrams layout:
+// CHANNEL ID | DATAGRAM KIND | KIND SPECIFIC HEADER | PAYLOAD
+pub(crate) const DATAGRAM_KIND_LEN: usize = 1;
+pub(crate) const UNRELIABLE_HEADER_LEN: usize = super::PROTOCOL_HEADER_LEN + DATAGRAM_KIND_LEN;
+
+// Fragment header layout:
+// MESSAGE ID (u16) | FRAGMENT INDEX (u8) | FRAGMENT COUNT (u8)
+pub(crate) const FRAGMENT_HEADER_LEN: usize = 2 + 1 + 1;
+
+// Sequenced header layout:
+// SEQUENCE NUMBER (u16)
+pub(crate) const SEQUENCE_HEADER_LEN: usize = 2;
+
+/// Maximum number of fragments a message sent on an [`super::ChannelType::UnreliableFragmented`] channel can be split into
+pub const MAX_FRAGMENT_COUNT: usize = u8::MAX as usize;
+
+/// Incomplete fragmented messages are dropped by the receiving end after this delay
+pub const DEFAULT_FRAGMENT_REASSEMBLY_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Most incomplete fragmented messages the receiving end buffers per channel. Past it, the oldest one is dropped
+pub const MAX_PARTIAL_MESSAGES_PER_CHANNEL: usize = 64;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DatagramKind {
+    /// The datagram carries a whole message
+    Whole = 0,
+    /// The datagram carries one fragment of a bigger message
+    Fragment = 1,
+    /// The datagram carries a whole message stamped with its channel sequence number
+    Sequenced = 2,
+}
+
+impl DatagramKind {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(DatagramKind::Whole),
+            1 => Some(DatagramKind::Fragment),
+            2 => Some(DatagramKind::Sequenced),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `sequence` was sent after `last`, accounting for wrap-around of the u16 sequence numbers
+pub(crate) fn sequence_more_recent(sequence: u16, last: u16) -> bool {
+    (sequence.wrapping_sub(last) as i16) > 0
+}
@@ -1,30 +1,77 @@
 use bevy::utils::tracing::trace;
-use bytes::Bytes;
-use std::fmt::Display;
+use bytes::{Buf, Bytes, BytesMut};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
 use tokio::sync::{
     broadcast,
     mpsc::{self},
 };
 
-use crate::shared::channels::{ChannelId, CHANNEL_ID_LEN};
+use crate::shared::{
+    channels::{ChannelId, ChannelsRecvCounters},
+    DisconnectReason,
+};
+
+use super::{
+    sequence_more_recent, DatagramKind, DEFAULT_FRAGMENT_REASSEMBLY_TIMEOUT, FRAGMENT_HEADER_LEN,
+    MAX_PARTIAL_MESSAGES_PER_CHANNEL, SEQUENCE_HEADER_LEN, UNRELIABLE_HEADER_LEN,
+};
 
 pub(crate) async fn unreliable_channel_receiver_task<T: Display>(
     task_id: T,
     connection: quinn::Connection,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    recv_counters: Arc<ChannelsRecvCounters>,
 ) {
+    let mut reassembler = FragmentReassembler::new(
+        DEFAULT_FRAGMENT_REASSEMBLY_TIMEOUT,
+        MAX_PARTIAL_MESSAGES_PER_CHANNEL,
+    );
+    // Most recent sequence number received on each sequenced channel
+    let mut last_sequences: HashMap<ChannelId, u16> = HashMap::new();
     tokio::select! {
         _ = close_recv.recv() => {
             trace!("Listener for unreliable datagrams with id {} received a close signal", task_id)
         }
         _ = async {
             while let Ok(mut msg_bytes) = connection.read_datagram().await {
-                if msg_bytes.len() <= CHANNEL_ID_LEN {
+                if msg_bytes.len() <= UNRELIABLE_HEADER_LEN {
                     continue;
                 }
-                let payload = msg_bytes.split_off(1).into();
-                let channel_id = msg_bytes[0];
+                let channel_id = msg_bytes.get_u8();
+                let payload = match DatagramKind::from_u8(msg_bytes.get_u8()) {
+                    Some(DatagramKind::Whole) => msg_bytes,
+                    Some(DatagramKind::Fragment) => {
+                        match reassembler.receive_fragment(channel_id, msg_bytes) {
+                            Some(payload) => payload,
+                            None => continue,
+                        }
+                    }
+                    Some(DatagramKind::Sequenced) => {
+                        if msg_bytes.len() <= SEQUENCE_HEADER_LEN {
+                            continue;
+                        }
+                        let sequence = msg_bytes.get_u16();
+                        match last_sequences.get(&channel_id) {
+                            Some(last) if !sequence_more_recent(sequence, *last) => {
+                                recv_counters
+                                    .dropped_stale_messages
+                                    .fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                            _ => {
+                                last_sequences.insert(channel_id, sequence);
+                            }
+                        }
+                        msg_bytes
+                    }
+                    None => continue,
+                };
                 // TODO Clean: error handling
                 bytes_incoming_send.send((channel_id, payload)).await.unwrap();
             }
@@ -33,3 +80,126 @@ pub(crate) async fn unreliable_channel_receiver_task<T: Display>(
         }
     };
 }
+
+struct PartialMessage {
+    fragments: Vec<Option<Bytes>>,
+    received_count: usize,
+    first_received: Instant,
+}
+
+/// Buffers the fragments of messages sent on fragmented unreliable channels until they are complete, expired, or
+/// pushed out by newer messages of the same channel.
+struct FragmentReassembler {
+    timeout: Duration,
+    max_partial_per_channel: usize,
+    partial_messages: HashMap<(ChannelId, u16), PartialMessage>,
+}
+
+impl FragmentReassembler {
+    fn new(timeout: Duration, max_partial_per_channel: usize) -> Self {
+        Self {
+            timeout,
+            max_partial_per_channel,
+            partial_messages: HashMap::new(),
+        }
+    }
+
+    /// Returns the reassembled message once its last missing fragment is received
+    fn receive_fragment(&mut self, channel_id: ChannelId, mut fragment: Bytes) -> Option<Bytes> {
+        if fragment.len() < FRAGMENT_HEADER_LEN {
+            return None;
+        }
+        let message_id = fragment.get_u16();
+        let fragment_index = fragment.get_u8() as usize;
+        let fragment_count = fragment.get_u8() as usize;
+        if fragment_index >= fragment_count {
+            return None;
+        }
+
+        let now = Instant::now();
+        self.drop_expired(now);
+        if !self.partial_messages.contains_key(&(channel_id, message_id)) {
+            self.make_room(channel_id);
+        }
+
+        let partial = self
+            .partial_messages
+            .entry((channel_id, message_id))
+            .or_insert_with(|| PartialMessage {
+                fragments: vec![None; fragment_count],
+                received_count: 0,
+                first_received: now,
+            });
+        // A mismatching count means the message id wrapped around onto a stale message
+        if partial.fragments.len() != fragment_count {
+            *partial = PartialMessage {
+                fragments: vec![None; fragment_count],
+                received_count: 0,
+                first_received: now,
+            };
+        }
+        if partial.fragments[fragment_index].is_none() {
+            partial.fragments[fragment_index] = Some(fragment);
+            partial.received_count += 1;
+        }
+        if partial.received_count < fragment_count {
+            return None;
+        }
+
+        let partial = self.partial_messages.remove(&(channel_id, message_id))?;
+        let mut message = BytesMut::new();
+        for fragment in partial.fragments.into_iter().flatten() {
+            message.extend_from_slice(&fragment);
+        }
+        Some(message.freeze())
+    }
+
+    /// Drops the oldest incomplete messages of the channel until a new one fits under the cap
+    fn make_room(&mut self, channel_id: ChannelId) {
+        loop {
+            let mut in_flight = 0;
+            let mut oldest: Option<(u16, Instant)> = None;
+            for ((partial_channel, message_id), partial) in self.partial_messages.iter() {
+                if *partial_channel != channel_id {
+                    continue;
+                }
+                in_flight += 1;
+                let older = match oldest {
+                    Some((_, first_received)) => partial.first_received < first_received,
+                    None => true,
+                };
+                if older {
+                    oldest = Some((*message_id, partial.first_received));
+                }
+            }
+            match oldest {
+                Some((message_id, _)) if in_flight >= self.max_partial_per_channel => {
+                    trace!(
+                        "Dropping incomplete message {} on channel {}, too many messages in reassembly",
+                        message_id,
+                        channel_id
+                    );
+                    self.partial_messages.remove(&(channel_id, message_id));
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn drop_expired(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        self.partial_messages.retain(|(channel_id, message_id), partial| {
+            let alive = now.duration_since(partial.first_received) < timeout;
+            if !alive {
+                trace!(
+                    "Dropping incomplete message {} on channel {} ({}/{} fragments received)",
+                    message_id,
+                    channel_id,
+                    partial.received_count,
+                    partial.fragments.len()
+                );
+            }
+            alive
+        });
+    }
+}
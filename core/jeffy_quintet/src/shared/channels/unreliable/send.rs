@@ -6,17 +6,148 @@ use bytes::{BufMut, Bytes, BytesMut};
 use quinn::SendDatagramError;
 use tokio::sync::{broadcast, mpsc};
 
-use crate::shared::channels::{ChannelAsyncMessage, ChannelId, PROTOCOL_HEADER_LEN};
+use crate::shared::{
+    channels::{ChannelAsyncMessage, ChannelId},
+    DisconnectReason,
+};
 
-pub(crate) async fn unreliable_channel_task(
+use super::{
+    DatagramKind, FRAGMENT_HEADER_LEN, MAX_FRAGMENT_COUNT, SEQUENCE_HEADER_LEN,
+    UNRELIABLE_HEADER_LEN,
+};
+
+/// Destination of the datagrams built by an unreliable channel: the connection itself, or a link conditioner forwarding to it
+pub(crate) struct DatagramSink {
     connection: quinn::Connection,
+    conditioner: Option<mpsc::Sender<Bytes>>,
+}
+
+impl DatagramSink {
+    pub(crate) fn new(
+        connection: quinn::Connection,
+        conditioner: Option<mpsc::Sender<Bytes>>,
+    ) -> Self {
+        Self {
+            connection,
+            conditioner,
+        }
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        self.connection.max_datagram_size()
+    }
+
+    fn send(&self, datagram: Bytes) -> Result<(), SendDatagramError> {
+        let Some(conditioner) = &self.conditioner else {
+            return self.connection.send_datagram(datagram);
+        };
+        // The conditioner sends later on: report the errors the connection would have raised right away
+        let max_datagram_size = self
+            .max_datagram_size()
+            .ok_or(SendDatagramError::UnsupportedByPeer)?;
+        if datagram.len() > max_datagram_size {
+            return Err(SendDatagramError::TooLarge);
+        }
+        // Like a congested link, a full conditioner queue drops the datagram
+        let _ = conditioner.try_send(datagram);
+        Ok(())
+    }
+}
+
+pub(crate) async fn unreliable_channel_task(
+    datagrams: DatagramSink,
+    channel_id: ChannelId,
+    keepalive: mpsc::Sender<()>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    close_recv: broadcast::Receiver<DisconnectReason>,
+    channel_close_recv: mpsc::Receiver<()>,
+    bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+) {
+    unreliable_task(
+        datagrams,
+        channel_id,
+        keepalive,
+        from_channels_send,
+        close_recv,
+        channel_close_recv,
+        bytes_to_channel_recv,
+        DatagramKind::Whole,
+    )
+    .await
+}
+
+pub(crate) async fn unreliable_fragmented_channel_task(
+    datagrams: DatagramSink,
+    channel_id: ChannelId,
+    keepalive: mpsc::Sender<()>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    close_recv: broadcast::Receiver<DisconnectReason>,
+    channel_close_recv: mpsc::Receiver<()>,
+    bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+) {
+    unreliable_task(
+        datagrams,
+        channel_id,
+        keepalive,
+        from_channels_send,
+        close_recv,
+        channel_close_recv,
+        bytes_to_channel_recv,
+        DatagramKind::Fragment,
+    )
+    .await
+}
+
+pub(crate) async fn unreliable_sequenced_channel_task(
+    datagrams: DatagramSink,
+    channel_id: ChannelId,
+    keepalive: mpsc::Sender<()>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    close_recv: broadcast::Receiver<DisconnectReason>,
+    channel_close_recv: mpsc::Receiver<()>,
+    bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+) {
+    unreliable_task(
+        datagrams,
+        channel_id,
+        keepalive,
+        from_channels_send,
+        close_recv,
+        channel_close_recv,
+        bytes_to_channel_recv,
+        DatagramKind::Sequenced,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn unreliable_task(
+    datagrams: DatagramSink,
     channel_id: ChannelId,
     _: mpsc::Sender<()>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
-    mut close_recv: broadcast::Receiver<()>,
+    mut close_recv: broadcast::Receiver<DisconnectReason>,
     mut channel_close_recv: mpsc::Receiver<()>,
     mut bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+    kind: DatagramKind,
 ) {
+    // Identifies the fragments of a same message (or orders messages for sequenced channels) on the receiving end.
+    // Wrapping is fine since incomplete messages expire long before an id is reused.
+    let mut next_message_id: u16 = 0;
+    let mut send = |msg_bytes: Bytes| {
+        let message_id = next_message_id;
+        next_message_id = next_message_id.wrapping_add(1);
+        match kind {
+            DatagramKind::Whole => send_unreliable_message(&datagrams, msg_bytes, channel_id),
+            DatagramKind::Fragment => {
+                send_fragmented_message(&datagrams, msg_bytes, channel_id, message_id)
+            }
+            DatagramKind::Sequenced => {
+                send_sequenced_message(&datagrams, msg_bytes, channel_id, message_id)
+            }
+        }
+    };
+
     tokio::select! {
         _ = close_recv.recv() => {
             trace!("Unreliable Channel task received a close signal")
@@ -27,7 +158,7 @@ pub(crate) async fn unreliable_channel_task(
         _ = async {
             while let Some(msg_bytes) = bytes_to_channel_recv.recv().await {
 
-                if let Err(err) = send_unreliable_message(&connection, msg_bytes, channel_id) {
+                if let Err(err) = send(msg_bytes) {
                     error!("Error while sending message on Unreliable Channel, {}", err);
                     match err {
                         SendDatagramError::UnsupportedByPeer => (),
@@ -47,7 +178,7 @@ pub(crate) async fn unreliable_channel_task(
         }
     };
     while let Ok(msg_bytes) = bytes_to_channel_recv.try_recv() {
-        if let Err(err) = send_unreliable_message(&connection, msg_bytes, channel_id) {
+        if let Err(err) = send(msg_bytes) {
             warn!(
                 "Failed to send a remaining message on Unreliable Channel, {}",
                 err
@@ -57,12 +188,66 @@ pub(crate) async fn unreliable_channel_task(
 }
 
 fn send_unreliable_message(
-    connection: &quinn::Connection,
+    datagrams: &DatagramSink,
+    msg_bytes: Bytes,
+    channel_id: ChannelId,
+) -> Result<(), SendDatagramError> {
+    let mut datagram = BytesMut::with_capacity(UNRELIABLE_HEADER_LEN + msg_bytes.len());
+    datagram.put_u8(channel_id);
+    datagram.put_u8(DatagramKind::Whole as u8);
+    datagram.extend_from_slice(&msg_bytes[..]);
+    datagrams.send(datagram.into())
+}
+
+fn send_fragmented_message(
+    datagrams: &DatagramSink,
+    msg_bytes: Bytes,
+    channel_id: ChannelId,
+    message_id: u16,
+) -> Result<(), SendDatagramError> {
+    let max_datagram_size = datagrams
+        .max_datagram_size()
+        .ok_or(SendDatagramError::UnsupportedByPeer)?;
+
+    // Messages fitting in a single datagram do not pay for the fragment header
+    if UNRELIABLE_HEADER_LEN + msg_bytes.len() <= max_datagram_size {
+        return send_unreliable_message(datagrams, msg_bytes, channel_id);
+    }
+
+    let fragment_payload_size = max_datagram_size
+        .checked_sub(UNRELIABLE_HEADER_LEN + FRAGMENT_HEADER_LEN)
+        .filter(|size| *size > 0)
+        .ok_or(SendDatagramError::TooLarge)?;
+    let fragment_count = msg_bytes.len().div_ceil(fragment_payload_size);
+    if fragment_count > MAX_FRAGMENT_COUNT {
+        return Err(SendDatagramError::TooLarge);
+    }
+
+    for (fragment_index, fragment) in msg_bytes.chunks(fragment_payload_size).enumerate() {
+        let mut datagram =
+            BytesMut::with_capacity(UNRELIABLE_HEADER_LEN + FRAGMENT_HEADER_LEN + fragment.len());
+        datagram.put_u8(channel_id);
+        datagram.put_u8(DatagramKind::Fragment as u8);
+        datagram.put_u16(message_id);
+        datagram.put_u8(fragment_index as u8);
+        datagram.put_u8(fragment_count as u8);
+        datagram.extend_from_slice(fragment);
+        datagrams.send(datagram.into())?;
+    }
+    Ok(())
+}
+
+fn send_sequenced_message(
+    datagrams: &DatagramSink,
     msg_bytes: Bytes,
     channel_id: ChannelId,
+    sequence: u16,
 ) -> Result<(), SendDatagramError> {
-    let mut datagram = BytesMut::with_capacity(PROTOCOL_HEADER_LEN + msg_bytes.len());
+    let mut datagram =
+        BytesMut::with_capacity(UNRELIABLE_HEADER_LEN + SEQUENCE_HEADER_LEN + msg_bytes.len());
     datagram.put_u8(channel_id);
+    datagram.put_u8(DatagramKind::Sequenced as u8);
+    datagram.put_u16(sequence);
     datagram.extend_from_slice(&msg_bytes[..]);
-    connection.send_datagram(datagram.into())
+    datagrams.send(datagram.into())
 }
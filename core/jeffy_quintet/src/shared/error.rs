@@ -63,12 +63,42 @@ pub enum QuintetError {
     /// A lock acquisition failed
     #[error("Lock acquisition failure")]
     LockAcquisitionFailure,
+    /// A certificate or private key in PEM form could not be parsed
+    #[error("Invalid PEM certificate or private key: {0}")]
+    InvalidPem(String),
+    /// A certificate fingerprint could not be parsed
+    #[error("The certificate fingerprint is invalid")]
+    InvalidFingerprint,
+    /// A routed message was sent by a side its route does not allow
+    #[error("Message `{0}` can not be sent in this direction")]
+    WrongMessageDirection(&'static str),
+    /// A routed message is larger than the maximum size of its route
+    #[error("Message `{0}` of {1} bytes is larger than the maximum size of its route")]
+    MessageTooLarge(&'static str, usize),
+    /// The route of a routed message is not among the routes of its type
+    #[error("Message `{0}` has no declared route")]
+    NoRoute(&'static str),
     /// A Certificate action was already sent for a CertificateInteractionEvent
     #[error("A Certificate action was already sent for a CertificateInteractionEvent")]
     CertificateActionAlreadyApplied,
+    /// A transport parameter can not be used
+    #[error("Invalid transport configuration: {0}")]
+    InvalidTransportConfig(String),
     /// Failed to read/write file(s)
     #[error("Failed to read/write file(s)")]
     IoError(#[from] io::Error),
+    /// A blob is larger than [`super::channels::MAX_BLOB_LEN`]
+    #[error("Blob of {0} bytes is larger than the maximum blob length")]
+    BlobTooLarge(usize),
+    /// A client group is unknown
+    #[error("Group `{0}` is unknown")]
+    UnknownGroup(String),
+    /// A client group with the same name already exists
+    #[error("Group `{0}` already exists")]
+    GroupAlreadyExists(String),
+    /// A joining client was refused by the admission validator
+    #[error("Client was refused: {0}")]
+    ClientRejected(String),
     ///Rustls protocol error
     #[error("Rustls protocol error")]
     RustlsError(#[from] rustls::Error),
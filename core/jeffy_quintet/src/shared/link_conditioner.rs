@@ -0,0 +1,237 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    time::Duration,
+};
+
+use bevy::log::trace;
+use bytes::Bytes;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::{
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+
+use crate::shared::{
+    channels::{ChannelId, ChannelType},
+    DEFAULT_MESSAGE_QUEUE_SIZE,
+};
+
+/// Simulated network conditions applied to the messages sent on a channel
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkConditions {
+    /// Fixed delay added to every message
+    pub latency: Duration,
+    /// Maximum random delay added on top of `latency`
+    pub jitter: Duration,
+    /// Probability (between 0 and 1) for a message to be dropped
+    pub drop_rate: f32,
+    /// Probability (between 0 and 1) for a message to be sent twice
+    pub duplication_rate: f32,
+}
+
+/// Configuration of the link conditioner, which simulates a degraded network on the messages sent by an endpoint.
+///
+/// Conditions can be set for all channels or per [`ChannelType`]. Reliable channel types keep their guarantees: only latency and jitter apply to them, and ordered channels are never reordered.
+///
+/// Random decisions come from a seeded RNG so that runs are reproducible. On a server, each client connection mixes its client id into the seed, so clients do not all see the same pattern.
+#[derive(Debug, Clone, Default)]
+pub struct LinkConditionerConfig {
+    seed: u64,
+    conditions: Option<LinkConditions>,
+    channel_type_conditions: HashMap<ChannelType, LinkConditions>,
+}
+
+impl LinkConditionerConfig {
+    /// New configuration without any conditions, using `seed` for its random decisions
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    /// Applies `conditions` to all channels which do not have conditions specific to their [`ChannelType`]
+    pub fn with_conditions(mut self, conditions: LinkConditions) -> Self {
+        self.conditions = Some(conditions);
+        self
+    }
+
+    /// Applies `conditions` to all channels of type `channel_type`
+    pub fn with_channel_type_conditions(
+        mut self,
+        channel_type: ChannelType,
+        conditions: LinkConditions,
+    ) -> Self {
+        self.channel_type_conditions
+            .insert(channel_type, conditions);
+        self
+    }
+
+    /// Returns the seed used by the conditioner RNG
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Same conditions, with a seed of its own for the connection identified by `connection_id`
+    pub(crate) fn for_connection(&self, connection_id: u64) -> Self {
+        Self {
+            // Multiplying by the golden ratio spreads consecutive ids over the whole seed range
+            seed: self.seed ^ connection_id.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the conditions applied to channels of type `channel_type`, if any
+    pub fn conditions_for(&self, channel_type: ChannelType) -> Option<LinkConditions> {
+        self.channel_type_conditions
+            .get(&channel_type)
+            .copied()
+            .or(self.conditions)
+    }
+}
+
+/// Inserts a link conditioner between a reliable channel and its sending task, if the configuration has conditions for this channel type.
+///
+/// Returns the receiver the channel sending task should read from. Unreliable channels are conditioned after framing, see [`condition_datagrams`].
+pub(crate) fn condition_channel(
+    config: Option<&LinkConditionerConfig>,
+    channel_id: ChannelId,
+    channel_type: ChannelType,
+    bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+) -> mpsc::Receiver<Bytes> {
+    if !is_reliable(channel_type) {
+        return bytes_to_channel_recv;
+    }
+    let Some((conditions, rng)) = conditions_and_rng(config, channel_id, channel_type) else {
+        return bytes_to_channel_recv;
+    };
+    let (conditioned_send, conditioned_recv) = mpsc::channel(DEFAULT_MESSAGE_QUEUE_SIZE);
+    tokio::spawn(async move {
+        link_conditioner_task(
+            conditions,
+            channel_type,
+            rng,
+            bytes_to_channel_recv,
+            conditioned_send,
+        )
+        .await
+    });
+    conditioned_recv
+}
+
+/// Inserts a link conditioner between an unreliable channel and the connection, if the configuration has conditions for this channel type.
+///
+/// The conditioner acts on whole datagrams, once stamped with their sequence number or fragment header, so that the receiving end sees the stale, duplicated and reordered datagrams a degraded network would produce.
+///
+/// Returns the sender the channel sending task should hand its datagrams to. `keepalive` is held until the delayed datagrams have been sent.
+pub(crate) fn condition_datagrams(
+    config: Option<&LinkConditionerConfig>,
+    channel_id: ChannelId,
+    channel_type: ChannelType,
+    connection: quinn::Connection,
+    keepalive: mpsc::Sender<()>,
+) -> Option<mpsc::Sender<Bytes>> {
+    if is_reliable(channel_type) {
+        return None;
+    }
+    let (conditions, rng) = conditions_and_rng(config, channel_id, channel_type)?;
+    let (datagrams_send, datagrams_recv) = mpsc::channel(DEFAULT_MESSAGE_QUEUE_SIZE);
+    let (conditioned_send, mut conditioned_recv) = mpsc::channel(DEFAULT_MESSAGE_QUEUE_SIZE);
+    tokio::spawn(async move {
+        link_conditioner_task(
+            conditions,
+            channel_type,
+            rng,
+            datagrams_recv,
+            conditioned_send,
+        )
+        .await
+    });
+    tokio::spawn(async move {
+        let _keepalive = keepalive;
+        while let Some(datagram) = conditioned_recv.recv().await {
+            if let Err(err) = connection.send_datagram(datagram) {
+                trace!("Failed to send a conditioned datagram, {}", err);
+            }
+        }
+    });
+    Some(datagrams_send)
+}
+
+fn is_reliable(channel_type: ChannelType) -> bool {
+    matches!(
+        channel_type,
+        ChannelType::OrderedReliable | ChannelType::UnorderedReliable
+    )
+}
+
+fn conditions_and_rng(
+    config: Option<&LinkConditionerConfig>,
+    channel_id: ChannelId,
+    channel_type: ChannelType,
+) -> Option<(LinkConditions, StdRng)> {
+    let config = config?;
+    let conditions = config.conditions_for(channel_type)?;
+    let rng = StdRng::seed_from_u64(config.seed.wrapping_add(channel_id as u64));
+    Some((conditions, rng))
+}
+
+async fn link_conditioner_task(
+    conditions: LinkConditions,
+    channel_type: ChannelType,
+    mut rng: StdRng,
+    mut bytes_recv: mpsc::Receiver<Bytes>,
+    conditioned_send: mpsc::Sender<Bytes>,
+) {
+    let reliable = is_reliable(channel_type);
+    let ordered = matches!(channel_type, ChannelType::OrderedReliable);
+
+    // Messages waiting for their delivery time. The sequence number keeps insertion order between messages due at the same time.
+    let mut pending: BinaryHeap<Reverse<(Instant, u64)>> = BinaryHeap::new();
+    let mut payloads: HashMap<u64, Bytes> = HashMap::new();
+    let mut next_sequence: u64 = 0;
+    let mut last_delivery = Instant::now();
+    let mut input_open = true;
+
+    while input_open || !pending.is_empty() {
+        let next_delivery = pending.peek().map(|Reverse((at, _))| *at);
+        tokio::select! {
+            msg = bytes_recv.recv(), if input_open => {
+                let Some(msg_bytes) = msg else {
+                    input_open = false;
+                    continue;
+                };
+                if !reliable && rng.gen::<f32>() < conditions.drop_rate {
+                    continue;
+                }
+                let copies = if !reliable && rng.gen::<f32>() < conditions.duplication_rate { 2 } else { 1 };
+                for _ in 0..copies {
+                    let jitter = conditions.jitter.mul_f64(rng.gen::<f64>());
+                    let mut delivery = Instant::now() + conditions.latency + jitter;
+                    if ordered {
+                        delivery = delivery.max(last_delivery);
+                    }
+                    last_delivery = delivery;
+                    pending.push(Reverse((delivery, next_sequence)));
+                    payloads.insert(next_sequence, msg_bytes.clone());
+                    next_sequence += 1;
+                }
+            }
+            _ = sleep_until(next_delivery.unwrap_or_else(Instant::now)), if next_delivery.is_some() => {
+                let now = Instant::now();
+                while let Some(Reverse((at, sequence))) = pending.peek().copied() {
+                    if at > now {
+                        break;
+                    }
+                    pending.pop();
+                    if let Some(msg_bytes) = payloads.remove(&sequence) {
+                        if conditioned_send.send(msg_bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io::{self, IoSliceMut},
+    net::SocketAddr,
+    sync::{Arc, Mutex, OnceLock},
+    task::{Context, Poll},
+};
+
+use borsh::BorshDeserialize;
+use bytes::Bytes;
+use quinn::{
+    udp::{RecvMeta, Transmit, UdpState},
+    AsyncUdpSocket, EndpointConfig, ServerConfig, TokioRuntime,
+};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+/// Max number of datagrams buffered by a loopback socket before new ones get dropped, like a full UDP receive buffer would.
+pub const LOOPBACK_SOCKET_QUEUE_SIZE: usize = 4096;
+
+/// First port handed out when a loopback socket is bound to port 0
+const LOOPBACK_EPHEMERAL_PORT_START: u16 = 49152;
+
+/// Transport used by an endpoint to exchange its QUIC packets
+#[derive(Debug, BorshDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EndpointTransport {
+    /// Packets are sent over real UDP sockets
+    #[default]
+    Udp,
+    /// Packets are exchanged over in-memory queues with endpoints of the same process. Loopback addresses live in their own namespace and never conflict with UDP ports.
+    Loopback,
+}
+
+type LoopbackDatagram = (SocketAddr, Bytes);
+type LoopbackRegistry = HashMap<SocketAddr, mpsc::Sender<LoopbackDatagram>>;
+
+fn registry() -> &'static Mutex<LoopbackRegistry> {
+    static REGISTRY: OnceLock<Mutex<LoopbackRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Creates a quinn endpoint bound to `local_addr` on the chosen [`EndpointTransport`]
+pub(crate) fn bind_endpoint(
+    transport: EndpointTransport,
+    local_addr: SocketAddr,
+    server_config: Option<ServerConfig>,
+) -> io::Result<quinn::Endpoint> {
+    match transport {
+        EndpointTransport::Udp => match server_config {
+            Some(server_config) => quinn::Endpoint::server(server_config, local_addr),
+            None => quinn::Endpoint::client(local_addr),
+        },
+        EndpointTransport::Loopback => quinn::Endpoint::new_with_abstract_socket(
+            EndpointConfig::default(),
+            server_config,
+            LoopbackSocket::bind(local_addr)?,
+            Arc::new(TokioRuntime),
+        ),
+    }
+}
+
+/// In-memory socket exchanging datagrams with the other loopback sockets of the process
+#[derive(Debug)]
+pub(crate) struct LoopbackSocket {
+    local_addr: SocketAddr,
+    incoming: Mutex<mpsc::Receiver<LoopbackDatagram>>,
+}
+
+impl LoopbackSocket {
+    pub(crate) fn bind(mut local_addr: SocketAddr) -> io::Result<Self> {
+        let mut registry = registry()
+            .lock()
+            .map_err(|_| io::Error::other("Loopback registry poisoned"))?;
+        if local_addr.port() == 0 {
+            let port = (LOOPBACK_EPHEMERAL_PORT_START..=u16::MAX)
+                .find(|port| !registry.contains_key(&SocketAddr::new(local_addr.ip(), *port)))
+                .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?;
+            local_addr.set_port(port);
+        } else if registry.contains_key(&local_addr) {
+            return Err(io::Error::from(io::ErrorKind::AddrInUse));
+        }
+
+        let (incoming_send, incoming_recv) = mpsc::channel(LOOPBACK_SOCKET_QUEUE_SIZE);
+        registry.insert(local_addr, incoming_send);
+        Ok(Self {
+            local_addr,
+            incoming: Mutex::new(incoming_recv),
+        })
+    }
+}
+
+impl Drop for LoopbackSocket {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = registry().lock() {
+            registry.remove(&self.local_addr);
+        }
+    }
+}
+
+impl AsyncUdpSocket for LoopbackSocket {
+    fn poll_send(
+        &self,
+        _state: &UdpState,
+        _cx: &mut Context,
+        transmits: &[Transmit],
+    ) -> Poll<Result<usize, io::Error>> {
+        let registry = registry()
+            .lock()
+            .map_err(|_| io::Error::other("Loopback registry poisoned"))?;
+        for transmit in transmits {
+            // Like UDP, datagrams sent to nobody or to a full socket are silently lost
+            let Some(peer) = registry.get(&transmit.destination) else {
+                continue;
+            };
+            let len = transmit.contents.len();
+            let segment_size = transmit.segment_size.unwrap_or(len).max(1);
+            for start in (0..len).step_by(segment_size) {
+                let end = (start + segment_size).min(len);
+                let _ = peer.try_send((self.local_addr, transmit.contents.slice(start..end)));
+            }
+        }
+        Poll::Ready(Ok(transmits.len()))
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let mut incoming = self
+            .incoming
+            .lock()
+            .map_err(|_| io::Error::other("Loopback socket poisoned"))?;
+        let mut count = 0;
+        while count < bufs.len().min(meta.len()) {
+            let (source, contents) = if count == 0 {
+                match incoming.poll_recv(cx) {
+                    Poll::Ready(Some(datagram)) => datagram,
+                    Poll::Ready(None) => {
+                        return Poll::Ready(Err(io::Error::from(io::ErrorKind::BrokenPipe)))
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            } else {
+                match incoming.try_recv() {
+                    Ok(datagram) => datagram,
+                    Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+                }
+            };
+            let len = contents.len().min(bufs[count].len());
+            bufs[count][..len].copy_from_slice(&contents[..len]);
+            meta[count] = RecvMeta {
+                addr: source,
+                len,
+                stride: len,
+                ecn: None,
+                dst_ip: Some(self.local_addr.ip()),
+            };
+            count += 1;
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn may_fragment(&self) -> bool {
+        false
+    }
+}
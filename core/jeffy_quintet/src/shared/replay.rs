@@ -0,0 +1,225 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    time::Instant,
+};
+
+use bevy::{app::App, log::error};
+
+use crate::{
+    client::{
+        certificate::CertificateVerificationMode,
+        connection::{ClientEndpointConfiguration, ConnectionLocalId, ConnectionState},
+        QuintetClient, QuintetClientPlugin,
+    },
+    server::{
+        certificate::CertificateRetrievalMode, QuintetServer, QuintetServerPlugin,
+        ServerEndpointConfiguration,
+    },
+    shared::{
+        capture::{CaptureDirection, CaptureRecord},
+        channels::ChannelsConfiguration,
+        error::QuintetError,
+        ClientId, LOOPBACK_IP,
+    },
+};
+
+/// Captured payloads waiting to be replayed with their original timing
+struct ReplaySchedule {
+    records: VecDeque<CaptureRecord>,
+    first_timestamp_us: u64,
+    started_at: Option<Instant>,
+}
+
+impl ReplaySchedule {
+    fn new(records: Vec<CaptureRecord>) -> Self {
+        let records: VecDeque<CaptureRecord> = records
+            .into_iter()
+            .filter(|record| record.direction == CaptureDirection::Received)
+            .collect();
+        Self {
+            first_timestamp_us: records.front().map_or(0, |record| record.timestamp_us),
+            records,
+            started_at: None,
+        }
+    }
+
+    fn next_due(&mut self) -> Option<CaptureRecord> {
+        let elapsed_us = self.started_at?.elapsed().as_micros() as u64;
+        let record = self.records.front()?;
+        if record.timestamp_us.saturating_sub(self.first_timestamp_us) > elapsed_us {
+            return None;
+        }
+        self.records.pop_front()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// Feeds a server endpoint, offline, with the payloads its clients sent during a captured session.
+///
+/// Each captured client is impersonated by a connection over the [`crate::shared::loopback::EndpointTransport::Loopback`] transport, which sends again the payloads the server received from it, with their original timing. The replayed server must listen on the loopback transport and open the same channels as during the capture.
+pub struct ServerReplay {
+    app: App,
+    schedule: ReplaySchedule,
+    connections: HashMap<ClientId, ConnectionLocalId>,
+}
+
+impl ServerReplay {
+    /// Opens one connection per captured client to the server listening on loopback port `server_port`.
+    ///
+    /// `records` should come from a capture recorded on the server, and `channels_config` should match the channels opened by the captured clients.
+    pub fn new(
+        records: Vec<CaptureRecord>,
+        server_port: u16,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<Self, QuintetError> {
+        let schedule = ReplaySchedule::new(records);
+        let mut app = App::new();
+        app.add_plugins(QuintetClientPlugin::default());
+
+        let mut client = app.world_mut().resource_mut::<QuintetClient>();
+        let mut connections = HashMap::new();
+        for client_id in schedule.records.iter().filter_map(|record| record.client_id) {
+            if let Entry::Vacant(entry) = connections.entry(client_id) {
+                entry.insert(client.open_connection(
+                    ClientEndpointConfiguration::loopback(server_port),
+                    CertificateVerificationMode::SkipVerification,
+                    channels_config.clone(),
+                )?);
+            }
+        }
+
+        Ok(Self {
+            app,
+            schedule,
+            connections,
+        })
+    }
+
+    /// Advances the replay. Payloads start being sent once every captured client is connected.
+    ///
+    /// Returns true when all the captured payloads were sent.
+    pub fn update(&mut self) -> bool {
+        self.app.update();
+        let mut client = self.app.world_mut().resource_mut::<QuintetClient>();
+
+        // Messages of the replayed server are not checked, only drained
+        for (_, connection) in client.connections_mut() {
+            while let Ok(Some(_)) = connection.receive_payload() {}
+        }
+
+        if self.schedule.started_at.is_none() {
+            let all_connected = self.connections.values().all(|connection_id| {
+                client
+                    .get_connection_by_id(*connection_id)
+                    .is_some_and(|connection| connection.state() == ConnectionState::Connected)
+            });
+            if !all_connected {
+                return false;
+            }
+            self.schedule.started_at = Some(Instant::now());
+        }
+
+        while let Some(record) = self.schedule.next_due() {
+            let Some(connection) = record
+                .client_id
+                .and_then(|client_id| self.connections.get(&client_id))
+                .and_then(|connection_id| client.get_connection_by_id(*connection_id))
+            else {
+                continue;
+            };
+            match connection.send_payload_on(record.channel_id, record.payload.clone()) {
+                Ok(_) => (),
+                Err(QuintetError::FullQueue) => {
+                    // Retried on the next update
+                    self.schedule.records.push_front(record);
+                    break;
+                }
+                Err(err) => error!(
+                    "Failed to replay a payload of client {:?}: {}",
+                    record.client_id, err
+                ),
+            }
+        }
+        self.schedule.is_finished()
+    }
+}
+
+/// Feeds a client connection, offline, with the payloads its server sent during a captured session.
+///
+/// The server is impersonated by an endpoint listening on the [`crate::shared::loopback::EndpointTransport::Loopback`] transport, which sends again to the first client connecting the payloads the captured client received, with their original timing. The replayed client must connect over the loopback transport and accept the self-signed certificate of the replay endpoint.
+pub struct ClientReplay {
+    app: App,
+    schedule: ReplaySchedule,
+    client_id: Option<ClientId>,
+}
+
+impl ClientReplay {
+    /// Starts an endpoint on loopback port `port`, waiting for the replayed client.
+    ///
+    /// `records` should come from a capture recorded on the client, and `channels_config` should match the channels opened by the captured server.
+    pub fn new(
+        records: Vec<CaptureRecord>,
+        port: u16,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<Self, QuintetError> {
+        let mut app = App::new();
+        app.add_plugins(QuintetServerPlugin::default());
+        app.world_mut()
+            .resource_mut::<QuintetServer>()
+            .start_endpoint(
+                ServerEndpointConfiguration::loopback(port),
+                CertificateRetrievalMode::GenerateSelfSigned {
+                    server_hostname: LOOPBACK_IP.to_string(),
+                },
+                channels_config,
+            )?;
+
+        Ok(Self {
+            app,
+            schedule: ReplaySchedule::new(records),
+            client_id: None,
+        })
+    }
+
+    /// Advances the replay. Payloads start being sent once the replayed client is connected.
+    ///
+    /// Returns true when all the captured payloads were sent.
+    pub fn update(&mut self) -> bool {
+        self.app.update();
+        let mut server = self.app.world_mut().resource_mut::<QuintetServer>();
+        let endpoint = server.endpoint_mut();
+
+        // Messages of the replayed client are not checked, only drained
+        for client_id in endpoint.clients() {
+            while let Ok(Some(_)) = endpoint.receive_payload_from(client_id) {}
+        }
+
+        let client_id = match self.client_id {
+            Some(client_id) => client_id,
+            None => match endpoint.clients().first() {
+                Some(client_id) => {
+                    self.client_id = Some(*client_id);
+                    self.schedule.started_at = Some(Instant::now());
+                    *client_id
+                }
+                None => return false,
+            },
+        };
+
+        while let Some(record) = self.schedule.next_due() {
+            match endpoint.send_payload_on(client_id, record.channel_id, record.payload.clone()) {
+                Ok(_) => (),
+                Err(QuintetError::FullQueue) => {
+                    // Retried on the next update
+                    self.schedule.records.push_front(record);
+                    break;
+                }
+                Err(err) => error!("Failed to replay a payload to client {}: {}", client_id, err),
+            }
+        }
+        self.schedule.is_finished()
+    }
+}
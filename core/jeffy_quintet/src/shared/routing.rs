@@ -0,0 +1,209 @@
+use borsh::BorshSerialize;
+use bytes::Bytes;
+
+use super::{
+    channels::{
+        ChannelConfig, ChannelId, ChannelPriority, ChannelType, ChannelsConfiguration,
+        DEFAULT_CHANNEL_PRIORITY,
+    },
+    error::QuintetError,
+};
+
+/// Side(s) of a connection allowed to send a message
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MessageDirection {
+    /// Only sent by clients, to the server
+    ClientToServer,
+    /// Only sent by the server, to clients
+    ServerToClient,
+    /// Sent by both sides
+    Both,
+}
+
+impl MessageDirection {
+    /// Returns `true` if a message with this direction can be sent in the `sent` direction
+    pub const fn allows(self, sent: MessageDirection) -> bool {
+        matches!(
+            (self, sent),
+            (MessageDirection::Both, _)
+                | (MessageDirection::ClientToServer, MessageDirection::ClientToServer)
+                | (MessageDirection::ServerToClient, MessageDirection::ServerToClient)
+        )
+    }
+}
+
+/// How a message is sent: on which kind of channel, by which side, and its maximum serialized size
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MessageRoute {
+    /// Channel the message is sent on. Each distinct [`ChannelConfig`] among the routes of a message type gets its own channel
+    pub channel: ChannelConfig,
+    /// Side(s) allowed to send the message
+    pub direction: MessageDirection,
+    /// Maximum size of the serialized message, larger messages are refused before being sent
+    pub max_size: usize,
+}
+
+impl MessageRoute {
+    /// Route on a channel of type `channel_type` with the default [`ChannelPriority`]
+    pub const fn new(
+        channel_type: ChannelType,
+        direction: MessageDirection,
+        max_size: usize,
+    ) -> Self {
+        Self {
+            channel: channel_type.with_priority(DEFAULT_CHANNEL_PRIORITY),
+            direction,
+            max_size,
+        }
+    }
+
+    /// Same route, on a channel with the given priority
+    pub const fn with_priority(mut self, priority: ChannelPriority) -> Self {
+        self.channel.priority = priority;
+        self
+    }
+}
+
+/// A message type which declares the [`MessageRoute`] of each of its messages, usually an enum with a route per variant.
+///
+/// Routed messages are sent with the `send_typed` methods of a [`crate::server::Endpoint`] or a [`crate::client::connection::Connection`], which pick the channel of the message and refuse messages sent in the wrong direction. Both sides must open the channels of [`RoutedMessage::channels_configuration`] (and only those) so that the channel ids match.
+pub trait RoutedMessage: BorshSerialize {
+    /// Routes of all the possible messages of this type, in a fixed order. Channels ids are given to the distinct [`ChannelConfig`] in their order of first appearance
+    fn routes() -> &'static [MessageRoute];
+
+    /// Route of this message, must be one of [`RoutedMessage::routes`]
+    fn route(&self) -> MessageRoute;
+
+    /// Name of this message, used in errors
+    fn name(&self) -> &'static str;
+
+    /// Channels of [`RoutedMessage::routes`], built once for the message type
+    fn route_table() -> &'static RouteTable;
+
+    /// Configuration of the channels used by the routes, to give to the server endpoint and to the client connections
+    fn channels_configuration() -> ChannelsConfiguration {
+        let mut channels = ChannelsConfiguration::new();
+        for channel in Self::route_table().channels.iter() {
+            channels.add(*channel);
+        }
+        channels
+    }
+}
+
+/// The distinct [`ChannelConfig`] of a set of routes, in their order of first appearance. The index of a channel is its id
+#[derive(Debug, Clone)]
+pub struct RouteTable {
+    channels: Vec<ChannelConfig>,
+}
+
+impl RouteTable {
+    /// Builds the table of the given routes
+    pub fn new(routes: &[MessageRoute]) -> Self {
+        let mut channels: Vec<ChannelConfig> = Vec::new();
+        for route in routes {
+            if !channels.contains(&route.channel) {
+                channels.push(route.channel);
+            }
+        }
+        Self { channels }
+    }
+
+    /// Id of the channel with this configuration, if one of the routes uses it
+    pub fn channel_id(&self, channel: &ChannelConfig) -> Option<ChannelId> {
+        self.channels
+            .iter()
+            .position(|config| config == channel)
+            .map(|index| index as ChannelId)
+    }
+}
+
+/// Checks the direction and size of a routed message, and returns its serialized form with the id of its channel
+pub(crate) fn serialize_routed<M: RoutedMessage>(
+    message: &M,
+    sent: MessageDirection,
+) -> Result<(ChannelId, Bytes), QuintetError> {
+    let route = message.route();
+    if !route.direction.allows(sent) {
+        return Err(QuintetError::WrongMessageDirection(message.name()));
+    }
+    let payload = borsh::to_vec(message).map_err(|_| QuintetError::Serialization)?;
+    if payload.len() > route.max_size {
+        return Err(QuintetError::MessageTooLarge(message.name(), payload.len()));
+    }
+    let channel_id = M::route_table()
+        .channel_id(&route.channel)
+        .ok_or(QuintetError::NoRoute(message.name()))?;
+    Ok((channel_id, payload.into()))
+}
+
+/// Declares an enum along with its [`RoutedMessage`] implementation, by giving a route to each variant.
+///
+/// A route is written `(channel_type, direction, max_size)` or `(channel_type, direction, max_size, priority)`, with the variant names of [`ChannelType`] and [`MessageDirection`]:
+///
+/// ```
+/// # use borsh::BorshSerialize;
+/// jeffy_quintet::routed_message! {
+///     #[derive(BorshSerialize)]
+///     pub enum Example {
+///         Move(u32) => (Unreliable, ClientToServer, 16),
+///         Chat(String) => (OrderedReliable, Both, 256),
+///         Welcome => (OrderedReliable, ServerToClient, 1, 10),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! routed_message {
+    (@route $channel_type:ident, $direction:ident, $max_size:expr) => {
+        $crate::shared::routing::MessageRoute::new(
+            $crate::shared::channels::ChannelType::$channel_type,
+            $crate::shared::routing::MessageDirection::$direction,
+            $max_size,
+        )
+    };
+    (@route $channel_type:ident, $direction:ident, $max_size:expr, $priority:expr) => {
+        $crate::routed_message!(@route $channel_type, $direction, $max_size).with_priority($priority)
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $e_ident:ident {
+            $(
+                $(#[$v_attr:meta])*
+                $v_ident:ident $(($($v_field:ty),* $(,)?))? => ($($route:tt)*)
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $e_ident {
+            $(
+                $(#[$v_attr])*
+                $v_ident $(($($v_field),*))?
+            ),*
+        }
+
+        impl $crate::shared::routing::RoutedMessage for $e_ident {
+            fn routes() -> &'static [$crate::shared::routing::MessageRoute] {
+                const ROUTES: &[$crate::shared::routing::MessageRoute] =
+                    &[$($crate::routed_message!(@route $($route)*)),*];
+                ROUTES
+            }
+
+            fn route_table() -> &'static $crate::shared::routing::RouteTable {
+                static TABLE: ::std::sync::OnceLock<$crate::shared::routing::RouteTable> =
+                    ::std::sync::OnceLock::new();
+                TABLE.get_or_init(|| $crate::shared::routing::RouteTable::new(Self::routes()))
+            }
+
+            fn route(&self) -> $crate::shared::routing::MessageRoute {
+                match self {
+                    $(Self::$v_ident { .. } => $crate::routed_message!(@route $($route)*)),*
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$v_ident { .. } => stringify!($v_ident)),*
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,98 @@
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use crate::shared::channels::ChannelId;
+
+/// Traffic statistics of a channel on a connection, as seen by the local side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// Number of messages queued for sending on the channel
+    pub sent_messages: u64,
+    /// Number of payload bytes queued for sending on the channel
+    pub sent_bytes: u64,
+    /// Number of messages read from the channel
+    pub received_messages: u64,
+    /// Number of payload bytes read from the channel
+    pub received_bytes: u64,
+    /// Number of messages refused with [`crate::shared::error::QuintetError::FullQueue`] because the channel queue was full
+    pub full_queue_rejections: u64,
+}
+
+impl ChannelStats {
+    fn add(&mut self, other: &ChannelStats) {
+        self.sent_messages += other.sent_messages;
+        self.sent_bytes += other.sent_bytes;
+        self.received_messages += other.received_messages;
+        self.received_bytes += other.received_bytes;
+        self.full_queue_rejections += other.full_queue_rejections;
+    }
+}
+
+/// Traffic statistics of a connection, per channel
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Smoothed round-trip time estimated by QUIC. [`None`] if not connected
+    pub rtt: Option<Duration>,
+    /// Statistics of every channel which had some traffic on the connection, including closed ones
+    pub channels: BTreeMap<ChannelId, ChannelStats>,
+}
+
+impl TrafficStats {
+    /// Returns the sum of the statistics of all the channels
+    pub fn total(&self) -> ChannelStats {
+        let mut total = ChannelStats::default();
+        for channel_stats in self.channels.values() {
+            total.add(channel_stats);
+        }
+        total
+    }
+}
+
+/// Per channel traffic counters of a connection, updated by the sync client & server
+#[derive(Debug, Default)]
+pub(crate) struct ChannelsStats {
+    channels: Mutex<BTreeMap<ChannelId, ChannelStats>>,
+}
+
+impl ChannelsStats {
+    fn update<F: FnOnce(&mut ChannelStats)>(&self, channel_id: ChannelId, update: F) {
+        if let Ok(mut channels) = self.channels.lock() {
+            update(channels.entry(channel_id).or_default());
+        }
+    }
+
+    pub(crate) fn record_sent(&self, channel_id: ChannelId, len: usize) {
+        self.update(channel_id, |stats| {
+            stats.sent_messages += 1;
+            stats.sent_bytes += len as u64;
+        });
+    }
+
+    pub(crate) fn record_full_queue(&self, channel_id: ChannelId) {
+        self.update(channel_id, |stats| stats.full_queue_rejections += 1);
+    }
+
+    pub(crate) fn record_received(&self, channel_id: ChannelId, len: usize) {
+        self.update(channel_id, |stats| {
+            stats.received_messages += 1;
+            stats.received_bytes += len as u64;
+        });
+    }
+
+    pub(crate) fn channel(&self, channel_id: ChannelId) -> Option<ChannelStats> {
+        self.channels
+            .lock()
+            .ok()
+            .and_then(|channels| channels.get(&channel_id).copied())
+    }
+
+    pub(crate) fn traffic_stats(&self, rtt: Option<Duration>) -> TrafficStats {
+        TrafficStats {
+            rtt,
+            channels: self
+                .channels
+                .lock()
+                .map(|channels| channels.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{sync::Arc, time::Duration};
+
+use quinn::{
+    congestion::{BbrConfig, CubicConfig, NewRenoConfig},
+    IdleTimeout, VarInt,
+};
+
+use super::{
+    channels::DEFAULT_MAX_RELIABLE_FRAME_LEN, error::QuintetError,
+    DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE, DEFAULT_KEEP_ALIVE_INTERVAL_S,
+    DEFAULT_MESSAGE_QUEUE_SIZE,
+};
+
+/// Default maximum duration of inactivity before a connection is timed out
+pub const DEFAULT_MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default maximum duration a server waits for the join request of a new connection
+pub const DEFAULT_JOIN_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default maximum number of streams of each kind (unidirectional, bidirectional) the remote side may have open at the same time
+pub const DEFAULT_MAX_CONCURRENT_STREAMS: u32 = 100;
+
+/// Congestion control algorithm used by the connections of an endpoint
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CongestionController {
+    /// CUBIC (RFC 8312), the default, suited to most networks
+    #[default]
+    Cubic,
+    /// NewReno (RFC 6582), more conservative
+    NewReno,
+    /// BBR, models the path bandwidth and round-trip time instead of reacting to losses. Can do better on lossy links such as mobile networks
+    Bbr,
+}
+
+/// Transport parameters of the connections of an endpoint, set on a [`crate::server::ServerEndpointConfiguration`] or a [`crate::client::connection::ClientEndpointConfiguration`]
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use jeffy_quintet::shared::transport::{CongestionController, TransportConfig};
+/// let config = TransportConfig {
+///     max_idle_timeout: Some(Duration::from_secs(30)),
+///     congestion_controller: CongestionController::Bbr,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportConfig {
+    /// Period of inactivity before sending a keep-alive packet. `None` to disable keep-alive packets
+    pub keep_alive_interval: Option<Duration>,
+    /// Maximum duration of inactivity before the connection is timed out. The lowest of the two sides' values is used. `None` to never time out
+    pub max_idle_timeout: Option<Duration>,
+    /// Maximum duration a server waits for the join request of a new connection before closing it. Unused by clients
+    pub join_request_timeout: Duration,
+    /// Maximum number of unidirectional streams the remote side may have open at the same time. Each reliable channel uses at least one, and each blob being transferred one
+    pub max_concurrent_uni_streams: u32,
+    /// Maximum number of bidirectional streams the remote side may have open at the same time
+    pub max_concurrent_bidi_streams: u32,
+    /// Maximum length of a message sent or received on a reliable channel. Both sides should use the same value
+    pub max_reliable_frame_len: usize,
+    /// Congestion control algorithm
+    pub congestion_controller: CongestionController,
+    /// Max size of the queues holding the network messages: messages received from the remote side, and messages waiting to be sent on each channel
+    pub message_queue_size: usize,
+    /// Max size of the queues used internally between the sync and async sides
+    pub internal_message_channel_size: usize,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive_interval: Some(DEFAULT_KEEP_ALIVE_INTERVAL_S),
+            max_idle_timeout: Some(DEFAULT_MAX_IDLE_TIMEOUT),
+            join_request_timeout: DEFAULT_JOIN_REQUEST_TIMEOUT,
+            max_concurrent_uni_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+            max_concurrent_bidi_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+            max_reliable_frame_len: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+            congestion_controller: CongestionController::default(),
+            message_queue_size: DEFAULT_MESSAGE_QUEUE_SIZE,
+            internal_message_channel_size: DEFAULT_INTERNAL_MESSAGE_CHANNEL_SIZE,
+        }
+    }
+}
+
+impl TransportConfig {
+    pub(crate) fn to_quinn_transport_config(&self) -> Result<quinn::TransportConfig, QuintetError> {
+        let max_idle_timeout = match self.max_idle_timeout {
+            Some(timeout) => Some(IdleTimeout::try_from(timeout).map_err(|_| {
+                QuintetError::InvalidTransportConfig("max_idle_timeout is too large".to_string())
+            })?),
+            None => None,
+        };
+
+        let mut transport = quinn::TransportConfig::default();
+        transport
+            .keep_alive_interval(self.keep_alive_interval)
+            .max_idle_timeout(max_idle_timeout)
+            .max_concurrent_uni_streams(VarInt::from_u32(self.max_concurrent_uni_streams))
+            .max_concurrent_bidi_streams(VarInt::from_u32(self.max_concurrent_bidi_streams));
+        match self.congestion_controller {
+            CongestionController::Cubic => {
+                transport.congestion_controller_factory(Arc::new(CubicConfig::default()))
+            }
+            CongestionController::NewReno => {
+                transport.congestion_controller_factory(Arc::new(NewRenoConfig::default()))
+            }
+            CongestionController::Bbr => {
+                transport.congestion_controller_factory(Arc::new(BbrConfig::default()))
+            }
+        };
+        Ok(transport)
+    }
+}
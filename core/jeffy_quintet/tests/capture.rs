@@ -0,0 +1,167 @@
+use std::{thread::sleep, time::Duration};
+
+use bevy::prelude::App;
+
+use jeffy_quintet::{
+    client::QuintetClient,
+    server::QuintetServer,
+    shared::{
+        capture::{read_capture, CaptureDirection},
+        channels::ChannelsConfiguration,
+        replay::{ClientReplay, ServerReplay},
+    },
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn capture_and_replay() {
+    let server_capture_path = std::env::temp_dir().join("jeffy_quintet_test_server.jqcap");
+    let client_capture_path = std::env::temp_dir().join("jeffy_quintet_test_client.jqcap");
+
+    let client_messages: Vec<SharedMessage> = (0..3)
+        .map(|i| SharedMessage::TestMessage(format!("Client {}", i)))
+        .collect();
+    let server_messages: Vec<SharedMessage> = (0..2)
+        .map(|i| SharedMessage::TestMessage(format!("Server {}", i)))
+        .collect();
+
+    // Capture
+    let port = 6001;
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_loopback_client_app(port);
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .start_capture(&server_capture_path)
+        .unwrap();
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .start_capture(&client_capture_path)
+        .unwrap();
+
+    for message in client_messages.iter() {
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .send_message_on(channel, message.clone())
+            .unwrap();
+        assert_eq!(
+            wait_for_client_message(client_id, &mut server_app).1,
+            *message
+        );
+    }
+    for message in server_messages.iter() {
+        server_app
+            .world()
+            .resource::<QuintetServer>()
+            .endpoint()
+            .send_message_on(client_id, channel, message.clone())
+            .unwrap();
+        assert_eq!(wait_for_server_message(&mut client_app).1, *message);
+    }
+
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .stop_capture()
+        .unwrap();
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .stop_capture()
+        .unwrap();
+
+    let server_records = read_capture(&server_capture_path).unwrap();
+    assert_eq!(server_records.len(), 5);
+    let client_records = read_capture(&client_capture_path).unwrap();
+    assert_eq!(client_records.len(), 5);
+    for (index, message) in client_messages.iter().enumerate() {
+        let payload = borsh::to_vec(message).unwrap();
+        let server_record = &server_records[index];
+        assert_eq!(server_record.direction, CaptureDirection::Received);
+        assert_eq!(server_record.client_id, Some(client_id));
+        assert_eq!(server_record.channel_id, channel);
+        assert_eq!(server_record.payload, payload);
+        let client_record = &client_records[index];
+        assert_eq!(client_record.direction, CaptureDirection::Sent);
+        assert_eq!(client_record.client_id, Some(client_id));
+        assert_eq!(client_record.payload, payload);
+    }
+    for (index, message) in server_messages.iter().enumerate() {
+        let payload = borsh::to_vec(message).unwrap();
+        let server_record = &server_records[client_messages.len() + index];
+        assert_eq!(server_record.direction, CaptureDirection::Sent);
+        assert_eq!(server_record.payload, payload);
+        let client_record = &client_records[client_messages.len() + index];
+        assert_eq!(client_record.direction, CaptureDirection::Received);
+        assert_eq!(client_record.payload, payload);
+    }
+    assert!(server_records
+        .windows(2)
+        .all(|records| records[0].timestamp_us <= records[1].timestamp_us));
+
+    // Replay of the clients messages into a new server
+    let port = 6002;
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut server_replay =
+        ServerReplay::new(server_records, port, ChannelsConfiguration::default()).unwrap();
+    let mut replayed = Vec::new();
+    let mut finished = false;
+    while !finished || replayed.len() < client_messages.len() {
+        finished = server_replay.update();
+        server_app.update();
+        let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+        let endpoint = server.endpoint_mut();
+        for replay_client_id in endpoint.clients() {
+            while let Some((_, message)) =
+                endpoint.try_receive_message_from::<SharedMessage>(replay_client_id)
+            {
+                replayed.push(message);
+            }
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(replayed, client_messages);
+
+    // Replay of the server messages into a new client
+    let port = 6003;
+    let mut client_replay =
+        ClientReplay::new(client_records, port, ChannelsConfiguration::default()).unwrap();
+    let mut client_app: App = start_loopback_client_app(port);
+    let mut replayed = Vec::new();
+    let mut finished = false;
+    while !finished || replayed.len() < server_messages.len() {
+        finished = client_replay.update();
+        client_app.update();
+        let mut client = client_app.world_mut().resource_mut::<QuintetClient>();
+        while let Ok(Some((_, message))) =
+            client.connection_mut().receive_message::<SharedMessage>()
+        {
+            replayed.push(message);
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(replayed, server_messages);
+
+    let _ = std::fs::remove_file(server_capture_path);
+    let _ = std::fs::remove_file(client_capture_path);
+}
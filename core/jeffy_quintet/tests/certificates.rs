@@ -14,7 +14,10 @@ use jeffy_quintet::{
         certificate::CertificateRetrievalMode, QuintetServer, QuintetServerPlugin,
         ServerEndpointConfiguration,
     },
-    shared::channels::ChannelsConfiguration,
+    shared::{
+        certificate::CertificateFingerprint, channels::ChannelsConfiguration,
+        error::QuintetError,
+    },
 };
 
 // https://github.com/rust-lang/rust/issues/46379
@@ -308,3 +311,147 @@ fn trust_on_first_use() {
     // Leave the workspace clean
     fs::remove_file(DEFAULT_KNOWN_HOSTS_FILE).expect("Failed to remove default known hosts file");
 }
+
+#[test]
+fn certificate_from_pem() {
+    let port = 6005; // TODO Use port 0 and retrieve the port used by the server.
+
+    let rcgen_cert = rcgen::generate_simple_self_signed(vec![SERVER_IP.to_string()]).unwrap();
+    // Each serialization signs the certificate again, serialize it only once
+    let cert_pem = rcgen_cert.serialize_pem().unwrap();
+    let key_pem = rcgen_cert.serialize_private_key_pem();
+    let cert_der = rustls_pemfile::certs(&mut cert_pem.as_bytes()).unwrap().remove(0);
+    let expected_fingerprint = CertificateFingerprint::from(&rustls::Certificate(cert_der));
+
+    let mut server_app = build_server_app_without_endpoint();
+    server_app.update();
+    let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+
+    // Invalid PEM strings are refused and no endpoint is started
+    let result = server.start_endpoint(
+        ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+        CertificateRetrievalMode::FromPem {
+            cert: cert_pem.clone(),
+            key: "Not a private key".to_string(),
+        },
+        ChannelsConfiguration::default(),
+    );
+    assert!(
+        matches!(result, Err(QuintetError::InvalidPem(_))),
+        "A missing private key should be refused"
+    );
+    assert!(!server.is_listening());
+
+    let server_cert = server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::FromPem {
+                cert: cert_pem,
+                key: key_pem,
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    assert_eq!(
+        server_cert.fingerprint, expected_fingerprint,
+        "The loaded cert fingerprint should match the fingerprint of the PEM certificate"
+    );
+
+    // A client can connect to a server using a certificate loaded from PEM
+    let mut client_app = build_client_app_without_connection();
+    client_app.update();
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    wait_for_client_connected(&mut client_app, &mut server_app);
+}
+
+#[test]
+fn pinned_fingerprints() {
+    let port = 6006; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut client_app = build_client_app_without_connection();
+    let mut server_app = build_server_app_without_endpoint();
+    client_app.update();
+    server_app.update();
+
+    let server_cert = server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    // As a server list would publish it
+    let published_fingerprint = server_cert.fingerprint.to_base64();
+    let other_fingerprint = CertificateFingerprint::new([7; 32]);
+    assert!(matches!(
+        CertificateFingerprint::from_base64("Not a fingerprint"),
+        Err(QuintetError::InvalidFingerprint)
+    ));
+
+    // The server's certificate is pinned: the connection succeeds
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::PinnedFingerprints(vec![
+                other_fingerprint.clone(),
+                CertificateFingerprint::from_base64(&published_fingerprint).unwrap(),
+            ]),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .close_all_connections()
+        .unwrap();
+
+    // The server's certificate is not pinned: the connection is aborted
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::PinnedFingerprints(vec![other_fingerprint]),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    while client_app
+        .world()
+        .resource::<ClientTestData>()
+        .cert_verif_connection_abort_events_received
+        == 0
+    {
+        server_app.update();
+        client_app.update();
+        sleep(Duration::from_millis(10));
+    }
+
+    let client_test_data = client_app.world().resource::<ClientTestData>();
+    assert_eq!(
+        client_test_data.last_abort_cert_status,
+        Some(CertVerificationStatus::UntrustedCertificate),
+        "The certificate verification status in the connection abort event should be `Untrusted`"
+    );
+    let abort_cert_info = client_test_data.last_abort_cert_info.as_ref().unwrap();
+    assert_eq!(abort_cert_info.fingerprint.to_base64(), published_fingerprint);
+    assert_eq!(abort_cert_info.server_name.to_string(), SERVER_IP.to_string());
+    assert!(
+        !client_app.world().resource::<QuintetClient>().is_connected(),
+        "The default connection should not be connected to the server"
+    );
+}
@@ -1,9 +1,29 @@
-use bevy::prelude::App;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::event::{Events, ManualEventReader},
+    prelude::App,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
 
 use jeffy_quintet::{
-    client::QuintetClient,
-    server::QuintetServer,
-    shared::{channels::ChannelType, error::QuintetError},
+    client::{
+        self, certificate::CertificateVerificationMode, connection::ClientEndpointConfiguration,
+        QuintetClient,
+    },
+    routed_message,
+    server::{
+        self, certificate::CertificateRetrievalMode, QuintetServer, ServerEndpointConfiguration,
+    },
+    shared::{
+        channels::{ChannelType, DEFAULT_BLOB_CHUNK_SIZE, DEFAULT_CHANNEL_PRIORITY},
+        error::QuintetError,
+        link_conditioner::{LinkConditionerConfig, LinkConditions},
+        routing::RoutedMessage,
+    },
 };
 
 // https://github.com/rust-lang/rust/issues/46379
@@ -161,3 +181,734 @@ fn multi_instance_channels() {
         }
     }
 }
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn unreliable_fragmented_channel() {
+    let port = 6006; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let client_channel = open_client_channel(ChannelType::UnreliableFragmented, &mut client_app);
+    let server_channel = open_server_channel(ChannelType::UnreliableFragmented, &mut server_app);
+
+    // Small messages still fit in a single datagram
+    let mut msg_counter = 0;
+    send_and_test_client_message(
+        client_id,
+        client_channel,
+        &mut client_app,
+        &mut server_app,
+        &mut msg_counter,
+    );
+    send_and_test_server_message(
+        client_id,
+        server_channel,
+        &mut server_app,
+        &mut client_app,
+        &mut msg_counter,
+    );
+
+    // Large messages are way above the path MTU and must be split
+    let client_message = SharedMessage::TestMessage("c".repeat(20_000));
+    {
+        let client = client_app.world_mut().resource_mut::<QuintetClient>();
+        client
+            .connection()
+            .send_message_on(client_channel, client_message.clone())
+            .unwrap();
+    }
+    let server_received = wait_for_client_message(client_id, &mut server_app);
+    assert_eq!((client_channel, client_message), server_received);
+
+    let server_message = SharedMessage::TestMessage("s".repeat(20_000));
+    {
+        let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+        server
+            .endpoint_mut()
+            .send_message_on(client_id, server_channel, server_message.clone())
+            .unwrap();
+    }
+    let client_received = wait_for_server_message(&mut client_app);
+    assert_eq!((server_channel, server_message), client_received);
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn unreliable_sequenced_channel() {
+    let port = 6007; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let client_channel = open_client_channel(ChannelType::UnreliableSequenced, &mut client_app);
+    let server_channel = open_server_channel(ChannelType::UnreliableSequenced, &mut server_app);
+
+    let mut msg_counter = 0;
+    for _ in 0..5 {
+        send_and_test_client_message(
+            client_id,
+            client_channel,
+            &mut client_app,
+            &mut server_app,
+            &mut msg_counter,
+        );
+        send_and_test_server_message(
+            client_id,
+            server_channel,
+            &mut server_app,
+            &mut client_app,
+            &mut msg_counter,
+        );
+    }
+
+    // Messages were awaited one by one, none of them could be stale
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<QuintetServer>()
+            .endpoint()
+            .dropped_stale_messages_count(client_id),
+        Some(0)
+    );
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .dropped_stale_messages_count(),
+        0
+    );
+}
+
+#[test]
+fn unreliable_sequenced_channel_drops_stale_messages() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6007;
+    // Jitter delivers the datagrams out of order
+    let conditioner = LinkConditionerConfig::new(42).with_channel_type_conditions(
+        ChannelType::UnreliableSequenced,
+        LinkConditions {
+            jitter: Duration::from_millis(100),
+            ..Default::default()
+        },
+    );
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_conditioned_loopback_client_app(port, conditioner);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let client_channel = open_client_channel(ChannelType::UnreliableSequenced, &mut client_app);
+    let message_count = 50;
+    for i in 0..message_count {
+        send_sequenced_index(client_channel, i, &mut client_app);
+    }
+
+    let mut received = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while received.len() as u64 + stale_messages_count(client_id, &server_app) < message_count
+        && Instant::now() < deadline
+    {
+        received.extend(receive_sequenced_indices(client_id, &mut server_app));
+        sleep(Duration::from_millis(10));
+    }
+
+    // A message arriving after a more recent one is dropped and counted, never delivered
+    let stale_count = stale_messages_count(client_id, &server_app);
+    assert!(
+        stale_count > 0,
+        "Jitter should have delivered some messages after more recent ones"
+    );
+    assert_eq!(received.len() as u64 + stale_count, message_count);
+    assert!(
+        received.windows(2).all(|pair| pair[0] < pair[1]),
+        "Delivered messages should be in sending order: {:?}",
+        received
+    );
+}
+
+#[test]
+fn unreliable_sequenced_channel_wraps_around() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6008;
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_loopback_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let client_channel = open_client_channel(ChannelType::UnreliableSequenced, &mut client_app);
+    // More messages than sequence numbers: the u16 sequence wraps around once
+    let message_count = u16::MAX as u64 + 1000;
+    let batch_size = 100;
+    let mut last_received = None;
+    let mut sent = 0;
+    while sent < message_count {
+        let batch_end = (sent + batch_size).min(message_count);
+        for i in sent..batch_end {
+            send_sequenced_index(client_channel, i, &mut client_app);
+        }
+        sent = batch_end;
+
+        // Wait for the batch before sending the next one, to stay within the send queue
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while last_received != Some(batch_end - 1) && Instant::now() < deadline {
+            if let Some(index) = receive_sequenced_indices(client_id, &mut server_app).last() {
+                last_received = Some(*index);
+            }
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    assert_eq!(
+        last_received,
+        Some(message_count - 1),
+        "Messages sent after the sequence wrapped around should be delivered"
+    );
+    assert_eq!(stale_messages_count(client_id, &server_app), 0);
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn link_conditioner() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6001;
+    let latency = Duration::from_millis(200);
+    let conditioner = LinkConditionerConfig::new(42)
+        .with_channel_type_conditions(
+            ChannelType::OrderedReliable,
+            LinkConditions {
+                latency,
+                jitter: Duration::from_millis(50),
+                ..Default::default()
+            },
+        )
+        .with_channel_type_conditions(
+            ChannelType::Unreliable,
+            LinkConditions {
+                drop_rate: 1.0,
+                ..Default::default()
+            },
+        );
+    let mut server_app: App = start_conditioned_loopback_server_app(port, conditioner.clone());
+    let mut client_app: App = start_conditioned_loopback_client_app(port, conditioner);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    // Latency and jitter delay ordered messages, without reordering them
+    let client_channel = get_default_client_channel(&client_app);
+    let sent_messages: Vec<SharedMessage> = (0..10)
+        .map(|i| SharedMessage::TestMessage(format!("Delayed message {}", i)))
+        .collect();
+    let send_time = Instant::now();
+    for message in sent_messages.iter() {
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .send_message_on(client_channel, message.clone())
+            .unwrap();
+    }
+    for message in sent_messages {
+        let server_received = wait_for_client_message(client_id, &mut server_app);
+        assert_eq!((client_channel, message), server_received);
+    }
+    assert!(
+        send_time.elapsed() >= latency,
+        "Messages should not arrive before the configured latency"
+    );
+
+    // Every unreliable message is dropped
+    let unreliable_channel = open_client_channel(ChannelType::Unreliable, &mut client_app);
+    for i in 0..10 {
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .send_message_on(
+                unreliable_channel,
+                SharedMessage::TestMessage(format!("Dropped message {}", i)),
+            )
+            .unwrap();
+    }
+    sleep(Duration::from_millis(300));
+    server_app.update();
+    assert!(
+        server_app
+            .world_mut()
+            .resource_mut::<QuintetServer>()
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+            .is_none(),
+        "All the unreliable messages should have been dropped"
+    );
+}
+
+#[test]
+fn link_conditioner_sequenced_channel() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6006;
+    let conditioner = LinkConditionerConfig::new(42).with_channel_type_conditions(
+        ChannelType::UnreliableSequenced,
+        LinkConditions {
+            duplication_rate: 1.0,
+            ..Default::default()
+        },
+    );
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_conditioned_loopback_client_app(port, conditioner);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    // Every datagram is sent twice with the same sequence number: the first copy is delivered, the second one is stale
+    let client_channel = open_client_channel(ChannelType::UnreliableSequenced, &mut client_app);
+    let message_count = 10;
+    for i in 0..message_count {
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .send_message_on(
+                client_channel,
+                SharedMessage::TestMessage(format!("Duplicated message {}", i)),
+            )
+            .unwrap();
+    }
+    for i in 0..message_count {
+        let server_received = wait_for_client_message(client_id, &mut server_app);
+        assert_eq!(
+            (
+                client_channel,
+                SharedMessage::TestMessage(format!("Duplicated message {}", i))
+            ),
+            server_received
+        );
+    }
+
+    let stale_count = || {
+        server_app
+            .world()
+            .resource::<QuintetServer>()
+            .endpoint()
+            .dropped_stale_messages_count(client_id)
+    };
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while stale_count() != Some(message_count) && Instant::now() < deadline {
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        stale_count(),
+        Some(message_count),
+        "Every duplicated datagram should have been dropped as stale"
+    );
+    assert!(server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .receive_message_from::<SharedMessage>(client_id)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn channel_traffic_stats() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6002;
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_loopback_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    // Sending more messages than the channel queue can hold at once
+    let message = SharedMessage::TestMessage("Traffic".to_string());
+    let message_len = borsh::to_vec(&message).unwrap().len() as u64;
+    let (mut sent, mut refused) = (0, 0);
+    for _ in 0..500 {
+        match client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .send_message_on(channel, message.clone())
+        {
+            Ok(_) => sent += 1,
+            Err(QuintetError::FullQueue) => refused += 1,
+            Err(err) => panic!("Unexpected send error: {}", err),
+        }
+    }
+
+    let mut received = 0;
+    while received < sent {
+        server_app.update();
+        while let Some((_, msg)) = server_app
+            .world_mut()
+            .resource_mut::<QuintetServer>()
+            .endpoint_mut()
+            .try_receive_message_from::<SharedMessage>(client_id)
+        {
+            assert_eq!(msg, message);
+            received += 1;
+        }
+        sleep(Duration::from_millis(10));
+    }
+
+    let client_stats = client_app
+        .world()
+        .resource::<QuintetClient>()
+        .connection()
+        .channel_stats(channel)
+        .expect("Channel should have client stats");
+    assert_eq!(client_stats.sent_messages, sent);
+    assert_eq!(client_stats.sent_bytes, sent * message_len);
+    assert_eq!(client_stats.full_queue_rejections, refused);
+
+    let server_traffic = server_app
+        .world()
+        .resource::<QuintetServer>()
+        .endpoint()
+        .traffic_stats(client_id)
+        .expect("Client should be connected");
+    assert!(server_traffic.rtt.is_some());
+    let server_stats = server_traffic.channels[&channel];
+    assert_eq!(server_stats.received_messages, sent);
+    assert_eq!(server_stats.received_bytes, sent * message_len);
+    assert_eq!(server_traffic.total(), server_stats);
+}
+
+#[test]
+fn blob_transfer() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6003;
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_loopback_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_server_channel(&server_app);
+
+    // Server to client, with progress
+    let blob: Vec<u8> = (0..(16 * DEFAULT_BLOB_CHUNK_SIZE + 123))
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let blob_tag = 7;
+    let blob_id = server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .send_blob(client_id, blob_tag, blob.clone())
+        .unwrap();
+    // A blob does not hold back the channel messages sent after it
+    let message = SharedMessage::TestMessage("After blob".to_string());
+    server_app
+        .world()
+        .resource::<QuintetServer>()
+        .endpoint()
+        .send_message_on(client_id, channel, message.clone())
+        .unwrap();
+    assert_eq!(wait_for_server_message(&mut client_app).1, message);
+
+    let mut progress_reader = ManualEventReader::<client::connection::BlobProgressEvent>::default();
+    let mut received_reader = ManualEventReader::<client::connection::BlobReceivedEvent>::default();
+    let mut last_progress = 0;
+    let received = loop {
+        client_app.update();
+        let progress_events = client_app
+            .world()
+            .resource::<Events<client::connection::BlobProgressEvent>>();
+        for progress in progress_reader.read(progress_events) {
+            assert_eq!(progress.blob_id, blob_id);
+            assert_eq!(progress.tag, blob_tag);
+            assert_eq!(progress.total, blob.len());
+            assert!(progress.received > last_progress && progress.received < blob.len());
+            last_progress = progress.received;
+        }
+        let received_events = client_app
+            .world()
+            .resource::<Events<client::connection::BlobReceivedEvent>>();
+        if let Some(received) = received_reader.read(received_events).next() {
+            break received.clone();
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert!(last_progress > 0, "Progress should be reported for a large blob");
+    assert_eq!(received.blob_id, blob_id);
+    assert_eq!(received.tag, blob_tag);
+    assert_eq!(received.payload, blob);
+
+    // Client to server
+    let blob: Vec<u8> = (0..(2 * DEFAULT_BLOB_CHUNK_SIZE)).map(|i| (i % 13) as u8).collect();
+    let blob_id = client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .send_blob(3, blob.clone())
+        .unwrap();
+
+    let mut received_reader = ManualEventReader::<server::BlobReceivedEvent>::default();
+    let received = loop {
+        server_app.update();
+        let received_events = server_app
+            .world()
+            .resource::<Events<server::BlobReceivedEvent>>();
+        if let Some(received) = received_reader.read(received_events).next() {
+            break received.clone();
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert_eq!(received.id, client_id);
+    assert_eq!(received.blob_id, blob_id);
+    assert_eq!(received.tag, 3);
+    assert_eq!(received.payload, blob);
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn channel_priority() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6004;
+    let bulk_message_count = 100;
+    let bulk_message_len = 256 * 1024;
+    let max_delay = Duration::from_millis(250);
+
+    let mut server_app: App = start_loopback_server_app(port);
+    let mut client_app: App = start_loopback_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let (bulk_channel, urgent_channel) = {
+        let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+        let endpoint = server.endpoint_mut();
+        (
+            endpoint
+                .open_channel(ChannelType::OrderedReliable.with_priority(DEFAULT_CHANNEL_PRIORITY))
+                .unwrap(),
+            endpoint
+                .open_channel(ChannelType::OrderedReliable.with_priority(10))
+                .unwrap(),
+        )
+    };
+    // Let the client handle the new channels' streams
+    sleep(Duration::from_millis(100));
+
+    // Flood the bulk channel with way more data than can be sent at once, then send one urgent message
+    let urgent_message = SharedMessage::TestMessage("Urgent".to_string());
+    let urgent_sent_at = {
+        let server = server_app.world().resource::<QuintetServer>();
+        for _ in 0..bulk_message_count {
+            server
+                .endpoint()
+                .send_message_on(
+                    client_id,
+                    bulk_channel,
+                    SharedMessage::TestMessage("b".repeat(bulk_message_len)),
+                )
+                .unwrap();
+        }
+        server
+            .endpoint()
+            .send_message_on(client_id, urgent_channel, urgent_message.clone())
+            .unwrap();
+        Instant::now()
+    };
+
+    let mut bulk_received_before_urgent = 0;
+    let urgent_delay = loop {
+        assert!(
+            urgent_sent_at.elapsed() < Duration::from_secs(10),
+            "The urgent message was not received"
+        );
+        let mut client = client_app.world_mut().resource_mut::<QuintetClient>();
+        match client.connection_mut().receive_message::<SharedMessage>() {
+            Ok(Some((channel, message))) if channel == urgent_channel => {
+                assert_eq!(message, urgent_message);
+                break urgent_sent_at.elapsed();
+            }
+            Ok(Some((channel, _))) => {
+                assert_eq!(channel, bulk_channel);
+                bulk_received_before_urgent += 1;
+            }
+            Ok(None) => sleep(Duration::from_millis(1)),
+            Err(_) => panic!("Deserialization should be correct"),
+        }
+    };
+
+    assert!(
+        bulk_received_before_urgent < bulk_message_count / 2,
+        "The urgent message should not wait for the bulk transfer, {} bulk messages were received before it",
+        bulk_received_before_urgent
+    );
+    assert!(
+        urgent_delay < max_delay,
+        "The urgent message was delayed by {:?}",
+        urgent_delay
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+routed_message! {
+    #[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+    enum RoutedTestMessage {
+        Move(u32) => (Unreliable, ClientToServer, 16),
+        Chat(String) => (OrderedReliable, Both, 64),
+        Welcome(u64) => (OrderedReliable, ServerToClient, 16),
+    }
+}
+
+#[test]
+fn typed_messages() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6005;
+    let mut server_app = build_server_app_without_endpoint();
+    let mut client_app = build_client_app_without_connection();
+    server_app.update();
+    client_app.update();
+
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::loopback(port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            RoutedTestMessage::channels_configuration(),
+        )
+        .unwrap();
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .open_connection(
+            ClientEndpointConfiguration::loopback(port),
+            CertificateVerificationMode::SkipVerification,
+            RoutedTestMessage::channels_configuration(),
+        )
+        .unwrap();
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    // Messages sharing a channel type and priority share a channel
+    let client = client_app.world().resource::<QuintetClient>();
+    client
+        .connection()
+        .send_typed(RoutedTestMessage::Chat("Hello".to_string()))
+        .unwrap();
+    let (channel, message) = wait_for_typed_client_message(client_id, &mut server_app);
+    assert_eq!(channel, 1);
+    assert_eq!(message, RoutedTestMessage::Chat("Hello".to_string()));
+
+    let server = server_app.world().resource::<QuintetServer>();
+    server
+        .endpoint()
+        .send_typed(client_id, RoutedTestMessage::Welcome(42))
+        .unwrap();
+    let (channel, message) = wait_for_typed_server_message(&mut client_app);
+    assert_eq!(channel, 1);
+    assert_eq!(message, RoutedTestMessage::Welcome(42));
+
+    // Wrong direction
+    let server = server_app.world().resource::<QuintetServer>();
+    assert!(matches!(
+        server
+            .endpoint()
+            .send_typed(client_id, RoutedTestMessage::Move(1)),
+        Err(QuintetError::WrongMessageDirection("Move"))
+    ));
+    let client = client_app.world().resource::<QuintetClient>();
+    assert!(matches!(
+        client
+            .connection()
+            .send_typed(RoutedTestMessage::Welcome(1)),
+        Err(QuintetError::WrongMessageDirection("Welcome"))
+    ));
+
+    // Larger than the route allows
+    assert!(matches!(
+        client
+            .connection()
+            .send_typed(RoutedTestMessage::Chat("c".repeat(100))),
+        Err(QuintetError::MessageTooLarge("Chat", _))
+    ));
+}
+
+fn wait_for_typed_client_message(client_id: u64, server_app: &mut App) -> (u8, RoutedTestMessage) {
+    let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+    loop {
+        sleep(Duration::from_millis(10));
+        if let Some(message) = server
+            .endpoint_mut()
+            .try_receive_message_from::<RoutedTestMessage>(client_id)
+        {
+            return message;
+        }
+    }
+}
+
+fn wait_for_typed_server_message(client_app: &mut App) -> (u8, RoutedTestMessage) {
+    let mut client = client_app.world_mut().resource_mut::<QuintetClient>();
+    loop {
+        sleep(Duration::from_millis(10));
+        if let Some(message) = client
+            .connection_mut()
+            .try_receive_message::<RoutedTestMessage>()
+        {
+            return message;
+        }
+    }
+}
+
+fn send_sequenced_index(channel_id: u8, index: u64, client_app: &mut App) {
+    client_app
+        .world()
+        .resource::<QuintetClient>()
+        .connection()
+        .send_message_on(channel_id, SharedMessage::TestMessage(index.to_string()))
+        .unwrap();
+}
+
+fn receive_sequenced_indices(client_id: u64, server_app: &mut App) -> Vec<u64> {
+    let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+    let mut indices = Vec::new();
+    while let Some((_, SharedMessage::TestMessage(index))) = server
+        .endpoint_mut()
+        .receive_message_from::<SharedMessage>(client_id)
+        .unwrap()
+    {
+        indices.push(index.parse().unwrap());
+    }
+    indices
+}
+
+fn stale_messages_count(client_id: u64, server_app: &App) -> u64 {
+    server_app
+        .world()
+        .resource::<QuintetServer>()
+        .endpoint()
+        .dropped_stale_messages_count(client_id)
+        .unwrap()
+}
@@ -1,6 +1,23 @@
 use std::{thread::sleep, time::Duration};
 
-use jeffy_quintet::{client::QuintetClient, server::QuintetServer};
+use jeffy_quintet::{
+    client::{
+        certificate::CertificateVerificationMode,
+        connection::{ClientEndpointConfiguration, ConnectionState, ReconnectPolicy},
+        handle::ClientHandle,
+        ClientEvent, QuintetClient, QuintetConnectionError,
+    },
+    server::{
+        certificate::CertificateRetrievalMode, handle::ServerHandle, QuintetServer,
+        ServerEndpointConfiguration, ServerEvent,
+    },
+    shared::{
+        admission::{Admission, JoinRequest},
+        channels::{ChannelType, ChannelsConfiguration},
+        transport::{CongestionController, TransportConfig},
+        DisconnectReason,
+    },
+};
 
 // https://github.com/rust-lang/rust/issues/46379
 pub use utils::*;
@@ -135,7 +152,7 @@ fn reconnection() {
         .world_mut()
         .resource_mut::<QuintetClient>()
         .connection_mut()
-        .disconnect()
+        .disconnect(DisconnectReason::default())
         .unwrap();
 
     let last_disconnected_client_id = wait_for_all_clients_disconnected(&mut server_app);
@@ -171,3 +188,604 @@ fn reconnection() {
         2
     );
 }
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn loopback_connection() {
+    // Loopback ports live in their own namespace and do not bind any UDP socket
+    let port = 6000;
+
+    let mut server_app = start_loopback_server_app(port);
+    let mut client_app = start_loopback_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_events_received,
+        1
+    );
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<ClientTestData>()
+            .connection_events_received,
+        1
+    );
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .client_id(),
+        Some(client_id),
+        "The client should receive the id assigned by the server"
+    );
+
+    let mut msg_counter = 0;
+    for channel_type in [
+        ChannelType::OrderedReliable,
+        ChannelType::UnorderedReliable,
+        ChannelType::Unreliable,
+        ChannelType::UnreliableFragmented,
+        ChannelType::UnreliableSequenced,
+    ] {
+        let client_channel = open_client_channel(channel_type, &mut client_app);
+        let server_channel = open_server_channel(channel_type, &mut server_app);
+        send_and_test_client_message(
+            client_id,
+            client_channel,
+            &mut client_app,
+            &mut server_app,
+            &mut msg_counter,
+        );
+        send_and_test_server_message(
+            client_id,
+            server_channel,
+            &mut server_app,
+            &mut client_app,
+            &mut msg_counter,
+        );
+    }
+
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .disconnect(DisconnectReason::default())
+        .unwrap();
+
+    let last_disconnected_client_id = wait_for_all_clients_disconnected(&mut server_app);
+    assert_eq!(last_disconnected_client_id, client_id);
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_lost_events_received,
+        1
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn admission_validator() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6001;
+    let whitelisted_player = 7;
+
+    let mut server_app = start_loopback_server_app(port);
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .set_admission_validator(move |join_request, _context| {
+            if join_request.player_id == whitelisted_player {
+                Admission::Accept
+            } else {
+                Admission::Reject("Not whitelisted".to_string())
+            }
+        });
+
+    // Refused client
+    let mut refused_client_app = start_client_app_with_configuration(
+        ClientEndpointConfiguration::loopback(port).with_join_request(JoinRequest {
+            player_id: 3,
+            ..Default::default()
+        }),
+    );
+    loop {
+        refused_client_app.update();
+        server_app.update();
+        if refused_client_app
+            .world()
+            .resource::<ClientTestData>()
+            .connection_failed_events_received
+            > 0
+        {
+            break;
+        }
+    }
+    assert_eq!(
+        refused_client_app
+            .world()
+            .resource::<ClientTestData>()
+            .last_connection_failed_err,
+        Some(QuintetConnectionError::Rejected("Not whitelisted".to_string()).to_string())
+    );
+    assert_eq!(
+        refused_client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .state(),
+        ConnectionState::Disconnected
+    );
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_events_received,
+        0,
+        "A refused client should not raise a connection event"
+    );
+
+    // Admitted client
+    let mut client_app = start_client_app_with_configuration(
+        ClientEndpointConfiguration::loopback(port).with_join_request(JoinRequest {
+            player_id: whitelisted_player,
+            ..Default::default()
+        }),
+    );
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<QuintetServer>()
+            .endpoint()
+            .join_request(client_id)
+            .map(|join_request| join_request.player_id),
+        Some(whitelisted_player)
+    );
+}
+
+#[test]
+fn disconnect_reason() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6002;
+
+    let mut server_app = start_loopback_server_app(port);
+
+    // Client disconnecting with a reason
+    let mut client_app = start_loopback_client_app(port);
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .disconnect(DisconnectReason::new(3, "quit"))
+        .unwrap();
+    wait_for_all_clients_disconnected(&mut server_app);
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .last_disconnect_reason,
+        Some(DisconnectReason::new(3, "quit"))
+    );
+
+    // Server kicking a client with a reason
+    let mut client_app = start_loopback_client_app(port);
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .disconnect_client(client_id, DisconnectReason::new(7, "kicked: server full"))
+        .unwrap();
+    loop {
+        client_app.update();
+        server_app.update();
+        if client_app
+            .world()
+            .resource::<ClientTestData>()
+            .connection_lost_events_received
+            > 0
+        {
+            break;
+        }
+    }
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<ClientTestData>()
+            .last_disconnect_reason,
+        Some(DisconnectReason::new(7, "kicked: server full"))
+    );
+}
+
+#[test]
+fn automatic_reconnection() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6003;
+
+    let mut server_app = start_loopback_server_app(port);
+    let mut client_app = start_client_app_with_configuration(
+        ClientEndpointConfiguration::loopback(port).with_reconnect_policy(ReconnectPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(500),
+            jitter: 0.,
+            // Stopping the endpoint closes the connection with a reason
+            reconnect_on_server_close: true,
+            ..Default::default()
+        }),
+    );
+    wait_for_client_connected(&mut client_app, &mut server_app);
+
+    // Server restart
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .stop_endpoint()
+        .unwrap();
+    while !client_app.world().resource::<QuintetClient>().is_reconnecting() {
+        client_app.update();
+        server_app.update();
+    }
+    drop(server_app);
+    let mut server_app = start_loopback_server_app(port);
+
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    let client_test_data = client_app.world().resource::<ClientTestData>();
+    assert_eq!(client_test_data.connection_events_received, 2);
+    assert_eq!(client_test_data.reconnect_attempt_events_received, 1);
+    assert_eq!(client_test_data.last_reconnect_attempt, Some(1));
+    assert_eq!(
+        client_test_data.connection_lost_events_received, 0,
+        "A connection which reconnected should not raise a connection lost event"
+    );
+
+    // Server restart, refusing the client this time
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .stop_endpoint()
+        .unwrap();
+    while !client_app.world().resource::<QuintetClient>().is_reconnecting() {
+        client_app.update();
+        server_app.update();
+    }
+    drop(server_app);
+    let mut server_app = start_loopback_server_app(port);
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .set_admission_validator(|_, _| Admission::Reject("Maintenance".to_string()));
+
+    while client_app
+        .world()
+        .resource::<ClientTestData>()
+        .connection_lost_events_received
+        == 0
+    {
+        client_app.update();
+        server_app.update();
+    }
+    let client_test_data = client_app.world().resource::<ClientTestData>();
+    assert_eq!(client_test_data.reconnect_attempt_events_received, 2);
+    assert_eq!(
+        client_test_data.last_disconnect_reason.as_ref().map(|reason| reason.message.as_str()),
+        Some("server stopped")
+    );
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .state(),
+        ConnectionState::Disconnected
+    );
+}
+
+#[test]
+fn no_reconnection_after_kick() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6007;
+
+    let mut server_app = start_loopback_server_app(port);
+    let mut client_app = start_client_app_with_configuration(
+        ClientEndpointConfiguration::loopback(port)
+            .with_reconnect_policy(ReconnectPolicy::default()),
+    );
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    // A client kicked with a reason does not come back
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .disconnect_client(client_id, DisconnectReason::new(7, "kicked"))
+        .unwrap();
+    while client_app
+        .world()
+        .resource::<ClientTestData>()
+        .connection_lost_events_received
+        == 0
+    {
+        client_app.update();
+        server_app.update();
+    }
+    let client_test_data = client_app.world().resource::<ClientTestData>();
+    assert_eq!(client_test_data.reconnect_attempt_events_received, 0);
+    assert_eq!(
+        client_test_data.last_disconnect_reason,
+        Some(DisconnectReason::new(7, "kicked"))
+    );
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<QuintetClient>()
+            .connection()
+            .state(),
+        ConnectionState::Disconnected
+    );
+}
+
+#[test]
+fn client_groups() {
+    // Loopback port, does not conflict with the UDP ports of the other tests
+    let port = 6004;
+
+    let mut server_app = start_loopback_server_app(port);
+    let mut client_app_1 = start_loopback_client_app(port);
+    let client_id_1 = wait_for_client_connected(&mut client_app_1, &mut server_app);
+    let mut client_app_2 = start_loopback_client_app(port);
+    let client_id_2 = wait_for_client_connected(&mut client_app_2, &mut server_app);
+
+    {
+        let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+        let endpoint = server.endpoint_mut();
+        endpoint.create_group("room").unwrap();
+        assert!(endpoint.create_group("room").is_err());
+        assert!(endpoint.add_client_to_group("room", client_id_1).unwrap());
+        assert!(!endpoint.add_client_to_group("room", client_id_1).unwrap());
+        assert!(endpoint.add_client_to_group("lobby", client_id_2).is_err());
+        assert_eq!(
+            endpoint.client_groups(client_id_1).collect::<Vec<&str>>(),
+            vec!["room"]
+        );
+
+        endpoint
+            .broadcast_group_message("room", SharedMessage::TestMessage("Room".to_string()))
+            .unwrap();
+    }
+    assert_eq!(
+        wait_for_server_message(&mut client_app_1).1,
+        SharedMessage::TestMessage("Room".to_string())
+    );
+    sleep(Duration::from_millis(100));
+    assert!(client_app_2
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .receive_message::<SharedMessage>()
+        .unwrap()
+        .is_none());
+
+    // Membership is cleaned up on disconnection
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .endpoint_mut()
+        .add_client_to_group("room", client_id_2)
+        .unwrap();
+    client_app_1
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .connection_mut()
+        .disconnect(DisconnectReason::default())
+        .unwrap();
+    loop {
+        server_app.update();
+        let server = server_app.world().resource::<QuintetServer>();
+        if !server.endpoint().clients().contains(&client_id_1) {
+            let members = server.endpoint().group_members("room").unwrap();
+            assert!(!members.contains(&client_id_1));
+            assert!(members.contains(&client_id_2));
+            break;
+        }
+        sleep(Duration::from_millis(10));
+    }
+
+    let mut server = server_app.world_mut().resource_mut::<QuintetServer>();
+    let endpoint = server.endpoint_mut();
+    assert_eq!(
+        endpoint.remove_group("room").unwrap().into_iter().collect::<Vec<_>>(),
+        vec![client_id_2]
+    );
+    assert!(endpoint
+        .broadcast_group_message("room", SharedMessage::TestMessage("Gone".to_string()))
+        .is_err());
+}
+
+#[test]
+fn connection_without_bevy() {
+    let port = 6005;
+
+    let mut server = ServerHandle::new().unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::loopback(port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client = ClientHandle::new().unwrap();
+    client
+        .open_connection(
+            ClientEndpointConfiguration::loopback(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let mut client_connected = false;
+    let mut server_client_id = None;
+    while !client_connected || server_client_id.is_none() {
+        for event in client.poll() {
+            if let ClientEvent::Connection(_) = event {
+                client_connected = true;
+            }
+        }
+        for event in server.poll() {
+            if let ServerEvent::Connection(connection) = event {
+                server_client_id = Some(connection.id);
+            }
+        }
+        sleep(Duration::from_millis(10));
+    }
+    let client_id = server_client_id.unwrap();
+    assert!(client.is_connected());
+    assert_eq!(server.endpoint().clients(), vec![client_id]);
+
+    let client_message = SharedMessage::TestMessage("Hello from a bot".to_string());
+    client
+        .connection()
+        .send_message(client_message.clone())
+        .unwrap();
+    let received = loop {
+        server.poll();
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .try_receive_message_from::<SharedMessage>(client_id)
+        {
+            break message;
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert_eq!(received, client_message);
+
+    let server_message = SharedMessage::TestMessage("Hello from a headless server".to_string());
+    server
+        .endpoint()
+        .send_message(client_id, server_message.clone())
+        .unwrap();
+    let received = loop {
+        client.poll();
+        if let Some((_, message)) = client
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap()
+        {
+            break message;
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert_eq!(received, server_message);
+
+    client
+        .connection_mut()
+        .disconnect(DisconnectReason::default())
+        .unwrap();
+    'lost: loop {
+        for event in server.poll() {
+            if let ServerEvent::ConnectionLost(lost) = event {
+                assert_eq!(lost.id, client_id);
+                break 'lost;
+            }
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert!(server.endpoint().clients().is_empty());
+}
+
+#[test]
+fn transport_config() {
+    let port = 6006;
+
+    // Without keep-alive packets, an idle connection times out
+    let transport_config = TransportConfig {
+        keep_alive_interval: None,
+        max_idle_timeout: Some(Duration::from_millis(300)),
+        max_reliable_frame_len: 1024,
+        congestion_controller: CongestionController::Bbr,
+        message_queue_size: 10,
+        ..Default::default()
+    };
+
+    let mut server = ServerHandle::new().unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::loopback(port)
+                .with_transport_config(transport_config.clone()),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client = ClientHandle::new().unwrap();
+    client
+        .open_connection(
+            ClientEndpointConfiguration::loopback(port).with_transport_config(transport_config),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let client_id = 'connected: loop {
+        client.poll();
+        for event in server.poll() {
+            if let ServerEvent::Connection(connection) = event {
+                break 'connected connection.id;
+            }
+        }
+        sleep(Duration::from_millis(10));
+    };
+
+    let message = SharedMessage::TestMessage("Within the frame length".to_string());
+    client.connection().send_message(message.clone()).unwrap();
+    let received = loop {
+        server.poll();
+        if let Some((_, received)) = server
+            .endpoint_mut()
+            .try_receive_message_from::<SharedMessage>(client_id)
+        {
+            break received;
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert_eq!(received, message);
+
+    let idle_start = std::time::Instant::now();
+    'lost: loop {
+        client.poll();
+        for event in server.poll() {
+            if let ServerEvent::ConnectionLost(lost) = event {
+                assert_eq!(lost.id, client_id);
+                assert!(lost.reason.is_none(), "A timeout has no disconnect reason");
+                break 'lost;
+            }
+        }
+        assert!(
+            idle_start.elapsed() < Duration::from_secs(5),
+            "The idle connection should have timed out"
+        );
+        sleep(Duration::from_millis(10));
+    }
+    assert!(!client.is_connected());
+}
@@ -8,6 +8,7 @@ use bevy::{
     app::ScheduleRunnerPlugin,
     prelude::{App, EventReader, Res, ResMut, Resource, Startup, Update},
 };
+use borsh::{BorshDeserialize, BorshSerialize};
 use jeffy_quintet::{
     client::{
         self,
@@ -25,15 +26,24 @@ use jeffy_quintet::{
     },
     shared::{
         channels::{ChannelId, ChannelType, ChannelsConfiguration},
-        ClientId,
+        link_conditioner::LinkConditionerConfig,
+        ClientId, DisconnectReason,
     },
 };
-use borsh::{BorshDeserialize, BorshSerialize};
 
 #[derive(Resource, Debug, Clone, Default)]
 pub struct ClientTestData {
     pub connection_events_received: u64,
 
+    pub connection_failed_events_received: u64,
+    pub last_connection_failed_err: Option<String>,
+
+    pub connection_lost_events_received: u64,
+    pub last_disconnect_reason: Option<DisconnectReason>,
+
+    pub reconnect_attempt_events_received: u64,
+    pub last_reconnect_attempt: Option<u32>,
+
     pub cert_trust_update_events_received: u64,
     pub last_trusted_cert_info: Option<CertVerificationInfo>,
 
@@ -52,6 +62,7 @@ pub struct ServerTestData {
     pub last_connected_client_id: Option<ClientId>,
     pub connection_lost_events_received: u64,
     pub last_disconnected_client_id: Option<ClientId>,
+    pub last_disconnect_reason: Option<DisconnectReason>,
 }
 
 #[derive(Resource, Debug, Clone, Default)]
@@ -66,6 +77,18 @@ pub const SERVER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 pub const LOCAL_BIND_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
 
 pub fn build_client_app() -> App {
+    let mut client_app = build_client_app_without_connection();
+    client_app.add_systems(Startup, start_simple_connection);
+    client_app
+}
+
+pub fn build_server_app() -> App {
+    let mut server_app = build_server_app_without_endpoint();
+    server_app.add_systems(Startup, start_listening);
+    server_app
+}
+
+pub fn build_client_app_without_connection() -> App {
     let mut client_app = App::new();
     client_app
         .add_plugins((
@@ -73,12 +96,11 @@ pub fn build_client_app() -> App {
             QuintetClientPlugin::default(),
         ))
         .insert_resource(ClientTestData::default())
-        .add_systems(Startup, start_simple_connection)
         .add_systems(Update, handle_client_events);
     client_app
 }
 
-pub fn build_server_app() -> App {
+pub fn build_server_app_without_endpoint() -> App {
     let mut server_app = App::new();
     server_app
         .add_plugins((
@@ -86,7 +108,6 @@ pub fn build_server_app() -> App {
             QuintetServerPlugin::default(),
         ))
         .insert_resource(ServerTestData::default())
-        .add_systems(Startup, start_listening)
         .add_systems(Update, handle_server_events);
     server_app
 }
@@ -117,8 +138,34 @@ pub fn start_listening(mut server: ResMut<QuintetServer>, port: Res<Port>) {
         .unwrap();
 }
 
+pub fn start_loopback_connection(mut client: ResMut<QuintetClient>, port: Res<Port>) {
+    client
+        .open_connection(
+            ClientEndpointConfiguration::loopback(port.0),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+}
+
+pub fn start_loopback_listening(mut server: ResMut<QuintetServer>, port: Res<Port>) {
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::loopback(port.0),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn handle_client_events(
     mut connection_events: EventReader<client::connection::ConnectionEvent>,
+    mut connection_failed_events: EventReader<client::connection::ConnectionFailedEvent>,
+    mut connection_lost_events: EventReader<client::connection::ConnectionLostEvent>,
+    mut reconnect_attempt_events: EventReader<client::connection::ReconnectAttemptEvent>,
     mut cert_trust_update_events: EventReader<CertTrustUpdateEvent>,
     mut cert_interaction_events: EventReader<CertInteractionEvent>,
     mut cert_connection_abort_events: EventReader<CertConnectionAbortEvent>,
@@ -127,6 +174,18 @@ pub fn handle_client_events(
     for _connected_event in connection_events.read() {
         test_data.connection_events_received += 1;
     }
+    for failed_event in connection_failed_events.read() {
+        test_data.connection_failed_events_received += 1;
+        test_data.last_connection_failed_err = Some(failed_event.err.to_string());
+    }
+    for lost_event in connection_lost_events.read() {
+        test_data.connection_lost_events_received += 1;
+        test_data.last_disconnect_reason = lost_event.reason.clone();
+    }
+    for attempt_event in reconnect_attempt_events.read() {
+        test_data.reconnect_attempt_events_received += 1;
+        test_data.last_reconnect_attempt = Some(attempt_event.attempt);
+    }
     for trust_update in cert_trust_update_events.read() {
         test_data.cert_trust_update_events_received += 1;
         test_data.last_trusted_cert_info = Some(trust_update.cert_info.clone());
@@ -165,6 +224,7 @@ pub fn handle_server_events(
     for event in connection_lost_events.read() {
         test_data.connection_lost_events_received += 1;
         test_data.last_disconnected_client_id = Some(event.id);
+        test_data.last_disconnect_reason = event.reason.clone();
     }
 }
 
@@ -186,6 +246,74 @@ pub fn start_simple_client_app(port: u16) -> App {
     client_app
 }
 
+pub fn start_loopback_server_app(port: u16) -> App {
+    let mut server_app = build_server_app_without_endpoint();
+    server_app
+        .insert_resource(Port(port))
+        .add_systems(Startup, start_loopback_listening);
+
+    // Startup
+    server_app.update();
+    server_app
+}
+
+pub fn start_loopback_client_app(port: u16) -> App {
+    let mut client_app = build_client_app_without_connection();
+    client_app
+        .insert_resource(Port(port))
+        .add_systems(Startup, start_loopback_connection);
+
+    // Startup
+    client_app.update();
+    client_app
+}
+
+pub fn start_conditioned_loopback_server_app(
+    port: u16,
+    link_conditioner: LinkConditionerConfig,
+) -> App {
+    let mut server_app = build_server_app_without_endpoint();
+    server_app
+        .world_mut()
+        .resource_mut::<QuintetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::loopback(port).with_link_conditioner(link_conditioner),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    server_app.update();
+    server_app
+}
+
+pub fn start_conditioned_loopback_client_app(
+    port: u16,
+    link_conditioner: LinkConditionerConfig,
+) -> App {
+    start_client_app_with_configuration(
+        ClientEndpointConfiguration::loopback(port).with_link_conditioner(link_conditioner),
+    )
+}
+
+pub fn start_client_app_with_configuration(config: ClientEndpointConfiguration) -> App {
+    let mut client_app = build_client_app_without_connection();
+    client_app
+        .world_mut()
+        .resource_mut::<QuintetClient>()
+        .open_connection(
+            config,
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    client_app.update();
+    client_app
+}
+
 pub fn wait_for_client_connected(client_app: &mut App, server_app: &mut App) -> ClientId {
     loop {
         client_app.update();
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::blockinfo::Blocks;
+use crate::cube::Cube;
+use crate::inventory::Inventory;
+use crate::specialblocks::door::DoorInfo;
+use crate::vec;
+
+//How far from a player's last known position it may edit: the select distance, plus head height and movement between updates
+pub const MAX_EDIT_REACH: f32 = 14.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditRejection {
+    //Further than MAX_EDIT_REACH, or the player has not told us where it is yet
+    OutOfReach,
+    Unbreakable,
+    NotPlaceable,
+    NotOwned,
+}
+
+//What an accepted edit does to the editor's inventory: the block it gets for breaking, the item placing costs
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct EditOutcome {
+    pub broke: Option<u32>,
+    pub placed: Option<u32>,
+}
+
+//Crystal broken in whole clusters, from the one clicked to all the same crystals touching it
+pub const CLUSTER_CRYSTAL: u32 = 16;
+
+//The item a broken block gives, the same for the client's drop and the server's count
+pub fn broken_item(id: u32) -> u32 {
+    match id {
+        //Red crystal that can't be placed breaks into the kind that can
+        16 => 17,
+        id => id,
+    }
+}
+
+//Server side check of one block a client set, old being what the server has there
+pub fn check_block_edit(
+    player: Option<Vec3>,
+    spot: &vec::IVec3,
+    old: u32,
+    new: u32,
+    inv: &Inventory,
+) -> Result<EditOutcome, EditRejection> {
+    let center = Vec3::new(spot.x as f32 + 0.5, spot.y as f32 + 0.5, spot.z as f32 + 0.5);
+    match player {
+        Some(position) if position.distance(center) <= MAX_EDIT_REACH => {}
+        _ => return Err(EditRejection::OutOfReach),
+    }
+
+    check_block_change(old, new, inv)
+}
+
+//What setting a block does to the inventory, reach aside
+pub fn check_block_change(old: u32, new: u32, inv: &Inventory) -> Result<EditOutcome, EditRejection> {
+    let old_id = old & Blocks::block_id_bits();
+    let new_id = new & Blocks::block_id_bits();
+    let mut outcome = EditOutcome::default();
+
+    //Same block with other flags: a door opening, a neighbor door joining it...
+    if old_id == new_id {
+        return Ok(outcome);
+    }
+
+    if !Blocks::is_overwritable(old_id) {
+        if Blocks::is_unbreakable(old_id) {
+            return Err(EditRejection::Unbreakable);
+        }
+        //A door drops once, for its bottom half
+        if !(old_id == 19 && DoorInfo::get_door_top_bit(old) == 1) {
+            outcome.broke = Some(broken_item(old_id));
+        }
+    }
+
+    if new_id != 0 {
+        if !Blocks::is_block(new_id) || Blocks::is_non_placeable(new_id) {
+            return Err(EditRejection::NotPlaceable);
+        }
+        //The top half of a door comes with its bottom half
+        if !(new_id == 19 && DoorInfo::get_door_top_bit(new) == 1) {
+            if !inv.has(new_id) {
+                return Err(EditRejection::NotOwned);
+            }
+            outcome.placed = Some(new_id);
+        }
+    }
+
+    Ok(outcome)
+}
+
+//Whether a batch of edits, with what the server has at each spot, breaks one crystal cluster. Only its first spot, the
+//crystal clicked, has to be in reach: every other one is a crystal touching one broken before it
+pub fn is_crystal_cluster_break(edits: &[(vec::IVec3, u32, u32)]) -> bool {
+    let mut broken = HashSet::new();
+    for (index, (spot, old, new)) in edits.iter().enumerate() {
+        if *old & Blocks::block_id_bits() != CLUSTER_CRYSTAL || *new != 0 {
+            return false;
+        }
+        if index > 0 && !Cube::get_neighbors().iter().any(|neighbor| broken.contains(&(*neighbor + *spot))) {
+            return false;
+        }
+        broken.insert(*spot);
+    }
+    edits.len() > 1
+}
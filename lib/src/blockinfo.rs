@@ -1,6 +1,8 @@
 use crate::{chunk::LightColor, cube::CubeSide};
 
 pub const BLOCK_DIRECTION_BITS: u32 = 0b0000_0000_0000_0011_0000_0000_0000_0000;
+//Break time of blocks players can not break (bedrock)
+pub const UNBREAKABLE_BREAK_TIME: f32 = 9999999.0;
 pub struct Blocks {}
 
 
@@ -20,7 +22,7 @@ static BREAKTIMES: [f32; 51] = [
     1.1,
     1.5,
     0.7,
-    9999999.0,
+    UNBREAKABLE_BREAK_TIME,
     1.2,
     0.5,
     1.0,
@@ -242,6 +244,14 @@ impl Blocks {
     pub fn get_break_time(id: u32) -> f32 {
         return BREAKTIMES[id as usize];
     }
+    //Ids with an entry in the block tables, nothing else belongs in the world
+    pub fn is_block(id: u32) -> bool {
+        return (id as usize) < BREAKTIMES.len();
+    }
+    //Blocks like bedrock, whose break time no player sits through
+    pub fn is_unbreakable(id: u32) -> bool {
+        return Blocks::is_block(id) && Blocks::get_break_time(id) >= UNBREAKABLE_BREAK_TIME;
+    }
     pub fn get_texs_length() -> usize {
         return TEXS.len();
     }
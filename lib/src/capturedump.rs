@@ -0,0 +1,62 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use jeffy_quintet::shared::capture::{CaptureDirection, CaptureReader, CaptureRecord};
+use jeffy_quintet::shared::error::QuintetError;
+
+use crate::server_types::Message;
+
+//Readable form of a message, without dumping every edit of a chunk
+fn describe_message(message: &Message) -> String {
+    match message {
+        Message::ChunkEdits(chunk, blocks, chests) => format!(
+            "ChunkEdits({} {}, <{} blocks>, <{} chests>)",
+            chunk.0.x,
+            chunk.0.y,
+            blocks.len(),
+            chests.len()
+        ),
+        message => format!("{:?}", message),
+    }
+}
+
+//One line per record: time, direction, client, channel, then the decoded message
+pub fn format_capture_record(record: &CaptureRecord) -> String {
+    let direction = match record.direction {
+        CaptureDirection::Sent => "SENT",
+        CaptureDirection::Received => "RECV",
+    };
+    let client = match record.client_id {
+        Some(client_id) => client_id.to_string(),
+        None => "-".to_string(),
+    };
+    let message = match borsh::from_slice::<Message>(&record.payload) {
+        Ok(message) => describe_message(&message),
+        Err(_) => format!("<{} bytes, not a Message>", record.payload.len()),
+    };
+
+    format!(
+        "{:>10.3}ms {} client {} channel {} {}",
+        record.timestamp_us as f64 / 1000.0,
+        direction,
+        client,
+        record.channel_id,
+        message
+    )
+}
+
+//Decodes a capture file written by a QuintetServer endpoint or a client connection into readable message lines
+pub fn dump_capture<P: AsRef<Path>, W: Write>(path: P, out: &mut W) -> Result<usize, QuintetError> {
+    let mut count = 0;
+    for record in CaptureReader::open(path)? {
+        writeln!(out, "{}", format_capture_record(&record?))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+pub fn dump_capture_to_stdout<P: AsRef<Path>>(path: P) -> Result<usize, QuintetError> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    dump_capture(path, &mut out)
+}
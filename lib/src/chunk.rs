@@ -26,6 +26,7 @@ use rand::SeedableRng;
 use rusqlite::params;
 use rusqlite::Connection;
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use parking_lot::{Mutex, RwLock};
@@ -308,6 +309,11 @@ pub static mut AUTOMATA_QUEUED_CHANGES: Lazy<VecDeque<ACSet>> = Lazy::new(|| Vec
 pub static mut USERDATAMAP: Option<Arc<DashMap<vec::IVec3, u32>>> = None;
 pub static mut NONUSERDATAMAP: Option<Arc<DashMap<vec::IVec3, u32>>> = None;
 
+//Set for multiplayer clients, whose user edits come from the server one chunk at a time instead of from a database
+pub static STREAM_USER_EDITS: AtomicBool = AtomicBool::new(false);
+//Chunks that came into takencare, their edits are asked for by newclient::request_chunk_edits
+pub static CHUNK_EDIT_REQUESTS: Lazy<lockfree::queue::Queue<vec::IVec2>> = Lazy::new(|| lockfree::queue::Queue::new());
+
 
 pub struct ChunkSystem {
     pub chunks: Vec<Arc<Mutex<ChunkFacade>>>,
@@ -485,7 +491,23 @@ impl ChunkSystem {
         writeln!(file, "{}", self.planet_type).unwrap();
     }
 
+    //Multiplayer client world: generated from the server's seed, with user edits filled in as chunks are requested
+    pub fn start_streamed_world(&mut self, seed: u32, planet_type: u8) {
+        let udm = unsafe {USERDATAMAP.as_ref().unwrap()};
+        let nudm = unsafe {NONUSERDATAMAP.as_ref().unwrap()};
+        udm.clear();
+        nudm.clear();
+
+        *(self.perlin.write()) = Perlin::new(seed);
+        unsafe { CURRSEED.store(seed, std::sync::atomic::Ordering::Relaxed) };
+        self.planet_type = planet_type;
+
+        STREAM_USER_EDITS.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
     pub fn load_world_from_file(&mut self, path: String) {
+        STREAM_USER_EDITS.store(false, std::sync::atomic::Ordering::Relaxed);
+
         let udm = unsafe {USERDATAMAP.as_ref().unwrap()};
         let nudm = unsafe {NONUSERDATAMAP.as_ref().unwrap()};
         udm.clear();
@@ -2502,6 +2524,9 @@ impl ChunkSystem {
 
         if !tc.contains_key(&chunklock.pos) {
             tc.insert(chunklock.pos, chunklock);
+            if STREAM_USER_EDITS.load(std::sync::atomic::Ordering::Relaxed) {
+                CHUNK_EDIT_REQUESTS.push(chunklock.pos);
+            }
         }
     }
 
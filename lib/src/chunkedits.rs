@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use dashmap::DashMap;
+
+use crate::chunk::ChunkSystem;
+use crate::game::ROWLENGTH;
+use crate::inventory::ChestInventory;
+use crate::server_types::SerIVec3;
+use crate::vec;
+
+//Server side: user edits and chests grouped by chunk, so answering a RequestChunkEdits does not walk the whole world
+#[derive(Resource, Default)]
+pub struct ChunkEditCache {
+    built: bool,
+    blocks: HashMap<vec::IVec2, HashMap<vec::IVec3, u32>>,
+    chests: HashMap<vec::IVec2, HashSet<vec::IVec3>>,
+}
+
+impl ChunkEditCache {
+    //Indexes the loaded world once, edits made afterwards are recorded as they come in
+    pub fn build(
+        &mut self,
+        udm: &DashMap<vec::IVec3, u32>,
+        chestreg: &DashMap<vec::IVec3, ChestInventory>,
+    ) {
+        self.blocks.clear();
+        self.chests.clear();
+        for entry in udm.iter() {
+            self.record_block(*entry.key(), *entry.value());
+        }
+        for entry in chestreg.iter() {
+            self.record_chest(*entry.key());
+        }
+        self.built = true;
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.built
+    }
+
+    pub fn record_block(&mut self, spot: vec::IVec3, block: u32) {
+        self.blocks
+            .entry(ChunkSystem::spot_to_chunk_pos(&spot))
+            .or_default()
+            .insert(spot, block);
+    }
+
+    pub fn record_chest(&mut self, spot: vec::IVec3) {
+        self.chests
+            .entry(ChunkSystem::spot_to_chunk_pos(&spot))
+            .or_default()
+            .insert(spot);
+    }
+
+    pub fn edits(&self, chunk: &vec::IVec2) -> Vec<(SerIVec3, u32)> {
+        match self.blocks.get(chunk) {
+            Some(blocks) => blocks
+                .iter()
+                .map(|(spot, block)| (SerIVec3(IVec3::new(spot.x, spot.y, spot.z)), *block))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    //Current contents of the chunk's chests, read from the registry
+    pub fn chests(
+        &self,
+        chunk: &vec::IVec2,
+        chestreg: &DashMap<vec::IVec3, ChestInventory>,
+    ) -> Vec<(SerIVec3, [(u32, u32); ROWLENGTH as usize * 4])> {
+        match self.chests.get(chunk) {
+            Some(chests) => chests
+                .iter()
+                .filter_map(|spot| {
+                    chestreg
+                        .get(spot)
+                        .map(|chest| (SerIVec3(IVec3::new(spot.x, spot.y, spot.z)), chest.inv))
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+//Client side: chunks whose edits were asked for, with the blocks the server set in them since, which are newer than the reply
+#[derive(Resource, Default)]
+pub struct PendingChunkEdits(HashMap<vec::IVec2, (u32, HashSet<vec::IVec3>)>);
+
+impl PendingChunkEdits {
+    pub fn request(&mut self, chunk: vec::IVec2) {
+        self.0.entry(chunk).or_default().0 += 1;
+    }
+
+    //A BlockSet or MultiBlockSet from the server
+    pub fn block_set(&mut self, spot: vec::IVec3) {
+        if let Some((_, set)) = self.0.get_mut(&ChunkSystem::spot_to_chunk_pos(&spot)) {
+            set.insert(spot);
+        }
+    }
+
+    //Edits of a reply still worth applying, None if the chunk was not asked for
+    pub fn receive(
+        &mut self,
+        chunk: vec::IVec2,
+        edits: Vec<(SerIVec3, u32)>,
+    ) -> Option<Vec<(vec::IVec3, u32)>> {
+        let (outstanding, set) = self.0.get_mut(&chunk)?;
+        let edits = edits
+            .into_iter()
+            .map(|(spot, block)| (vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z), block))
+            .filter(|(spot, _)| !set.contains(spot))
+            .collect();
+
+        *outstanding -= 1;
+        if *outstanding == 0 {
+            self.0.remove(&chunk);
+        }
+        Some(edits)
+    }
+}
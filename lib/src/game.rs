@@ -28,7 +28,7 @@ use std::sync::Arc;
 
 use parking_lot::{Mutex, RwLock};
 use jeffy_quintet::client::QuintetClientPlugin;
-use jeffy_quintet::server::QuintetServerPlugin;
+use jeffy_quintet::server::{server_listening, QuintetServerPlugin};
 
 
 pub const CHUNKFADEINTIME: f32 = 0.6;
@@ -48,6 +48,9 @@ use crate::audio::AudioPlayer;
 
 pub const PLAYERSCALE: f32 = 1.0;
 
+#[cfg(feature = "glfw")]
+use crate::blockedits::broken_item;
+use crate::blockedits::CLUSTER_CRYSTAL;
 use crate::blockinfo::Blocks;
 use crate::blockoverlay::BlockOverlay;
 use crate::chunk::{ChunkFacade, ChunkSystem, AUTOMATA_QUEUED_CHANGES, NONUSERDATAMAP, USERDATAMAP};
@@ -75,17 +78,21 @@ use crate::glyphface::GlyphFace;
 use crate::guisystem::GuiSystem;
 use crate::hud::{Hud, HudElement, SlotIndexType};
 use crate::inventory::*;
+use crate::invtransactions::{chest_pos, chest_slot, PlayerSlots};
 
 use crate::modelentity::ModelEntity;
-use crate::newclient::{handle_server_messages, start_connection, PlayerUpdateTimer};
-use crate::newserver::{handle_client_messages, start_listening};
+use crate::chunkedits::{ChunkEditCache, PendingChunkEdits};
+use crate::newclient::{greet_server, handle_connection_failures, handle_server_messages, request_chunk_edits, send_block_edits, send_inventory_intents, send_my_location, start_connection, update_otherplayers_interps, PlayerUpdateTimer};
+use crate::mobreplication::MobBaselines;
+use crate::newserver::{handle_client_connections, handle_client_messages, replicate_mobs, start_listening, MobUpdateTimer, ServerClients};
 //use crate::network::NetworkConnector;
 use crate::planetinfo::Planets;
 use crate::playerposition::PlayerPosition;
 use crate::raycast::*;
-use crate::recipes::{Recipe, RecipeEntry, RECIPES};
+use crate::recipes::{craft_in_inventory, RecipeEntry, RECIPES};
 use crate::selectcube::SelectCube;
 //use crate::server_types::{Message, MessageType};
+use crate::server_types::{InventoryIntent, SlotRef};
 use crate::shader::Shader;
 use crate::specialblocks::door::{self, DoorInfo};
 use crate::statics::{MISCSETTINGS, MY_MULTIPLAYER_UUID, save_misc};
@@ -370,6 +377,21 @@ pub static mut PLAYERPOS: Lazy<PlayerCam> = Lazy::new(|| PlayerCam {
 
 pub static mut CHUNKSYS: Option<Arc<RwLock<ChunkSystem>>> = None;
 pub static mut CAMERA: Option<Arc<Mutex<Camera>>> = None;
+pub static mut CHEST_REGISTRY: Option<Arc<DashMap<vec::IVec3, ChestInventory>>> = None;
+pub static mut TIMEOFDAY: Option<Arc<Mutex<f32>>> = None;
+pub static mut KNOWN_CAMERAS: Option<Arc<DashMap<Uuid, Vec3>>> = None;
+pub static mut INVENTORY: Option<Arc<RwLock<Inventory>>> = None;
+pub static mut PLAYER_MODEL_ENTITIES: Option<Arc<DashMap<Uuid, ModelEntity>>> = None;
+pub static mut NON_STATIC_MODEL_ENTITIES: Option<Arc<DashMap<u32, ModelEntity>>> = None;
+
+//Items the server has told us to put in our mouse slot, picked up by Game::update
+pub static MOUSE_SLOT_QUEUE: Lazy<Queue<(u32, u32)>> = Lazy::new(|| Queue::new());
+
+//Block edits already made locally in multiplayer, sent by newclient::send_block_edits. Refused ones come back as a BlockCorrection
+pub static BLOCK_EDIT_QUEUE: Lazy<Queue<Vec<(vec::IVec3, u32)>>> = Lazy::new(|| Queue::new());
+
+//Inventory intents for the server, sent by newclient::send_inventory_intents
+pub static INVENTORY_INTENT_QUEUE: Lazy<Queue<InventoryIntent>> = Lazy::new(|| Queue::new());
 
 
 
@@ -1136,17 +1158,22 @@ impl Game {
             
             if unsafe {HEADLESS} { //Headless server
                 app.add_plugins(QuintetServerPlugin::default());
-                app.add_systems(Startup, start_listening);
-                app.add_systems(Update, handle_client_messages);
+                app.init_resource::<ServerClients>();
+                app.init_resource::<ChunkEditCache>();
+                app.insert_resource(MobUpdateTimer(Timer::from_seconds(0.1, TimerMode::Repeating)));
+                app.add_systems(Update, start_listening);
+                app.add_systems(Update, (handle_client_connections, handle_client_messages, replicate_mobs).chain().run_if(server_listening));
             
             } else {
                 app.add_systems(Update, attend_needed_spots);
                 app.add_systems(Update, attend_chunk_queues);
                 if unsafe {!HEADLESS} && unsafe {!SINGLEPLAYER} { //Client multiplayer
-                    app.init_resource::<PlayerUpdateTimer>();
+                    app.insert_resource(PlayerUpdateTimer(Timer::from_seconds(0.25, TimerMode::Repeating)));
+                    app.init_resource::<MobBaselines>();
+                    app.init_resource::<PendingChunkEdits>();
                     app.add_plugins(QuintetClientPlugin::default());
                     app.add_systems(Startup, start_connection);
-                    app.add_systems(Update, handle_server_messages);
+                    app.add_systems(Update, (handle_connection_failures, greet_server, handle_server_messages, request_chunk_edits, send_block_edits, send_inventory_intents, update_otherplayers_interps, send_my_location).chain());
                 } else if unsafe {!HEADLESS} && unsafe {SINGLEPLAYER} { //Client singleplayer
 
                 } 
@@ -1617,11 +1644,19 @@ impl Game {
 
         let chest_registry = Arc::new(DashMap::new());
 
+        let timeofday = Arc::new(Mutex::new(250.0));
+
 
         //INITIALIZING PREVIOUS GAME RESOURCES HERE
 
         unsafe { CHUNKSYS = Some(chunksys.clone()) };
         unsafe { CAMERA = Some(cam.clone()) };
+        unsafe { CHEST_REGISTRY = Some(chest_registry.clone()) };
+        unsafe { TIMEOFDAY = Some(timeofday.clone()) };
+        unsafe { KNOWN_CAMERAS = Some(kc.clone()) };
+        unsafe { INVENTORY = Some(inv.clone()) };
+        unsafe { PLAYER_MODEL_ENTITIES = Some(pme.clone()) };
+        unsafe { NON_STATIC_MODEL_ENTITIES = Some(nsme.clone()) };
 
         let mut g = Game {
             //previously chunksys
@@ -1733,7 +1768,7 @@ impl Game {
             my_uuid,
             ambient_bright_mult: 1.0,
             daylength: 900.0,
-            timeofday: Arc::new(Mutex::new(250.0)),
+            timeofday,
             sunrise_factor: 0.0,
             sunset_factor: 0.0,
             visions_timer: 0.0,
@@ -3193,6 +3228,23 @@ impl Game {
         return result;
     }
 
+    //Applies an inventory intent to our own slots in singleplayer, or sends it to the server, which answers with the
+    //slots it changed. Returns whether the inventory needs redrawing now
+    pub fn inventory_intent(&mut self, intent: InventoryIntent) -> bool {
+        if self.vars.in_multiplayer {
+            INVENTORY_INTENT_QUEUE.push(intent);
+            return false;
+        }
+
+        let mut inv = self.inventory.write();
+        let mut slots = PlayerSlots {
+            inv: &mut *inv,
+            mouse: &mut self.mouse_slot,
+            chests: &*self.chest_registry,
+        };
+        slots.apply(&intent).is_ok()
+    }
+
     pub fn craft_recipe_index(&mut self, index: usize, all: bool) {
         unsafe {
             let recipe = {
@@ -3200,6 +3252,14 @@ impl Game {
                 &r[index].clone().recipe
             };
 
+            //The server crafts from its copy of our inventory, and sends us the slots that changed
+            if self.vars.in_multiplayer {
+                if let Some(recipeindex) = RECIPES.iter().position(|r| r == recipe) {
+                    INVENTORY_INTENT_QUEUE.push(InventoryIntent::CraftRecipe(recipeindex, all));
+                }
+                return;
+            }
+
             let mut hasreqs = true;
             let invlock = self.inventory.write();
 
@@ -3208,87 +3268,10 @@ impl Game {
 
             drop(invlock);
 
-            let mut morepossible = true;
-            fn craft_in_local_inv(recipe: &Recipe, inven: &mut Inventory, morepossible: &mut bool) {
-                let mut hasreqs = true;
-                for req in &recipe.0 {
-                    let mut amt = 0;
-
-                    for i in 0..ROWLENGTH as usize {
-                        let typehere = inven.inv[i].0;
-                        if typehere == req.0 {
-                            amt += inven.inv[i].1;
-                        }
-                    }
-
-                    if amt < req.1 {
-                        hasreqs = false;
-                        (*morepossible) = false;
-                    }
-                }
-
-                if hasreqs {
-                    //Find an empty spot OR MATCHING RESULT ITEM SPOT in their imaginary inv that would exist if we were to subtract the necessary ingredients:
-                    //Make an imaginary clone of their inventory:
-                    let invclone = &mut inven.inv;
-
-                    //Subtract the ingredients
-                    for req in &recipe.0 {
-                        let mut amt = 0;
-
-                        for i in 0..ROWLENGTH as usize {
-                            let typehere = invclone[i].0;
-                            if typehere == req.0 {
-                                while invclone[i].1 > 0 && amt < req.1 {
-                                    amt += 1;
-                                    invclone[i].1 -= 1;
-
-                                    if invclone[i].1 == 0 {
-                                        invclone[i].0 = 0;
-                                    }
-                                }
-                                if amt >= req.1 {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-
-                    //Find the predicted empty spot or matching item slot
-                    let resultslot = {
-                        let mut slot = None;
-
-                        for i in 0..ROWLENGTH as usize {
-                            let typehere = invclone[i].0;
-                            if (typehere == 0 || typehere == recipe.1 .0)
-                                && (invclone[i].1 + recipe.1 .1) <= 999
-                            {
-                                slot = Some(i);
-                                break;
-                            }
-                        }
-
-                        slot
-                    };
-
-                    match resultslot {
-                        Some(slot) => {
-                            invclone[slot].0 = recipe.1 .0;
-                            invclone[slot].1 = invclone[slot].1 + recipe.1 .1;
-                        }
-                        None => {
-                            (*morepossible) = false;
-                        }
-                    }
-                }
-            }
-
             if all {
                 let mut newinv = originalinvinv.clone();
 
-                while morepossible {
-                    craft_in_local_inv(recipe, &mut newinv, &mut morepossible);
-                }
+                while craft_in_inventory(recipe, &mut newinv) {}
 
                 if newinv != originalinvinv {
                     for i in 0..ROWLENGTH as usize {
@@ -4410,6 +4393,11 @@ impl Game {
             #[cfg(feature = "audio")]
             self.do_step_sounds();
 
+            while let Some(slot) = MOUSE_SLOT_QUEUE.pop() {
+                self.mouse_slot = slot;
+                self.inventory.write().dirty = true;
+            }
+
             if self.inventory.read().dirty {
                 self.update_inventory();
             }
@@ -6170,8 +6158,9 @@ impl Game {
         id: u32,
         at: IVec3,
         set: &mut HashSet<IVec2>,
-    ) {
+    ) -> Vec<IVec3> {
         let mut stack = vec![at]; // Initialize stack with initial position
+        let mut removed = Vec::new();
 
         while let Some(current) = stack.pop() {
             // Check if the block at the current position is already deleted
@@ -6181,6 +6170,7 @@ impl Game {
             if chunksys.blockat(current) != 0 {
                 // Set the block at the current position
                 chunksys.set_block(current, 0, true);
+                removed.push(current);
                 let key = ChunkSystem::spot_to_chunk_pos(&current);
                 set.insert(key);
                 // Add neighbors to the stack if they have the same id
@@ -6192,6 +6182,7 @@ impl Game {
                 }
             }
         }
+        removed
     }
     pub fn cast_break_ray(&mut self) {
         let cl = {
@@ -6210,16 +6201,20 @@ impl Game {
             Some((tip, block_hit)) => {
                 let blockbits = csys.read().blockat(block_hit);
                 let blockat = blockbits & Blocks::block_id_bits();
-                if blockat == 16 {
+                if blockat == CLUSTER_CRYSTAL {
                     let mut set: HashSet<IVec2> = HashSet::new();
-                    Game::delete_block_recursively(&csys, 16, block_hit, &mut set);
+                    let removed = Game::delete_block_recursively(&csys, CLUSTER_CRYSTAL, block_hit, &mut set);
                     for key in set {
                         csys
                             .read()
                             .queue_rerender_with_key(key, true, false);
                     }
+                    //One crystal per block, as the server counts them
                     #[cfg(feature = "glfw")]
-                    self.drops.add_drop(tip, 17, 1);
+                    self.drops.add_drop(tip, broken_item(CLUSTER_CRYSTAL), removed.len() as u32);
+                    if self.vars.in_multiplayer {
+                        BLOCK_EDIT_QUEUE.push(removed.into_iter().map(|spot| (spot, 0)).collect());
+                    }
                 } else if blockat == 19 {
                     //Door stuff
                     let top = DoorInfo::get_door_top_bit(blockbits);
@@ -6234,42 +6229,25 @@ impl Game {
                     #[cfg(feature = "glfw")]
                     self.drops.add_drop(tip, 19, 1);
 
+                    csys.read().set_block(block_hit, 0, true);
+                    csys
+                        .read()
+                        .set_block_and_queue_rerender(other_half, 0, true, true, false);
                     if self.vars.in_multiplayer {
-                        // let mut message = Message::new(
-                        //     MessageType::MultiBlockSet,
-                        //     Vec3::new(block_hit.x as f32, block_hit.y as f32, block_hit.z as f32),
-                        //     0.0,
-                        //     0,
-                        // );
-                        // message.info2 = 0;
-                        // message.otherpos = other_half;
-
-                        // self.netconn.send(&message);
-                    } else {
-                        csys.read().set_block(block_hit, 0, true);
-                        csys
-                            .read()
-                            .set_block_and_queue_rerender(other_half, 0, true, true, false);
+                        BLOCK_EDIT_QUEUE.push(vec![(block_hit, 0), (other_half, 0)]);
                     }
                 } else {
                     if blockat != 0 {
                         #[cfg(feature = "glfw")]
-                        self.drops.add_drop(tip, blockat, 1);
+                        self.drops.add_drop(tip, broken_item(blockat), 1);
                     }
 
                     //TODO: PROBLEM HERE THAT WILL ALLOW USERS TO KEEP DUPING A BLOCK AS LONG AS THE SERVER DOESNT RESPOND
+                    csys
+                        .read()
+                        .set_block_and_queue_rerender(block_hit, 0, true, true, false);
                     if self.vars.in_multiplayer {
-                        // let message = Message::new(
-                        //     MessageType::BlockSet,
-                        //     Vec3::new(block_hit.x as f32, block_hit.y as f32, block_hit.z as f32),
-                        //     0.0,
-                        //     0,
-                        // );
-                        // self.netconn.send(&message);
-                    } else {
-                        csys
-                            .read()
-                            .set_block_and_queue_rerender(block_hit, 0, true, true, false);
+                        BLOCK_EDIT_QUEUE.push(vec![(block_hit, 0)]);
                     }
                 }
             }
@@ -6932,31 +6910,18 @@ impl Game {
                         DoorInfo::toggle_door_open_bit(&mut blockbitshere);
                         DoorInfo::toggle_door_open_bit(&mut otherhalfbits);
 
+                        csys
+                            .write()
+                            .set_block(otherhalf, otherhalfbits, true);
+                        csys.write().set_block_and_queue_rerender(
+                            block_hit,
+                            blockbitshere,
+                            true,
+                            true,
+                            true,
+                        );
                         if self.vars.in_multiplayer {
-                            // let mut message = Message::new(
-                            //     MessageType::MultiBlockSet,
-                            //     Vec3::new(
-                            //         block_hit.x as f32,
-                            //         block_hit.y as f32,
-                            //         block_hit.z as f32,
-                            //     ),
-                            //     0.0,
-                            //     blockbitshere,
-                            // );
-                            // message.info2 = otherhalfbits;
-                            // message.otherpos = otherhalf;
-                            // self.netconn.send(&message);
-                        } else {
-                            csys
-                                .write()
-                                .set_block(otherhalf, otherhalfbits, true);
-                            csys.write().set_block_and_queue_rerender(
-                                block_hit,
-                                blockbitshere,
-                                true,
-                                true,
-                                true,
-                            );
+                            BLOCK_EDIT_QUEUE.push(vec![(block_hit, blockbitshere), (otherhalf, otherhalfbits)]);
                         }
                     } else if blockidhere == 21 {
                         //RIGHT CLICKED A CHEST
@@ -6970,6 +6935,8 @@ impl Game {
                             ATSMALLTABLE = false;
                         }
                         self.crafting_open = true;
+                        //The server only lets table recipes through for the table we opened last
+                        self.inventory_intent(InventoryIntent::OpenCraftingTable(chest_pos(block_hit)));
 
                         self.window
                             .write()
@@ -7106,37 +7073,22 @@ impl Game {
 
                                         let _chunktoreb = ChunkSystem::spot_to_chunk_pos(&right);
 
+                                        csys.read().set_block_and_queue_rerender(
+                                            right,
+                                            blockbitsright,
+                                            false,
+                                            true,
+                                            true,
+                                        );
+                                        csys.read().set_block_and_queue_rerender(
+                                            rightup,
+                                            neightopbits,
+                                            false,
+                                            true,
+                                            true,
+                                        );
                                         if self.vars.in_multiplayer {
-                                            // let mut message = Message::new(
-                                            //     MessageType::MultiBlockSet,
-                                            //     Vec3::new(
-                                            //         right.x as f32,
-                                            //         right.y as f32,
-                                            //         right.z as f32,
-                                            //     ),
-                                            //     0.0,
-                                            //     blockbitsright,
-                                            // );
-
-                                            // message.info2 = neightopbits;
-                                            // message.otherpos = rightup;
-
-                                            // self.netconn.send(&message);
-                                        } else {
-                                            csys.read().set_block_and_queue_rerender(
-                                                right,
-                                                blockbitsright,
-                                                false,
-                                                true,
-                                                true,
-                                            );
-                                            csys.read().set_block_and_queue_rerender(
-                                                rightup,
-                                                neightopbits,
-                                                false,
-                                                true,
-                                                true,
-                                            );
+                                            BLOCK_EDIT_QUEUE.push(vec![(right, blockbitsright), (rightup, neightopbits)]);
                                         }
                                     }
                                 }
@@ -7160,72 +7112,42 @@ impl Game {
 
                                         let _chunktoreb = ChunkSystem::spot_to_chunk_pos(&left);
 
+                                        csys.read().set_block_and_queue_rerender(
+                                            left,
+                                            blockbitsleft,
+                                            false,
+                                            true,
+                                            true,
+                                        );
+                                        csys.read().set_block_and_queue_rerender(
+                                            leftup,
+                                            neightopbits,
+                                            false,
+                                            true,
+                                            true,
+                                        );
                                         if self.vars.in_multiplayer {
-                                            // let mut message = Message::new(
-                                            //     MessageType::MultiBlockSet,
-                                            //     Vec3::new(
-                                            //         left.x as f32,
-                                            //         left.y as f32,
-                                            //         left.z as f32,
-                                            //     ),
-                                            //     0.0,
-                                            //     blockbitsleft,
-                                            // );
-
-                                            // message.info2 = neightopbits;
-                                            // message.otherpos = leftup;
-
-                                            // self.netconn.send(&message);
-                                        } else {
-                                            csys.read().set_block_and_queue_rerender(
-                                                left,
-                                                blockbitsleft,
-                                                false,
-                                                true,
-                                                true,
-                                            );
-                                            csys.read().set_block_and_queue_rerender(
-                                                leftup,
-                                                neightopbits,
-                                                false,
-                                                true,
-                                                true,
-                                            );
+                                            BLOCK_EDIT_QUEUE.push(vec![(left, blockbitsleft), (leftup, neightopbits)]);
                                         }
                                     }
                                 }
 
+                                csys.read().set_block_and_queue_rerender(
+                                    place_point,
+                                    bottom_id,
+                                    false,
+                                    true,
+                                    true,
+                                );
+                                csys.read().set_block_and_queue_rerender(
+                                    place_above,
+                                    top_id,
+                                    false,
+                                    true,
+                                    true,
+                                );
                                 if self.vars.in_multiplayer {
-                                    // let mut message = Message::new(
-                                    //     MessageType::MultiBlockSet,
-                                    //     Vec3::new(
-                                    //         place_point.x as f32,
-                                    //         place_point.y as f32,
-                                    //         place_point.z as f32,
-                                    //     ),
-                                    //     0.0,
-                                    //     bottom_id,
-                                    // );
-
-                                    // message.info2 = top_id;
-                                    // message.otherpos = place_above;
-
-                                    // self.netconn.send(&message);
-                                } else {
-                                    csys.read().set_block_and_queue_rerender(
-                                        place_point,
-                                        bottom_id,
-                                        false,
-                                        true,
-                                        true,
-                                    );
-                                    csys.read().set_block_and_queue_rerender(
-                                        place_above,
-                                        top_id,
-                                        false,
-                                        true,
-                                        true,
-                                    );
+                                    BLOCK_EDIT_QUEUE.push(vec![(place_point, bottom_id), (place_above, top_id)]);
                                 }
                             }
                         } else if id == 45 {
@@ -7246,27 +7168,15 @@ impl Game {
 
                             Blocks::set_direction_bits(&mut conveyor_id, direction);
 
+                            csys.read().set_block_and_queue_rerender(
+                                place_point,
+                                conveyor_id,
+                                false,
+                                true,
+                                false,
+                            );
                             if self.vars.in_multiplayer {
-                                // let message = Message::new(
-                                //     MessageType::BlockSet,
-                                //     Vec3::new(
-                                //         place_point.x as f32,
-                                //         place_point.y as f32,
-                                //         place_point.z as f32,
-                                //     ),
-                                //     0.0,
-                                //     conveyor_id,
-                                // );
-
-                                // self.netconn.send(&message);
-                            } else {
-                                csys.read().set_block_and_queue_rerender(
-                                    place_point,
-                                    conveyor_id,
-                                    false,
-                                    true,
-                                    false,
-                                );
+                                BLOCK_EDIT_QUEUE.push(vec![(place_point, conveyor_id)]);
                             }
                         } else if id == 20 {
                             //ladder shit
@@ -7286,27 +7196,15 @@ impl Game {
 
                             Blocks::set_direction_bits(&mut ladder_id, direction);
 
+                            csys.read().set_block_and_queue_rerender(
+                                place_point,
+                                ladder_id,
+                                false,
+                                true,
+                                false,
+                            );
                             if self.vars.in_multiplayer {
-                                // let message = Message::new(
-                                //     MessageType::BlockSet,
-                                //     Vec3::new(
-                                //         place_point.x as f32,
-                                //         place_point.y as f32,
-                                //         place_point.z as f32,
-                                //     ),
-                                //     0.0,
-                                //     ladder_id,
-                                // );
-
-                                // self.netconn.send(&message);
-                            } else {
-                                csys.read().set_block_and_queue_rerender(
-                                    place_point,
-                                    ladder_id,
-                                    false,
-                                    true,
-                                    false,
-                                );
+                                BLOCK_EDIT_QUEUE.push(vec![(place_point, ladder_id)]);
                             }
                         } else if id == 21 {
                             //Chest shit
@@ -7326,50 +7224,27 @@ impl Game {
 
                             Blocks::set_direction_bits(&mut chest_id, direction);
 
+                            csys.read().set_block_and_queue_rerender(
+                                place_point,
+                                chest_id,
+                                false,
+                                true,
+                                false,
+                            );
                             if self.vars.in_multiplayer {
-                                // let message = Message::new(
-                                //     MessageType::BlockSet,
-                                //     Vec3::new(
-                                //         place_point.x as f32,
-                                //         place_point.y as f32,
-                                //         place_point.z as f32,
-                                //     ),
-                                //     0.0,
-                                //     chest_id,
-                                // );
-
-                                // self.netconn.send(&message);
-                            } else {
+                                BLOCK_EDIT_QUEUE.push(vec![(place_point, chest_id)]);
+                            }
+                        } else {
+                            if !Blocks::is_non_placeable(slot.0) {
                                 csys.read().set_block_and_queue_rerender(
                                     place_point,
-                                    chest_id,
+                                    id,
                                     false,
                                     true,
                                     false,
                                 );
-                            }
-                        } else {
-                            if !Blocks::is_non_placeable(slot.0) {
                                 if self.vars.in_multiplayer {
-                                    // let message = Message::new(
-                                    //     MessageType::BlockSet,
-                                    //     Vec3::new(
-                                    //         place_point.x as f32,
-                                    //         place_point.y as f32,
-                                    //         place_point.z as f32,
-                                    //     ),
-                                    //     0.0,
-                                    //     id,
-                                    // );
-                                    // self.netconn.send(&message);
-                                } else {
-                                    csys.read().set_block_and_queue_rerender(
-                                        place_point,
-                                        id,
-                                        false,
-                                        true,
-                                        false,
-                                    );
+                                    BLOCK_EDIT_QUEUE.push(vec![(place_point, id)]);
                                 }
                             }
                         }
@@ -7437,47 +7312,8 @@ impl Game {
                 // self.stamina
                 //     .store((s + foodstats.1).min(100), Ordering::Relaxed);
 
-                //REDUCE THE INV ITEM:
-                if self.vars.in_multiplayer {
-                    if slot.1 == 1 {
-                        let mutslot = &mut self.inventory.write().inv[slot_selected];
-                        mutslot.1 = 0;
-                        mutslot.0 = 0;
-
-                        // let mut msg = Message::new(
-                        //     MessageType::ChestInvUpdate,
-                        //     Vec3::ZERO,
-                        //     0.0,
-                        //     slot_selected as u32,
-                        // );
-                        // msg.infof = 0.0;
-                        // msg.info2 = 1;
-
-                        // self.netconn.send(&msg);
-                    } else {
-                        // let slot = &self.inventory.read().inv[slot_selected];
-
-                        // let mut msg = Message::new(
-                        //     MessageType::ChestInvUpdate,
-                        //     Vec3::ZERO,
-                        //     slot.0 as f32,
-                        //     slot_selected as u32,
-                        // );
-                        // msg.infof = slot.1 as f32 - 1.0;
-                        // msg.info2 = 1;
-
-                        // self.netconn.send(&msg);
-                    }
-                } else {
-                    if slot.1 == 1 {
-                        let mutslot = &mut self.inventory.write().inv[slot_selected];
-                        mutslot.1 = 0;
-                        mutslot.0 = 0;
-                    } else {
-                        let mutslot = &mut self.inventory.write().inv[slot_selected];
-                        mutslot.1 -= 1;
-                    }
-                }
+                //REDUCE THE INV ITEM, the server does it for us in multiplayer and sends the slot back:
+                self.inventory_intent(InventoryIntent::ConsumeSlot(slot_selected));
             }
         } else {
         }
@@ -7515,6 +7351,13 @@ impl Game {
                             //let csys = self.chunksys.write();
                             unsafe {
                                 match MOUSED_SLOT {
+                                    //Shift click takes the whole stack into the inventory
+                                    SlotIndexType::ChestSlot(e) if CROUCHING => {
+                                        updateinv = self.inventory_intent(InventoryIntent::TakeFromChest(
+                                            chest_pos(self.hud.current_chest),
+                                            e as usize,
+                                        ));
+                                    }
                                     SlotIndexType::ChestSlot(e) => {
                                         match self.chest_registry.get_mut(&self.hud.current_chest) {
                                             Some(mut ch) => {
@@ -7523,26 +7366,7 @@ impl Game {
                                                 //IF This slot has an item id the same as our mouse slot
                                                 if slot.0 == self.mouse_slot.0 {
                                                     if self.vars.in_multiplayer {
-                                                        /*OTHERPOS: CURRENT CHEST */
-                                                        /*INFO: DEST SLOT INDEX */
-                                                        /*INFO2: SLOT INDEX TYPE */
-                                                        /*ROT: ID */
-                                                        /*INFOF: COUNT */
-                                                        /*X, Y:   SLOT MOVED TO MOUSE OF <GOOSE> PLAYER */
-                                                        /*Z: IF MOUSE_SLOT IS REPLACED */
-                                                        /*BO: IF WE WANT SERVER-SIDE CHEST-TO-MOUSE DISPLACEMENT (NO if this is adding to a stack, it will put the previous stack in our hand) */
-                                                        // let mut msg = Message::new(
-                                                        //     MessageType::ChestInvUpdate,
-                                                        //     Vec3::new(0 as f32, 0 as f32, 1.0),
-                                                        //     slot.0 as f32,
-                                                        //     e as u32,
-                                                        // );
-                                                        // msg.otherpos = self.hud.current_chest;
-                                                        // msg.info2 = /*0 = CHEST, 1 = INV, 2 = NONE */0;
-                                                        // msg.infof =
-                                                        //     (slot.1 + self.mouse_slot.1) as f32;
-                                                        // msg.bo = false;
-                                                        // self.netconn.send(&msg);
+                                                        INVENTORY_INTENT_QUEUE.push(InventoryIntent::MoveStack(SlotRef::Mouse, chest_slot(self.hud.current_chest, e as usize)));
                                                     } else {
                                                         slot.1 = slot.1 + self.mouse_slot.1;
 
@@ -7553,29 +7377,7 @@ impl Game {
                                                 {
                                                     let buff = slot.clone();
                                                     if self.vars.in_multiplayer {
-                                                        /*OTHERPOS: CURRENT CHEST */
-                                                        /*INFO: DEST SLOT INDEX */
-                                                        /*INFO2: SLOT INDEX TYPE */
-                                                        /*ROT: ID */
-                                                        /*INFOF: COUNT */
-                                                        /*X, Y:   SLOT MOVED TO MOUSE OF <GOOSE> PLAYER */
-                                                        /*Z: IF MOUSE_SLOT IS REPLACED */
-                                                        /*BO: IF WE WANT SERVER-SIDE CHEST-TO-MOUSE DISPLACEMENT (NO if this is adding to a stack, it will put the previous stack in our hand) */
-                                                        // let mut msg = Message::new(
-                                                        //     MessageType::ChestInvUpdate,
-                                                        //     Vec3::new(
-                                                        //         buff.0 as f32,
-                                                        //         buff.1 as f32,
-                                                        //         1.0,
-                                                        //     ),
-                                                        //     self.mouse_slot.0 as f32,
-                                                        //     e as u32,
-                                                        // );
-                                                        // msg.otherpos = self.hud.current_chest;
-                                                        // msg.info2 = /*0 = CHEST, 1 = INV, 2 = NONE */0;
-                                                        // msg.infof = self.mouse_slot.1 as f32;
-                                                        // msg.bo = true;
-                                                        // self.netconn.send(&msg);
+                                                        INVENTORY_INTENT_QUEUE.push(InventoryIntent::MoveStack(SlotRef::Mouse, chest_slot(self.hud.current_chest, e as usize)));
                                                     } else {
                                                         slot.0 = self.mouse_slot.0;
                                                         slot.1 = self.mouse_slot.1;
@@ -7595,25 +7397,7 @@ impl Game {
                                         //IF This slot has an item id the same as our mouse slot
                                         if slot.0 == self.mouse_slot.0 {
                                             if self.vars.in_multiplayer {
-                                                /*OTHERPOS: CURRENT CHEST */
-                                                /*INFO: DEST SLOT INDEX */
-                                                /*INFO2: SLOT INDEX TYPE */
-                                                /*ROT: ID */
-                                                /*INFOF: COUNT */
-                                                /*X, Y:   SLOT MOVED TO MOUSE OF <GOOSE> PLAYER */
-                                                /*Z: IF MOUSE_SLOT IS REPLACED */
-                                                /*BO: IF WE WANT SERVER-SIDE CHEST-TO-MOUSE DISPLACEMENT (NO if this is adding to a stack, it will put the previous stack in our hand) */
-                                                // let mut msg = Message::new(
-                                                //     MessageType::ChestInvUpdate,
-                                                //     Vec3::new(0 as f32, 0 as f32, 1.0),
-                                                //     slot.0 as f32,
-                                                //     e as u32,
-                                                // );
-                                                // msg.otherpos = self.hud.current_chest;
-                                                // msg.info2 = /*0 = CHEST, 1 = INV, 2 = NONE */1;
-                                                // msg.infof = (slot.1 + self.mouse_slot.1) as f32;
-                                                // msg.bo = false;
-                                                // self.netconn.send(&msg);
+                                                INVENTORY_INTENT_QUEUE.push(InventoryIntent::MoveStack(SlotRef::Mouse, SlotRef::Inv(e as usize)));
                                             } else {
                                                 slot.1 = slot.1 + self.mouse_slot.1;
 
@@ -7625,25 +7409,7 @@ impl Game {
                                             let buff = slot.clone();
 
                                             if self.vars.in_multiplayer {
-                                                /*OTHERPOS: CURRENT CHEST */
-                                                /*INFO: DEST SLOT INDEX */
-                                                /*INFO2: SLOT INDEX TYPE */
-                                                /*ROT: ID */
-                                                /*INFOF: COUNT */
-                                                /*X, Y:   SLOT MOVED TO MOUSE OF <GOOSE> PLAYER */
-                                                /*Z: IF MOUSE_SLOT IS REPLACED */
-                                                /*BO: IF WE WANT SERVER-SIDE CHEST-TO-MOUSE DISPLACEMENT (NO if this is adding to a stack, it will put the previous stack in our hand) */
-                                                // let mut msg = Message::new(
-                                                //     MessageType::ChestInvUpdate,
-                                                //     Vec3::new(buff.0 as f32, buff.1 as f32, 1.0),
-                                                //     self.mouse_slot.0 as f32,
-                                                //     e as u32,
-                                                // );
-                                                // msg.otherpos = self.hud.current_chest;
-                                                // msg.info2 = /*0 = CHEST, 1 = INV, 2 = NONE */ 1;
-                                                // msg.infof = self.mouse_slot.1 as f32;
-                                                // msg.bo = true;
-                                                // self.netconn.send(&msg);
+                                                INVENTORY_INTENT_QUEUE.push(InventoryIntent::MoveStack(SlotRef::Mouse, SlotRef::Inv(e as usize)));
                                             } else {
                                                 slot.0 = self.mouse_slot.0;
                                                 slot.1 = self.mouse_slot.1;
@@ -7667,6 +7433,30 @@ impl Game {
                     // }
                 }
                 "Place/Use" => {
+                    //Right click splits: half a stack into an empty mouse, else one item of the mouse onto the slot
+                    if a == Action::Press {
+                        let (slot, there) = match unsafe { &*addr_of!(MOUSED_SLOT) } {
+                            SlotIndexType::ChestSlot(e) => (
+                                chest_slot(self.hud.current_chest, *e as usize),
+                                self.chest_registry
+                                    .get(&self.hud.current_chest)
+                                    .map_or((0, 0), |ch| ch.inv[*e as usize]),
+                            ),
+                            SlotIndexType::InvSlot(e) => {
+                                (SlotRef::Inv(*e as usize), self.inventory.read().inv[*e as usize])
+                            }
+                            SlotIndexType::None => return,
+                        };
+
+                        let intent = if self.mouse_slot.0 == 0 {
+                            InventoryIntent::SplitStack(slot, SlotRef::Mouse, (there.1 + 1) / 2)
+                        } else {
+                            InventoryIntent::SplitStack(SlotRef::Mouse, slot, 1)
+                        };
+                        if self.inventory_intent(intent) {
+                            self.update_inventory();
+                        }
+                    }
                     //self.vars.right_mouse_clicked = a == Action::Press;
                     // if !self.vars.ship_taken_off {
                     //     if self.vars.right_mouse_clicked {
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::chunk::ChunkSystem;
+use crate::vec;
+
+//Chunks around a player the server keeps them informed about, the radius multiplayer clients load chunks with
+pub const VIEW_RADIUS: i32 = 10;
+
+//Server side, one per client: the chunks around its player, and the other players it currently sees
+#[derive(Default)]
+pub struct ClientInterest {
+    //Chunk the player was last in, None until their first PlayerUpdate
+    center: Option<vec::IVec2>,
+    subscribed: HashSet<vec::IVec2>,
+    pub visible_players: HashSet<(u64, u64)>,
+}
+
+impl ClientInterest {
+    //Moves the subscription along with the player, true if they changed chunk
+    pub fn update_center(&mut self, position: Vec3) -> bool {
+        let center = ChunkSystem::spot_to_chunk_pos_bevyvec3(&position);
+        if self.center == Some(center) {
+            return false;
+        }
+        self.center = Some(center);
+        self.subscribed.clear();
+        for x in -VIEW_RADIUS..=VIEW_RADIUS {
+            for z in -VIEW_RADIUS..=VIEW_RADIUS {
+                self.subscribed.insert(vec::IVec2::new(center.x + x, center.y + z));
+            }
+        }
+        true
+    }
+
+    pub fn sees_chunk(&self, chunk: &vec::IVec2) -> bool {
+        self.subscribed.contains(chunk)
+    }
+
+    pub fn sees_spot(&self, spot: &vec::IVec3) -> bool {
+        self.sees_chunk(&ChunkSystem::spot_to_chunk_pos(spot))
+    }
+
+    pub fn sees(&self, position: Vec3) -> bool {
+        self.sees_chunk(&ChunkSystem::spot_to_chunk_pos_bevyvec3(&position))
+    }
+}
@@ -1,11 +1,43 @@
 use crate::game::ROWLENGTH;
 
+//Most items a slot holds
+pub const MAX_STACK: u32 = 999;
+
 #[derive(Clone, PartialEq)]
 pub struct Inventory {
     pub dirty: bool,
     pub inv: [(u32, u32); ROWLENGTH as usize]
 }
 
+impl Inventory {
+    //Onto a stack of that item with room for all of it, else into the first empty slot. Returns the slot changed, None if full
+    pub fn add(&mut self, id: u32, count: u32) -> Option<usize> {
+        let index = match self.inv.iter().position(|slot| slot.0 == id && slot.1 + count <= MAX_STACK) {
+            Some(index) => index,
+            None => self.inv.iter().position(|slot| slot.0 == 0)?,
+        };
+        self.inv[index].0 = id;
+        self.inv[index].1 += count;
+        self.dirty = true;
+        Some(index)
+    }
+
+    pub fn has(&self, id: u32) -> bool {
+        self.inv.iter().any(|slot| slot.0 == id && slot.1 > 0)
+    }
+
+    //Returns the slot it came from, None if there is none of that item
+    pub fn take_one(&mut self, id: u32) -> Option<usize> {
+        let index = self.inv.iter().position(|slot| slot.0 == id && slot.1 > 0)?;
+        self.inv[index].1 -= 1;
+        if self.inv[index].1 == 0 {
+            self.inv[index] = (0, 0);
+        }
+        self.dirty = true;
+        Some(index)
+    }
+}
+
 #[derive(Clone)]
 pub struct ChestInventory {
     pub dirty: bool,
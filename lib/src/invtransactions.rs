@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use dashmap::DashMap;
+
+use crate::blockedits::MAX_EDIT_REACH;
+use crate::blockinfo::Blocks;
+use crate::game::ROWLENGTH;
+use crate::inventory::{ChestInventory, Inventory, MAX_STACK};
+use crate::recipes::{craft_in_inventory, RECIPES};
+use crate::server_types::{InventoryIntent, SerIVec3, SlotRef};
+use crate::vec;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionRejection {
+    NoSuchSlot,
+    //Not a chest block, or further than MAX_EDIT_REACH
+    ChestOutOfReach,
+    SameSlot,
+    Empty,
+    //More than the stack has, or onto a slot of another item
+    CantSplit,
+    InventoryFull,
+    NoSuchRecipe,
+    //Missing ingredients, or no room for the result
+    CantCraft,
+    NotFood,
+    //A recipe needing a crafting table, without one opened in reach
+    NoCraftingTable,
+}
+
+pub fn chest_pos(chest: vec::IVec3) -> SerIVec3 {
+    SerIVec3(IVec3::new(chest.x, chest.y, chest.z))
+}
+
+pub fn chest_slot(chest: vec::IVec3, index: usize) -> SlotRef {
+    SlotRef::Chest(chest_pos(chest), index)
+}
+
+fn chest_spot(chest: &SerIVec3) -> vec::IVec3 {
+    vec::IVec3::new(chest.0.x, chest.0.y, chest.0.z)
+}
+
+//The slots an intent names, whatever it ends up changing
+pub fn named_slots(intent: &InventoryIntent) -> Vec<SlotRef> {
+    match intent {
+        InventoryIntent::MoveStack(from, to) | InventoryIntent::SplitStack(from, to, _) => vec![*from, *to],
+        InventoryIntent::TakeFromChest(chest, index) => vec![SlotRef::Chest(*chest, *index)],
+        InventoryIntent::CraftRecipe(_, _) => Vec::new(),
+        InventoryIntent::ConsumeSlot(index) => vec![SlotRef::Inv(*index)],
+        InventoryIntent::OpenCraftingTable(_) => Vec::new(),
+    }
+}
+
+//Whether a player at player may use the chest at spot, block being what the world has there
+pub fn can_use_chest(player: Option<Vec3>, spot: &vec::IVec3, block: u32) -> bool {
+    block_in_reach(player, spot, block, 21)
+}
+
+//Same for the crafting table at spot
+pub fn can_use_crafting_table(player: Option<Vec3>, spot: &vec::IVec3, block: u32) -> bool {
+    block_in_reach(player, spot, block, 31)
+}
+
+fn block_in_reach(player: Option<Vec3>, spot: &vec::IVec3, block: u32, id: u32) -> bool {
+    let center = Vec3::new(spot.x as f32 + 0.5, spot.y as f32 + 0.5, spot.z as f32 + 0.5);
+    (block & Blocks::block_id_bits()) == id
+        && player.map_or(false, |position| position.distance(center) <= MAX_EDIT_REACH)
+}
+
+//One player's inventory and mouse slot, and the chests it can get at
+pub struct PlayerSlots<'a> {
+    pub inv: &'a mut Inventory,
+    pub mouse: &'a mut (u32, u32),
+    pub chests: &'a DashMap<vec::IVec3, ChestInventory>,
+}
+
+impl<'a> PlayerSlots<'a> {
+    pub fn get(&self, slot: &SlotRef) -> Option<(u32, u32)> {
+        match slot {
+            SlotRef::Mouse => Some(*self.mouse),
+            SlotRef::Inv(index) => self.inv.inv.get(*index).copied(),
+            SlotRef::Chest(chest, index) => self
+                .chests
+                .get(&chest_spot(chest))
+                .and_then(|chestinv| chestinv.inv.get(*index).copied()),
+        }
+    }
+
+    //Only called on slots get has found
+    fn set(&mut self, slot: &SlotRef, contents: (u32, u32)) {
+        let contents = if contents.1 == 0 { (0, 0) } else { contents };
+        match slot {
+            SlotRef::Mouse => *self.mouse = contents,
+            SlotRef::Inv(index) => {
+                self.inv.inv[*index] = contents;
+                self.inv.dirty = true;
+            }
+            SlotRef::Chest(chest, index) => {
+                if let Some(mut chestinv) = self.chests.get_mut(&chest_spot(chest)) {
+                    chestinv.inv[*index] = contents;
+                    chestinv.dirty = true;
+                }
+            }
+        }
+    }
+
+    //Applies the intent, returning the slots it changed. A refused intent changes nothing
+    pub fn apply(&mut self, intent: &InventoryIntent) -> Result<Vec<SlotRef>, TransactionRejection> {
+        match *intent {
+            InventoryIntent::MoveStack(from, to) => self.move_stack(from, to),
+            InventoryIntent::SplitStack(from, to, count) => self.split_stack(from, to, count),
+            InventoryIntent::TakeFromChest(chest, index) => self.take_from_chest(chest, index),
+            InventoryIntent::CraftRecipe(recipe, all) => self.craft(recipe, all),
+            InventoryIntent::ConsumeSlot(index) => self.consume(index),
+            //Nothing to the slots, the server keeps the table for the crafts after
+            InventoryIntent::OpenCraftingTable(_) => Ok(Vec::new()),
+        }
+    }
+
+    fn move_stack(&mut self, from: SlotRef, to: SlotRef) -> Result<Vec<SlotRef>, TransactionRejection> {
+        if from == to {
+            return Err(TransactionRejection::SameSlot);
+        }
+        let moved = self.get(&from).ok_or(TransactionRejection::NoSuchSlot)?;
+        let there = self.get(&to).ok_or(TransactionRejection::NoSuchSlot)?;
+
+        //Merged up to a full stack, the rest stays behind
+        if moved.0 == there.0 {
+            let merged = moved.1.min(MAX_STACK.saturating_sub(there.1));
+            self.set(&to, (there.0, there.1 + merged));
+            self.set(&from, (moved.0, moved.1 - merged));
+        } else {
+            self.set(&to, moved);
+            self.set(&from, there);
+        }
+        Ok(vec![from, to])
+    }
+
+    fn split_stack(&mut self, from: SlotRef, to: SlotRef, count: u32) -> Result<Vec<SlotRef>, TransactionRejection> {
+        if from == to {
+            return Err(TransactionRejection::SameSlot);
+        }
+        let split = self.get(&from).ok_or(TransactionRejection::NoSuchSlot)?;
+        let there = self.get(&to).ok_or(TransactionRejection::NoSuchSlot)?;
+
+        if split.0 == 0 || count == 0 || count > split.1 || (there.0 != 0 && there.0 != split.0) {
+            return Err(TransactionRejection::CantSplit);
+        }
+        //Only what fits under a full stack goes over
+        let count = count.min(MAX_STACK.saturating_sub(there.1));
+        if count == 0 {
+            return Err(TransactionRejection::CantSplit);
+        }
+        self.set(&to, (split.0, there.1 + count));
+        self.set(&from, (split.0, split.1 - count));
+        Ok(vec![from, to])
+    }
+
+    fn take_from_chest(&mut self, chest: SerIVec3, index: usize) -> Result<Vec<SlotRef>, TransactionRejection> {
+        let from = SlotRef::Chest(chest, index);
+        let taken = self.get(&from).ok_or(TransactionRejection::NoSuchSlot)?;
+        if taken.0 == 0 {
+            return Err(TransactionRejection::Empty);
+        }
+        let to = self.inv.add(taken.0, taken.1).ok_or(TransactionRejection::InventoryFull)?;
+        self.set(&from, (0, 0));
+        Ok(vec![from, SlotRef::Inv(to)])
+    }
+
+    fn consume(&mut self, index: usize) -> Result<Vec<SlotRef>, TransactionRejection> {
+        let slot = SlotRef::Inv(index);
+        let eaten = self.get(&slot).ok_or(TransactionRejection::NoSuchSlot)?;
+        if eaten.0 == 0 {
+            return Err(TransactionRejection::Empty);
+        }
+        if !Blocks::is_food(eaten.0) {
+            return Err(TransactionRejection::NotFood);
+        }
+        self.set(&slot, (eaten.0, eaten.1 - 1));
+        Ok(vec![slot])
+    }
+
+    //Crafting tables are checked by the server beforehand, against the one the player last opened
+    fn craft(&mut self, recipe: usize, all: bool) -> Result<Vec<SlotRef>, TransactionRejection> {
+        let recipe = RECIPES.get(recipe).ok_or(TransactionRejection::NoSuchRecipe)?;
+        let before = self.inv.inv;
+
+        if !craft_in_inventory(recipe, self.inv) {
+            return Err(TransactionRejection::CantCraft);
+        }
+        if all {
+            while craft_in_inventory(recipe, self.inv) {}
+        }
+
+        Ok((0..ROWLENGTH as usize)
+            .filter(|index| self.inv.inv[*index] != before[*index])
+            .map(SlotRef::Inv)
+            .collect())
+    }
+}
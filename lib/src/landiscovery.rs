@@ -0,0 +1,189 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::ptr::addr_of;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tracing::info;
+
+use crate::game::{CHUNKSYS, CURRSEED};
+
+//UDP port the servers listen on for discovery probes, the same on every machine of the LAN
+pub const DISCOVERY_PORT: u16 = 6999;
+
+//How long a client collects answers after sending its probe
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_millis(1500);
+
+//Every discovery packet starts with this, anything else on the port is ignored
+const DISCOVERY_MAGIC: [u8; 4] = *b"DGLD";
+
+//Players connected to this server, kept up to date by the server's connection handler
+pub static CONNECTED_PLAYERS: AtomicU32 = AtomicU32::new(0);
+
+//Servers found by the last search from the address entry screen
+pub static LAN_SERVERS: Lazy<Mutex<Vec<DiscoveredServer>>> = Lazy::new(|| Mutex::new(Vec::new()));
+pub static LAN_SEARCHING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ServerAnnouncement {
+    pub name: String,
+    pub players: u32,
+    pub seed: u32,
+    pub planet_type: u8,
+    //Game port, the one to connect to
+    pub port: u16,
+}
+
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+enum DiscoveryPacket {
+    //Nonce is echoed back, so answers to an older search are not mixed in
+    Probe(u64),
+    Answer(u64, ServerAnnouncement),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveredServer {
+    //Game address of the server, what goes in the server address box
+    pub address: SocketAddr,
+    pub announcement: ServerAnnouncement,
+}
+
+impl DiscoveredServer {
+    //A server on this machine answers both the broadcast and the localhost probe, from two different addresses
+    fn is_same_as(&self, other: &DiscoveredServer) -> bool {
+        let same_host = self.address.ip() == other.address.ip()
+            || self.address.ip().is_loopback()
+            || other.address.ip().is_loopback();
+        same_host && self.address.port() == other.address.port() && self.announcement == other.announcement
+    }
+}
+
+fn encode_packet(packet: &DiscoveryPacket) -> Vec<u8> {
+    let mut bytes = DISCOVERY_MAGIC.to_vec();
+    packet.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+fn decode_packet(bytes: &[u8]) -> Option<DiscoveryPacket> {
+    if bytes.len() < DISCOVERY_MAGIC.len() || bytes[..DISCOVERY_MAGIC.len()] != DISCOVERY_MAGIC {
+        return None;
+    }
+    borsh::from_slice(&bytes[DISCOVERY_MAGIC.len()..]).ok()
+}
+
+//What this server currently looks like to the LAN
+pub fn current_announcement(name: &str, port: u16) -> ServerAnnouncement {
+    //Don't wait on the chunk system if the world is busy loading, the planet type is only informative
+    let planet_type = unsafe { (*addr_of!(CHUNKSYS)).as_ref() }
+        .and_then(|csys| csys.try_read().map(|csys| csys.planet_type))
+        .unwrap_or(0);
+
+    ServerAnnouncement {
+        name: name.to_string(),
+        players: CONNECTED_PLAYERS.load(Ordering::Relaxed),
+        seed: unsafe { (*addr_of!(CURRSEED)).load(Ordering::Relaxed) },
+        planet_type,
+        port,
+    }
+}
+
+//Answers discovery probes on discovery_port until the process ends.
+//announce is called for every probe, so the answer always has the current player count.
+pub fn spawn_discovery_responder<F>(discovery_port: u16, announce: F) -> io::Result<JoinHandle<()>>
+where
+    F: Fn() -> ServerAnnouncement + Send + 'static,
+{
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery_port))?;
+    info!("Answering LAN discovery probes on port {}", discovery_port);
+
+    Ok(thread::spawn(move || {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) => {
+                    info!("LAN discovery responder stopped: {}", e);
+                    return;
+                }
+            };
+            if let Some(DiscoveryPacket::Probe(nonce)) = decode_packet(&buffer[..len]) {
+                let answer = encode_packet(&DiscoveryPacket::Answer(nonce, announce()));
+                let _ = socket.send_to(&answer, from);
+            }
+        }
+    }))
+}
+
+//Broadcasts a probe on the LAN (and to this machine, for servers running locally),
+//then collects the answers until timeout. Each server is listed once.
+pub fn discover_servers(discovery_port: u16, timeout: Duration) -> io::Result<Vec<DiscoveredServer>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+
+    let nonce: u64 = rand::random();
+    let probe = encode_packet(&DiscoveryPacket::Probe(nonce));
+
+    let mut sent = false;
+    for target in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+        match socket.send_to(&probe, (target, discovery_port)) {
+            Ok(_) => sent = true,
+            Err(e) => info!("Failed to send LAN discovery probe to {}: {}", target, e),
+        }
+    }
+    if !sent {
+        return Err(io::Error::other("no discovery probe could be sent"));
+    }
+
+    let mut servers: Vec<DiscoveredServer> = Vec::new();
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 1024];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        socket.set_read_timeout(Some(deadline - now))?;
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => break,
+            Err(e) => return Err(e),
+        };
+        if let Some(DiscoveryPacket::Answer(answer_nonce, announcement)) = decode_packet(&buffer[..len]) {
+            if answer_nonce != nonce {
+                continue;
+            }
+            let found = DiscoveredServer {
+                address: SocketAddr::new(from.ip(), announcement.port),
+                announcement,
+            };
+            if !servers.iter().any(|server| server.is_same_as(&found)) {
+                servers.push(found);
+            }
+        }
+    }
+
+    Ok(servers)
+}
+
+//Runs a search in the background for the address entry screen, results go to LAN_SERVERS
+pub fn start_lan_search() {
+    if LAN_SEARCHING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    thread::spawn(|| {
+        match discover_servers(DISCOVERY_PORT, DISCOVERY_TIMEOUT) {
+            Ok(servers) => {
+                *LAN_SERVERS.lock() = servers;
+            }
+            Err(e) => {
+                info!("LAN discovery failed: {}", e);
+                LAN_SERVERS.lock().clear();
+            }
+        }
+        LAN_SEARCHING.store(false, Ordering::Relaxed);
+    });
+}
@@ -34,6 +34,13 @@ pub mod audio;
 pub mod monsters;
 pub mod serializemap;
 pub mod server_types;
+pub mod capturedump;
+pub mod landiscovery;
+pub mod interest;
+pub mod chunkedits;
+pub mod blockedits;
+pub mod invtransactions;
+pub mod mobreplication;
 pub mod network;
 pub mod inventory;
 pub mod visions;
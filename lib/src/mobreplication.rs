@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+use lockfree::queue::Queue;
+use once_cell::sync::Lazy;
+
+use crate::modelentity::ModelEntity;
+use crate::server_types::{MobDelta, MobState, MOB_BATCH_SIZE};
+
+//How far back a delta may reference a batch. Past this the server sends full states again, and the client keeps this many batches to decode against
+pub const MOB_HISTORY: u32 = 64;
+
+//Mob updates decoded by the network thread, applied to non_static_model_entities by Game::update_server_received_modents
+pub static RECEIVED_MOBS: Lazy<Queue<ReceivedMob>> = Lazy::new(|| Queue::new());
+
+//What the server knows of a mob when building a batch
+#[derive(Clone, Debug)]
+pub struct MobSnapshot {
+    pub id: u32,
+    pub model_index: u8,
+    pub scale: f32,
+    pub hostile: bool,
+    pub state: MobState,
+}
+
+impl MobSnapshot {
+    pub fn of(modent: &ModelEntity) -> Self {
+        Self {
+            id: modent.id,
+            model_index: modent.model_index as u8,
+            scale: modent.scale,
+            hostile: modent.hostile,
+            state: MobState::quantize(modent.position, modent.rot.y, modent.sounding),
+        }
+    }
+
+    pub fn spawn(&self) -> MobDelta {
+        MobDelta::Spawn {
+            id: self.id,
+            model_index: self.model_index,
+            scale: self.scale,
+            hostile: self.hostile,
+            state: self.state,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReceivedMob {
+    Spawn {
+        id: u32,
+        model_index: usize,
+        scale: f32,
+        hostile: bool,
+        position: Vec3,
+        rot: f32,
+        sounding: bool,
+    },
+    Update {
+        id: u32,
+        position: Vec3,
+        rot: f32,
+        sounding: bool,
+    },
+    Despawn {
+        id: u32,
+    },
+}
+
+//Server side, one per client: what was sent, and what the client acknowledged
+#[derive(Default)]
+pub struct MobReplication {
+    next_seq: u32,
+    //Mobs of each batch not acknowledged yet, None for a despawn
+    in_flight: HashMap<u32, Vec<(u32, Option<MobState>)>>,
+    //Last state of each mob the client acknowledged, with the batch it came in
+    acked: HashMap<u32, (u32, MobState)>,
+}
+
+impl MobReplication {
+    //Deltas of every mob that changed since the client's acknowledged state, split in batches of MOB_BATCH_SIZE
+    pub fn build_batches(&mut self, mobs: &[MobSnapshot]) -> Vec<(u32, Vec<MobDelta>)> {
+        let mut deltas = Vec::new();
+        let mut sent = Vec::new();
+
+        for mob in mobs {
+            match self.acked.get(&mob.id) {
+                Some((_, base)) if *base == mob.state => continue,
+                //Half the history, batches sent later in this same tick still fall within it
+                Some((base_seq, base)) if self.next_seq.wrapping_sub(*base_seq) < MOB_HISTORY / 2 => {
+                    match mob.state.delta_from(base) {
+                        Some(dpos) => deltas.push(MobDelta::Delta {
+                            id: mob.id,
+                            base_seq: *base_seq,
+                            dpos,
+                            rot: mob.state.rot,
+                            sounding: mob.state.sounding,
+                        }),
+                        None => deltas.push(mob.spawn()),
+                    }
+                }
+                _ => deltas.push(mob.spawn()),
+            }
+            sent.push((mob.id, Some(mob.state)));
+        }
+
+        //Gone mobs the client may know of, repeated until acknowledged
+        let current: HashSet<u32> = mobs.iter().map(|mob| mob.id).collect();
+        let mut known: HashSet<u32> = self.acked.keys().copied().collect();
+        for sent_batch in self.in_flight.values() {
+            known.extend(sent_batch.iter().filter(|(_, state)| state.is_some()).map(|(id, _)| *id));
+        }
+        for id in known.difference(&current) {
+            deltas.push(MobDelta::Despawn { id: *id });
+            sent.push((*id, None));
+        }
+
+        let mut batches = Vec::new();
+        for (deltas, sent) in deltas.chunks(MOB_BATCH_SIZE).zip(sent.chunks(MOB_BATCH_SIZE)) {
+            let seq = self.next_seq;
+            self.next_seq = self.next_seq.wrapping_add(1);
+            self.in_flight.insert(seq, sent.to_vec());
+            batches.push((seq, deltas.to_vec()));
+        }
+
+        //Acks this late could only make deltas against batches the client no longer keeps
+        let next_seq = self.next_seq;
+        self.in_flight.retain(|seq, _| next_seq.wrapping_sub(*seq) < MOB_HISTORY);
+
+        batches
+    }
+
+    pub fn acknowledge(&mut self, seq: u32) {
+        let Some(sent) = self.in_flight.remove(&seq) else {
+            return;
+        };
+        for (id, state) in sent {
+            match state {
+                Some(state) => {
+                    let newer = match self.acked.get(&id) {
+                        Some((acked_seq, _)) => seq.wrapping_sub(*acked_seq) as i32 > 0,
+                        None => true,
+                    };
+                    if newer {
+                        self.acked.insert(id, (seq, state));
+                    }
+                }
+                None => {
+                    self.acked.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+//Client side: states of the last decoded batches, the bases of the server's deltas
+#[derive(Resource, Default)]
+pub struct MobBaselines {
+    batches: VecDeque<(u32, HashMap<u32, MobState>)>,
+    //Batch each mob was last updated from, so that late batches do not move mobs back
+    applied: HashMap<u32, u32>,
+}
+
+impl MobBaselines {
+    //None if a delta references a batch we no longer have, the batch is then dropped without an ack
+    pub fn decode(&mut self, seq: u32, deltas: Vec<MobDelta>) -> Option<Vec<ReceivedMob>> {
+        let mut states = HashMap::new();
+        let mut received = Vec::new();
+
+        for delta in deltas {
+            match delta {
+                MobDelta::Spawn { id, model_index, scale, hostile, state } => {
+                    states.insert(id, state);
+                    received.push(ReceivedMob::Spawn {
+                        id,
+                        model_index: model_index as usize,
+                        scale,
+                        hostile,
+                        position: state.position(),
+                        rot: state.rotation(),
+                        sounding: state.sounding,
+                    });
+                }
+                MobDelta::Delta { id, base_seq, dpos, rot, sounding } => {
+                    let base = self
+                        .batches
+                        .iter()
+                        .find(|(batch_seq, _)| *batch_seq == base_seq)
+                        .and_then(|(_, batch)| batch.get(&id))?;
+                    let state = MobState::apply_delta(base, dpos, rot, sounding);
+                    states.insert(id, state);
+                    received.push(ReceivedMob::Update {
+                        id,
+                        position: state.position(),
+                        rot: state.rotation(),
+                        sounding,
+                    });
+                }
+                MobDelta::Despawn { id } => {
+                    received.push(ReceivedMob::Despawn { id });
+                }
+            }
+        }
+
+        self.batches.push_back((seq, states));
+        if self.batches.len() > MOB_HISTORY as usize {
+            self.batches.pop_front();
+        }
+
+        received.retain(|mob| {
+            let id = match mob {
+                ReceivedMob::Spawn { id, .. } | ReceivedMob::Update { id, .. } | ReceivedMob::Despawn { id } => *id,
+            };
+            match self.applied.get(&id) {
+                //Sequence numbers wrap, a batch is older when it is behind by less than half the range
+                Some(applied_seq) if seq.wrapping_sub(*applied_seq) as i32 <= 0 => false,
+                _ => {
+                    self.applied.insert(id, seq);
+                    true
+                }
+            }
+        });
+
+        Some(received)
+    }
+}
@@ -12,6 +12,7 @@ use gltf::{image::Source, mesh::util::ReadIndices};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 use uuid::Uuid;
 // use crate::{camera::Camera, planetinfo::Planets};
+use crate::mobreplication::{ReceivedMob, RECEIVED_MOBS};
 use crate::planetinfo::Planets;
 use gltf::animation::util::ReadOutputs;
 use crate::{collisioncage::{CollCage, Side}, game::*, modelentity::{AggroTarget, ModelEntity}, vec};
@@ -244,6 +245,10 @@ impl Game {
     
 
     pub fn insert_player_model_entity(&mut self, id: Uuid, model_index: usize, pos: Vec3, scale: f32, rot: Vec3, jump_height: f32) {
+        Game::static_insert_player_model_entity(&self.player_model_entities, id, model_index, pos, scale, rot, jump_height);
+    }
+
+    pub fn static_insert_player_model_entity(pme: &Arc<DashMap<Uuid, ModelEntity>>, id: Uuid, model_index: usize, pos: Vec3, scale: f32, rot: Vec3, jump_height: f32) {
         
         let csys = unsafe { (*addr_of!(CHUNKSYS)).as_ref().unwrap() };
         let cam = unsafe { CAMERA.as_ref().unwrap() };
@@ -264,10 +269,38 @@ impl Game {
 
         modent.coll_cage = CollCage::new(solid_pred);
 
-        self.player_model_entities.insert(id, modent);
+        pme.insert(id, modent);
     }
 
     pub fn update_server_received_modents(&mut self) {
+        while let Some(mob) = RECEIVED_MOBS.pop() {
+            match mob {
+                ReceivedMob::Spawn { id, model_index, scale, hostile, position, rot, sounding } => {
+                    //The map entry must be released before inserting
+                    let known = match self.non_static_model_entities.get_mut(&id) {
+                        Some(mut modent) => {
+                            modent.scale = scale;
+                            modent.hostile = hostile;
+                            modent.set_received_state(position, rot, sounding);
+                            true
+                        }
+                        None => false,
+                    };
+                    if !known && model_index < self.animations.len() {
+                        self.insert_static_model_entity(id, model_index, position, scale, Vec3::new(0.0, rot, 0.0), 5.0, hostile);
+                    }
+                }
+                ReceivedMob::Update { id, position, rot, sounding } => {
+                    if let Some(mut modent) = self.non_static_model_entities.get_mut(&id) {
+                        modent.set_received_state(position, rot, sounding);
+                    }
+                }
+                ReceivedMob::Despawn { id } => {
+                    self.non_static_model_entities.remove(&id);
+                }
+            }
+        }
+
         let mut rng: StdRng = StdRng::from_entropy();
 
         let mut tookdamage: bool = false;
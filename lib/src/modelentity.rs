@@ -6,6 +6,7 @@ use parking_lot::{Mutex, RwLock};
 
 use dashmap::DashMap;
 use bevy::prelude::*;
+use glfw::ffi::glfwGetTime;
 use once_cell::sync::Lazy;
 use rand::{rngs::StdRng, Rng, SeedableRng};
 use uuid::Uuid;
@@ -517,4 +518,13 @@ impl ModelEntity {
         self.lastpos = self.position;
         self.position = newpos;
     }
+
+    //State replicated by the server, the model shader blends from the previous one since time_stamp
+    pub fn set_received_state(&mut self, newpos: Vec3, rot_y: f32, sounding: bool) {
+        self.set_pos(newpos);
+        self.lastrot = self.rot;
+        self.rot = Vec3::new(0.0, rot_y, 0.0);
+        self.sounding = sounding;
+        self.time_stamp = unsafe { glfwGetTime() };
+    }
 }
\ No newline at end of file
@@ -1,20 +1,33 @@
 // use std::net::{IpAddr, Ipv4Addr};
 // use std::str::FromStr;
+use std::ptr::addr_of;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::Duration;
 
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
 // use bevy::math::VectorSpace;
 use bevy::prelude::*;
 use jeffy_quintet::client::certificate::CertificateVerificationMode;
-use jeffy_quintet::shared::channels::ChannelType;
-use jeffy_quintet::{client::*, shared::channels::ChannelsConfiguration};
+use jeffy_quintet::client::*;
+use jeffy_quintet::shared::routing::RoutedMessage;
 // use jeffy_quintet::{client::*, server::*, shared::channels::ChannelsConfiguration};
 
 // use jeffy_quintet::server::certificate::CertificateRetrievalMode;
-use connection::ClientEndpointConfiguration;
-
-use crate::server_types::Message;
+use connection::{ClientEndpointConfiguration, ConnectionEvent, ConnectionFailedEvent, ConnectionLostEvent, ReconnectPolicy};
+use uuid::Uuid;
+
+use crate::chunk::CHUNK_EDIT_REQUESTS;
+use crate::chunkedits::PendingChunkEdits;
+use crate::game::{Game, BLOCK_EDIT_QUEUE, CHEST_REGISTRY, CHUNKSYS, INVENTORY, INVENTORY_INTENT_QUEUE, MOUSE_SLOT_QUEUE, PLAYERPOS, PLAYERSCALE, PLAYER_MODEL_ENTITIES, RECEIVED_WORLD, ROWLENGTH, TIMEOFDAY, UPDATE_THE_BLOCK_OVERLAY};
+use crate::inventory::ChestInventory;
+use crate::modelentity::direction_to_euler;
+use crate::mobreplication::{MobBaselines, RECEIVED_MOBS};
+use crate::server_types::{hello, Message, MobDelta, SerIVec2, SerIVec3, SerVec3};
+use crate::statics::MY_MULTIPLAYER_UUID;
+use crate::vec;
 //use crate::{add_player_to_scene, ChildJId, JId, JMoveState, JMyCollider, JMyId, JMyPlayer, JOtherPlayers};
 
 
@@ -51,25 +64,36 @@ impl InterpolationThing {
 #[derive(Resource, Default)]
 pub struct PlayerUpdateTimer(pub Timer);
 
-// pub fn send_my_location(
-//     myid: Res<JMyId>,
-//     mut client: ResMut<QuintetClient>,
-//     time: Res<Time>,
-//     mut timer: ResMut<PlayerUpdateTimer>,
-//     query: Query<&Transform, With<JMyCollider>>,
-//     query2: Query<&JMoveState, With<JMyPlayer>>,
-// ) {
-//     if timer.0.tick(time.delta()).just_finished() {
-//         let me = query.single();
-//         for mov in query2.iter() {
-//             (*client).connection_mut().send_message_on(
-//                 0,
-//                 Message::PlayerUpdate(myid.uuid, me.translation, me.rotation, me.scale, mov.moving),
-//             );
-//         }
-        
-//     }
-// }
+#[derive(Component)]
+pub struct RemotePlayer {
+    pub uuid: Uuid
+}
+
+pub fn send_my_location(
+    mut client: ResMut<QuintetClient>,
+    time: Res<Time>,
+    mut timer: ResMut<PlayerUpdateTimer>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        if !client.is_connected() || !RECEIVED_WORLD.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let c = unsafe {
+            PLAYERPOS.snapshot()
+        };
+        let dir = direction_to_euler(c.dir.into());
+        let myid = unsafe { (*MY_MULTIPLAYER_UUID).as_u64_pair() };
+
+        client.connection_mut().try_send_typed(
+            Message::PlayerUpdate(
+                myid,
+                SerVec3(Into::<Vec3>::into(c.pos) - Vec3::new(0.0, 1.25, 0.0)),
+                SerVec3(Vec3::new(c.pitch, c.yaw, dir.y)),
+            ),
+        );
+    }
+}
 pub static mut THEENTEREDADDRESS: String = String::new();
 pub static mut ADDRESSENTERED: AtomicBool = AtomicBool::new(false);
 
@@ -86,126 +110,316 @@ pub fn start_connection(mut client: ResMut<QuintetClient>) {
         ClientEndpointConfiguration::from_strings(
                              &address,
                              "0.0.0.0:0"
-                         ).unwrap(),
+                         ).unwrap()
+                         .with_join_request(hello(unsafe { *MY_MULTIPLAYER_UUID }))
+                         //Rides out network drops, but a kick or a server shutdown is final
+                         .with_reconnect_policy(ReconnectPolicy::default()),
         CertificateVerificationMode::SkipVerification,
-        ChannelsConfiguration::from_types(vec![
-            ChannelType::Unreliable,
-            ChannelType::Unreliable,
-            ChannelType::OrderedReliable,
-            ChannelType::OrderedReliable,
-        ])
-        .unwrap(),
+        Message::channels_configuration(),
     );
 }
 
-// pub fn update_otherplayers_interps(
-//     mut otherplayerbodies: Query<(&mut Transform, &JId, &mut InterpolationThing)>,
-//     time: Res<Time>
-// ) {
-//     for (mut transform, id, mut interp) in otherplayerbodies.iter_mut() {
-//         interp.t = (interp.t + time.delta_seconds() * 4.0).min(1.0);
-//         //println!("INterep: {}", interp.t);
-//         (*transform).translation = interp.lastpos.translation.lerp(interp.newpos.translation, interp.t);
-//         (*transform).rotation = interp.lastpos.rotation.slerp(interp.newpos.rotation, interp.t);
-//         (*transform).scale = Vec3::ONE;
- 
-//     }
-// }
+//Why the server refused us (incompatible version...), shown on the loading screen
+pub static CONNECTION_REFUSED: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 
-pub fn handle_server_messages(
-    _commands: Commands,
-    _asset_server: Res<AssetServer>,
-    mut client: ResMut<QuintetClient>,
-    //mut opl: ResMut<JOtherPlayers>,
-    //mut otherplayerbodies: Query<(&mut Transform, &JId, &mut InterpolationThing)>, /*...*/
-    //mut animstates: Query<(&mut JMoveState, &ChildJId)>
-) {
-    //println!("Checking for server messages...");
-    while let Ok(Some(message)) = client.connection_mut().receive_message::<Message>() {
-       // println!("Finally receiing");
+//Why we got disconnected once in (kicked, server stopped, timed out...), shown over the game
+pub static CONNECTION_LOST: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 
-        match message {
-            _ => {
-                    
-            }
-            // (channelid, Message::Disconnect) => {
-
-            // },
-            // (channelid, Message::ChestUpdate(chestloc, slotindex, slot)) => {
+pub fn handle_connection_failures(
+    mut connection_failed_events: EventReader<ConnectionFailedEvent>,
+    mut connection_lost_events: EventReader<ConnectionLostEvent>,
+) {
+    for event in connection_failed_events.read() {
+        println!("Could not join the server: {}", event.err);
+        *CONNECTION_REFUSED.lock() = Some(event.err.to_string());
+    }
+    for event in connection_lost_events.read() {
+        let reason = match &event.reason {
+            Some(reason) => format!("Disconnected from the server: {}", reason),
+            None => "Lost the connection to the server".to_string(),
+        };
+        println!("{}", reason);
+        *CONNECTION_LOST.lock() = Some(reason);
+    }
+}
 
-            // },
-            // (channelid, Message::BlockSet(servec3, blockid)) => {
+pub fn greet_server(
+    mut connection_events: EventReader<ConnectionEvent>,
+    mut client: ResMut<QuintetClient>,
+) {
+    for _ in connection_events.read() {
+        *CONNECTION_LOST.lock() = None;
+        let myid = unsafe { (*MY_MULTIPLAYER_UUID).as_u64_pair() };
+        let connection = client.connection_mut();
+        connection.try_send_typed(Message::TellYouMyID(myid.0, myid.1));
+        connection.try_send_typed(Message::RequestWorldInfo);
+    }
+}
 
-            // },
-            // (channelid, Message::InvUpdate(slotindex, slot)) => {
+pub fn update_otherplayers_interps(
+    mut otherplayerbodies: Query<(&RemotePlayer, &mut InterpolationThing)>,
+    time: Res<Time>
+) {
+    let pme = match unsafe { (*addr_of!(PLAYER_MODEL_ENTITIES)).as_ref() } {
+        Some(pme) => pme,
+        None => return,
+    };
+
+    for (player, mut interp) in otherplayerbodies.iter_mut() {
+        interp.t = (interp.t + time.delta_seconds() * 4.0).min(1.0);
+
+        let translation = interp.lastpos.translation.lerp(interp.newpos.translation, interp.t);
+        let rotation = interp.lastpos.rotation.slerp(interp.newpos.rotation, interp.t);
+
+        if let Some(mut modent) = pme.get_mut(&player.uuid) {
+            //Interpolated here, so keep the model shader's own lastpos blend out of it
+            modent.lastpos = translation;
+            modent.position = translation;
+            modent.lastrot = modent.rot;
+            modent.rot = Vec3::new(0.0, rotation.to_euler(EulerRot::YXZ).0, 0.0);
+        }
+    }
+}
 
-            // },
-            // (channelid, Message::ItemToYourMouse(slot)) => {
+//Chests and user edits arrive later, chunk by chunk, and the inventory comes in InvUpdates
+fn apply_world_info(pt: u8, seed: u32) {
+    let csys = unsafe { (*addr_of!(CHUNKSYS)).as_ref() };
+    let chestreg = unsafe { (*addr_of!(CHEST_REGISTRY)).as_ref() };
 
-            // },
-            // (channelid, Message::MobUpdate) => {
+    if let Some(csys) = csys {
+        csys.write().start_streamed_world(seed, pt);
+    }
 
-            // },
-            // (channelid, Message::MobUpdateBatch) => {
+    if let Some(chestreg) = chestreg {
+        chestreg.clear();
+    }
 
-            // },
-            // (channelid, Message::MultiBlockSet(blocks)) => {
+    RECEIVED_WORLD.store(true, Ordering::Relaxed);
+}
 
-            // },
-            // (channelid, Message::RequestWorldInfo) => {
+//Asks the server for the edits of the chunks the chunk system took care of since last time
+pub fn request_chunk_edits(
+    mut client: ResMut<QuintetClient>,
+    mut pending_chunk_edits: ResMut<PendingChunkEdits>,
+) {
+    if !client.is_connected() {
+        return;
+    }
 
-            // },
-            // (channelid, Message::TellYouMyID(id1, id2)) => {
+    while let Some(chunk) = CHUNK_EDIT_REQUESTS.pop() {
+        pending_chunk_edits.request(chunk);
+        client
+            .connection_mut()
+            .try_send_typed(Message::RequestChunkEdits(SerIVec2(IVec2::new(chunk.x, chunk.y))));
+    }
+}
 
-            // },
-            // (channelid, Message::YourId(id1, id2)) => {
+//Sends the edits Game already made on our side
+pub fn send_block_edits(mut client: ResMut<QuintetClient>) {
+    if !client.is_connected() {
+        return;
+    }
 
-            // },
-            // (channelid, Message::TimeUpdate(newtime)) => {
+    while let Some(mut edits) = BLOCK_EDIT_QUEUE.pop() {
+        let message = match edits.len() {
+            1 => {
+                let (spot, block) = edits.pop().unwrap();
+                Message::BlockSet(SerIVec3(IVec3::new(spot.x, spot.y, spot.z)), block)
+            }
+            _ => Message::MultiBlockSet(
+                edits
+                    .into_iter()
+                    .map(|(spot, block)| (SerIVec3(IVec3::new(spot.x, spot.y, spot.z)), block))
+                    .collect(),
+            ),
+        };
+        client.connection_mut().try_send_typed(message);
+    }
+}
 
-            // },
-            // (channelid, Message::WorldInfo(chestregbytes, pt, udmbytes, seed)) => {
-            //     //ChestReg, Pt, Udm, Seed
-            // },
-            // (channelid, Message::PlayerUpdate(uuid, pos, rot)) => {
+//Our slots change when the server answers with them, not before
+pub fn send_inventory_intents(mut client: ResMut<QuintetClient>) {
+    if !client.is_connected() {
+        return;
+    }
 
-            // }
+    while let Some(intent) = INVENTORY_INTENT_QUEUE.pop() {
+        client.connection_mut().try_send_typed(Message::InventoryIntent(intent));
+    }
+}
 
+fn remote_player_transform(pos: SerVec3, rot: SerVec3) -> Transform {
+    Transform {
+        translation: pos.0,
+        rotation: Quat::from_rotation_y(rot.0.z),
+        scale: Vec3::ONE,
+    }
+}
 
+//Decoded mobs go to Game::update_server_received_modents, and the batch is acknowledged so later deltas build on it
+fn receive_mobs(client: &mut QuintetClient, mob_baselines: &mut MobBaselines, seq: u32, deltas: Vec<MobDelta>) {
+    if let Some(mobs) = mob_baselines.decode(seq, deltas) {
+        for mob in mobs {
+            RECEIVED_MOBS.push(mob);
+        }
+        client.connection_mut().try_send_typed(Message::MobAck(seq));
+    }
+}
 
-            // Match on your own message types ...
-            //(channelid, Message::PlayerUpdate(uuid, trans, rot, scale, mov)) => {
-               // println!("Got on {}: {}", channelid, uuid);
+pub fn handle_server_messages(
+    mut commands: Commands,
+    mut client: ResMut<QuintetClient>,
+    mut otherplayerbodies: Query<(Entity, &RemotePlayer, &mut InterpolationThing)>,
+    mut mob_baselines: ResMut<MobBaselines>,
+    mut pending_chunk_edits: ResMut<PendingChunkEdits>,
+) {
+    let csys = unsafe { (*addr_of!(CHUNKSYS)).as_ref() };
+    let chestreg = unsafe { (*addr_of!(CHEST_REGISTRY)).as_ref() };
+    let inv = unsafe { (*addr_of!(INVENTORY)).as_ref() };
+    let tod = unsafe { (*addr_of!(TIMEOFDAY)).as_ref() };
+    let pme = unsafe { (*addr_of!(PLAYER_MODEL_ENTITIES)).as_ref() };
 
-                // if !opl.list.contains(&uuid) {
-                //     opl.list.insert(uuid);
+    let (csys, chestreg, inv, tod, pme) = match (csys, chestreg, inv, tod, pme) {
+        (Some(csys), Some(chestreg), Some(inv), Some(tod), Some(pme)) => (csys, chestreg, inv, tod, pme),
+        _ => return,
+    };
 
-                //     add_player_to_scene(&mut commands, &asset_server, trans, uuid);
-                // } else {
-                //     for (mut transform, id, mut interp) in otherplayerbodies.iter_mut() {
-                //         let newtrans = Transform {
-                //             translation: trans,
-                //             rotation: rot,
-                //             scale,
-                //         };
+    while let Ok(Some(message)) = client.connection_mut().receive_message::<Message>() {
+        match message {
+            (_, Message::WorldInfo(pt, seed)) => {
+                apply_world_info(pt, seed);
+            },
+            (_, Message::ChunkEdits(chunk, blocks, chests)) => {
+                let chunk = vec::IVec2::new(chunk.0.x, chunk.0.y);
+                if let Some(blocks) = pending_chunk_edits.receive(chunk, blocks) {
+                    for (spot, chest) in chests {
+                        chestreg.insert(
+                            vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z),
+                            ChestInventory { dirty: false, inv: chest },
+                        );
+                    }
+
+                    if !blocks.is_empty() {
+                        let csys = csys.read();
+                        for (spot, block) in blocks {
+                            csys.set_block_no_sound(spot, block, true);
+                        }
+                        //Built from generated terrain when it came in, lit again for the torches among the edits
+                        csys.queue_rerender_with_key(chunk, true, true);
+                    }
+                }
+            },
+            (_, Message::BlockSet(spot, block)) => {
+                let spot = vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z);
+                pending_chunk_edits.block_set(spot);
+                csys.read().set_block_and_queue_rerender(spot, block, block == 0, true, false);
+                unsafe {
+                    UPDATE_THE_BLOCK_OVERLAY = true;
+                }
+            },
+            (_, Message::MultiBlockSet(blocks)) => {
+                let csys = csys.read();
+                for (spot, block) in blocks {
+                    let spot = vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z);
+                    pending_chunk_edits.block_set(spot);
+                    csys.set_block_and_queue_rerender(spot, block, true, true, false);
+                }
+                unsafe {
+                    UPDATE_THE_BLOCK_OVERLAY = true;
+                }
+            },
+            (_, Message::BlockCorrection(blocks)) => {
+                //The server refused edits we already made, back to what it has
+                let csys = csys.read();
+                for (spot, block) in blocks {
+                    let spot = vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z);
+                    pending_chunk_edits.block_set(spot);
+                    csys.set_block_and_queue_rerender(spot, block, true, true, false);
+                }
+                unsafe {
+                    UPDATE_THE_BLOCK_OVERLAY = true;
+                }
+            },
+            (_, Message::ChestUpdate(chestloc, slotindex, slot)) => {
+                if slotindex < ROWLENGTH as usize * 4 {
+                    let currchest = vec::IVec3::new(chestloc.0.x, chestloc.0.y, chestloc.0.z);
+                    let mut chestinv = chestreg.entry(currchest).or_insert(ChestInventory {
+                        dirty: false,
+                        inv: [(0, 0); ROWLENGTH as usize * 4],
+                    });
+                    chestinv.inv[slotindex] = slot;
+                    drop(chestinv);
+
+                    //Marks the hud for a refresh, which redraws the open chest as well
+                    inv.write().dirty = true;
+                }
+            },
+            (_, Message::InvUpdate(slotindex, slot)) => {
+                if slotindex < ROWLENGTH as usize {
+                    let mut invlock = inv.write();
+                    invlock.inv[slotindex] = slot;
+                    invlock.dirty = true;
+                }
+            },
+            (_, Message::ItemToYourMouse(slot)) => {
+                MOUSE_SLOT_QUEUE.push(slot);
+            },
+            (_, Message::TimeUpdate(newtime)) => {
+                *tod.lock() = newtime;
+            },
+            (_, Message::MobUpdate(seq, delta)) => {
+                receive_mobs(&mut client, &mut mob_baselines, seq, vec![delta]);
+            },
+            (_, Message::MobUpdateBatch(seq, deltas)) => {
+                receive_mobs(&mut client, &mut mob_baselines, seq, deltas);
+            },
+            (_, Message::PlayerLeftView(id)) => {
+                let uuid = Uuid::from_u64_pair(id.0, id.1);
+                pme.remove(&uuid);
+                for (entity, player, _) in otherplayerbodies.iter() {
+                    if player.uuid == uuid {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            },
+            (_, Message::PlayerUpdate(id, pos, rot)) => {
+                //Only PlayerEnteredView creates players, so a late update can not bring back one out of view
+                let uuid = Uuid::from_u64_pair(id.0, id.1);
+                if pme.contains_key(&uuid) {
+                    for (_, player, mut interp) in otherplayerbodies.iter_mut() {
+                        if player.uuid == uuid {
+                            interp.update(remote_player_transform(pos, rot));
+                        }
+                    }
+                }
+            },
+            (_, Message::PlayerEnteredView(id, pos, rot)) => {
+                let uuid = Uuid::from_u64_pair(id.0, id.1);
+                let newtrans = remote_player_transform(pos, rot);
+
+                if pme.contains_key(&uuid) {
+                    for (_, player, mut interp) in otherplayerbodies.iter_mut() {
+                        if player.uuid == uuid {
+                            interp.update(newtrans);
+                        }
+                    }
+                } else {
+                    info!("Player {} came into view. Creating it...", uuid);
+                    Game::static_insert_player_model_entity(
+                        pme,
+                        uuid,
+                        0, //0 for player
+                        pos.0,
+                        PLAYERSCALE,
+                        Vec3::new(0.0, rot.0.z, 0.0),
+                        5.0,
+                    );
+                    commands.spawn((RemotePlayer { uuid }, InterpolationThing::new(newtrans)));
+                }
+            },
+            _ => {
 
-                        
-                        
-                        
-                //         if id.uuid == uuid {
-                //             (*interp).update(newtrans);
-                //             //(*transform) = newtrans; was causing glitch movement
-                //         }
-                //     }
-                //     for (mut movestate, jid) in animstates.iter_mut() {
-                //         if jid.uuid == uuid {
-                //             movestate.moving = mov;
-                //         }
-                //     }
-                // }
-            //} //ServerMessage::ClientConnected { client_id, username} => {/*...*/}
-              //ServerMessage::ClientDisconnected { client_id } => {/*...*/}
-              //ServerMessage::ChatMessage { client_id, message } => {/*...*/}
+            }
         }
     }
 }
@@ -1,126 +1,787 @@
+use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
+use std::ptr::addr_of;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
 
 use bevy::prelude::*;
+use crossbeam::queue::SegQueue;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+use uuid::Uuid;
 // use jeffy_quintet::client::certificate::CertificateVerificationMode;
-use jeffy_quintet::shared::channels::ChannelType;
+use jeffy_quintet::shared::routing::RoutedMessage;
 // use jeffy_quintet::{client::*, server::*, shared::channels::ChannelsConfiguration};
-use jeffy_quintet::{server::*, shared::channels::ChannelsConfiguration};
+use jeffy_quintet::server::*;
+use jeffy_quintet::shared::{ClientId, DisconnectReason};
 
 use jeffy_quintet::server::certificate::CertificateRetrievalMode;
 // use connection::ClientEndpointConfiguration;
-// use uuid::Uuid;
 
+use crate::blockedits::{check_block_change, check_block_edit, is_crystal_cluster_break};
+use crate::chunk::{ChunkSystem, USERDATAMAP};
+use crate::chunkedits::ChunkEditCache;
+use crate::game::{CHEST_REGISTRY, CHUNKSYS, CURRSEED, KNOWN_CAMERAS, NON_STATIC_MODEL_ENTITIES, ROWLENGTH, STARTINGITEMS, TIMEOFDAY};
+use crate::inventory::{ChestInventory, Inventory};
+use crate::interest::ClientInterest;
+use crate::invtransactions::{can_use_chest, can_use_crafting_table, named_slots, PlayerSlots, TransactionRejection};
+use crate::landiscovery::CONNECTED_PLAYERS;
+use crate::mobreplication::{MobReplication, MobSnapshot};
+use crate::playerposition::{PlayerPosition, PlayerVec};
+use crate::recipes::RECIPES;
 use crate::server_types::*;
+use crate::vec;
 
-pub fn start_listening(mut server: ResMut<QuintetServer>) {
-    server
-        .start_endpoint(
-            ServerEndpointConfiguration::from_ip(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 6000),
-            CertificateRetrievalMode::GenerateSelfSigned {
-                server_hostname: String::from("Test Server"),
+pub static SERVERPORT: AtomicU16 = AtomicU16::new(6000);
+
+//Set by the dedicated server to open the endpoint on SERVERPORT, start_listening answers in LISTEN_RESULT
+pub static LISTEN_REQUESTED: AtomicBool = AtomicBool::new(false);
+pub static LISTEN_RESULT: Lazy<Mutex<Option<Result<(), String>>>> = Lazy::new(|| Mutex::new(None));
+
+//Set once the dedicated server has loaded its world, client messages wait in their queues until then
+pub static SERVER_WORLD_READY: AtomicBool = AtomicBool::new(false);
+
+pub static QUEUED_SQL: Lazy<SegQueue<QueuedSqlType>> = Lazy::new(|| SegQueue::new());
+
+//Inventories of the players that left, newer than the database until QUEUED_SQL catches up. Each leave gets its own
+//generation, so the LeftInventorySaved of an earlier leave does not drop the entry of a later one
+static LEFT_INVENTORIES: Lazy<DashMap<Uuid, (u64, [(u32, u32); ROWLENGTH as usize])>> = Lazy::new(|| DashMap::new());
+static LEFT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub enum QueuedSqlType {
+    UserDataMap(u32, vec::IVec3, u32),
+    ChestInventoryUpdate(vec::IVec3, [(u32, u32); ROWLENGTH as usize * 4], u32),
+    InventoryInventoryUpdate(Uuid, [(u32, u32); ROWLENGTH as usize]),
+    PlayerPositionUpdate(Uuid, Vec3, f32, f32),
+    //Queued after the last inventory write of a player that left: the database has its inventory once this comes up
+    LeftInventorySaved(Uuid, u64),
+    None
+}
+
+pub struct ServerClient {
+    //The player id of its join request
+    pub player: Uuid,
+    //Set once it asked for its inventory, with TellYouMyID
+    pub uuid: Option<Uuid>,
+    pub inv: Inventory,
+    pub saveposcounter: i32,
+    pub mobs: MobReplication,
+    pub interest: ClientInterest,
+    //Last PlayerUpdate of this client, to introduce it to players it comes in view of
+    pub last_update: Option<((u64, u64), SerVec3, SerVec3)>,
+    //What it holds in its mouse in the inventory screen
+    pub mouse: (u32, u32),
+    //Crafting table it last opened, for the recipes that need one
+    pub crafting_table: Option<vec::IVec3>,
+}
+
+impl ServerClient {
+    pub fn new(player: Uuid) -> Self {
+        Self {
+            player,
+            uuid: None,
+            inv: Inventory {
+                dirty: false,
+                inv: STARTINGITEMS.clone(),
             },
-            ChannelsConfiguration::from_types(vec![
-                ChannelType::Unreliable,
-                ChannelType::Unreliable,
-                ChannelType::OrderedReliable,
-                ChannelType::OrderedReliable,
+            saveposcounter: 0,
+            mobs: MobReplication::default(),
+            interest: ClientInterest::default(),
+            last_update: None,
+            mouse: (0, 0),
+            crafting_table: None,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ServerClients(pub HashMap<ClientId, ServerClient>);
+
+//A busy database is retried for a while, then the write is given up on rather than taking the server down
+pub fn handlesql(sql: &QueuedSqlType) {
+    for _ in 0..30 {
+        match run_sql(sql) {
+            Ok(()) => return,
+            Err(e) => {
+                println!("Sqlite failure, retrying.. ({})", e);
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+    println!("Retried an operation more than 30 times, dropping it.");
+}
+
+fn run_sql(sql: &QueuedSqlType) -> rusqlite::Result<()> {
+    let written = match sql {
+        QueuedSqlType::UserDataMap(seed, spot, block) => {
+
+            let table_name = format!("userdatamap_{}", seed);
+
+            let conn = Connection::open("db")?;
+
+            // Ensure the table exists
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        x INTEGER,
+                        y INTEGER,
+                        z INTEGER,
+                        value INTEGER,
+                        PRIMARY KEY (x, y, z)
+                    )",
+                    table_name
+                ),
+                (),
+            )?;
+
+            // Insert userdatamap entries
+            let mut stmt = conn.prepare(&format!(
+                "INSERT OR REPLACE INTO {} (x, y, z, value) VALUES (?, ?, ?, ?)",
+                table_name
+            ))?;
+
+            stmt.execute(params![spot.x, spot.y, spot.z, block])
+        },
+        QueuedSqlType::ChestInventoryUpdate(key, inv, seed) => {
+
+            let table_name = format!("chest_registry_{}", seed);
+
+            let conn = Connection::open("chestdb")?;
+
+            // Ensure the table exists
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        x INTEGER,
+                        y INTEGER,
+                        z INTEGER,
+                        dirty BOOLEAN,
+                        inventory BLOB,
+                        PRIMARY KEY (x, y, z)
+                    )",
+                    table_name
+                ),
+                (),
+            )?;
+
+            let inv_bin = bincode::serialize(&inv).unwrap();
+
+            // Update the specific entry in the database
+            let mut stmt = conn.prepare(&format!(
+                "INSERT OR REPLACE INTO {} (x, y, z, dirty, inventory) VALUES (?, ?, ?, ?, ?)",
+                table_name
+            ))?;
+
+            stmt.execute(params![
+                key.x,
+                key.y,
+                key.z,
+                false,
+                inv_bin
             ])
-            .unwrap(),
-        )
-        .unwrap();
+        },
+        QueuedSqlType::InventoryInventoryUpdate(key, inv) => {
+
+            let table_name = "invs";
+
+            let conn = Connection::open("chestdb")?;
+
+            // Ensure the table exists
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        id TEXT PRIMARY KEY,
+                        inventory BLOB
+                    )",
+                    table_name
+                ),
+                (),
+            )?;
+
+            let inv_bin = bincode::serialize(&inv).unwrap();
+
+            // Insert or update the inventory data
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} (id, inventory) VALUES (?1, ?2)
+                    ON CONFLICT(id) DO UPDATE SET inventory = excluded.inventory",
+                    table_name
+                ),
+                (key.to_string(), inv_bin),
+            )
+        },
+        QueuedSqlType::PlayerPositionUpdate(key, pos, pitch, yaw) => {
+
+            let playerposition = PlayerPosition{pitch: *pitch, yaw: *yaw, pos: PlayerVec{x: pos.x, y: pos.y, z: pos.z}};
+
+            let table_name = "poses";
+
+            let conn = Connection::open("chestdb")?;
+
+            // Ensure the table exists
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        id TEXT PRIMARY KEY,
+                        playerposition BLOB
+                    )",
+                    table_name
+                ),
+                (),
+            )?;
+
+            let inv_bin = bincode::serialize(&playerposition).unwrap();
+
+            // Insert or update the inventory data
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} (id, playerposition) VALUES (?1, ?2)
+                    ON CONFLICT(id) DO UPDATE SET playerposition = excluded.playerposition",
+                    table_name
+                ),
+                (key.to_string(), inv_bin),
+            )
+        },
+        QueuedSqlType::LeftInventorySaved(uuid, generation) => {
+            LEFT_INVENTORIES.remove_if(uuid, |_, (left_generation, _)| left_generation == generation);
+            Ok(0)
+        },
+        QueuedSqlType::None => {
+            Ok(0)
+        },
+    };
+    written.map(|_| ())
+}
+
+pub fn load_client_inventory(uuid: Uuid) -> [(u32, u32); ROWLENGTH as usize] {
+    let mut previously_loaded_inv = STARTINGITEMS.clone();
+
+    let table_name = "invs";
+
+    let conn = Connection::open("chestdb").unwrap();
+
+    conn.execute(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id TEXT PRIMARY KEY,
+            inventory BLOB
+        )",
+        table_name
+    ), ()).unwrap();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT inventory FROM {} WHERE id = ?1",
+        table_name
+    )).unwrap();
+
+    let mut rows = stmt.query([uuid.to_string()]).unwrap();
+
+    if let Some(row) = rows.next().unwrap() {
+        let inventory: Vec<u8> = row.get(0).unwrap();
+
+        match bincode::deserialize::<[(u32, u32); ROWLENGTH as usize]>(&inventory) {
+            Ok(inv) => {
+                previously_loaded_inv = inv;
+            }
+            Err(_e) => {
+                println!("Couldn't de-serialize inventory blob");
+            }
+        }
+    }
+
+    previously_loaded_inv
+}
+
+//Opens the endpoint once the dedicated server asks for it, a taken port is reported back instead of panicking
+pub fn start_listening(mut server: ResMut<QuintetServer>) {
+    if !LISTEN_REQUESTED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let started = server.start_endpoint(
+        ServerEndpointConfiguration::from_ip(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), SERVERPORT.load(Ordering::Relaxed)),
+        CertificateRetrievalMode::GenerateSelfSigned {
+            server_hostname: String::from("Test Server"),
+        },
+        Message::channels_configuration(),
+    );
+    if started.is_ok() {
+        server
+            .endpoint_mut()
+            .set_admission_validator(|hello, _| check_hello(hello));
+    }
+    *LISTEN_RESULT.lock() = Some(started.map(|_| ()).map_err(|e| e.to_string()));
+}
+
+pub fn handle_client_connections(
+    mut connection_events: EventReader<ConnectionEvent>,
+    mut connection_lost_events: EventReader<ConnectionLostEvent>,
+    mut server: ResMut<QuintetServer>,
+    mut clients: ResMut<ServerClients>,
+) {
+    for event in connection_events.read() {
+        println!("New connection: {}", event.id);
+        let Some(join_request) = server.endpoint().join_request(event.id) else {
+            continue;
+        };
+        let player = Uuid::from_u128(join_request.player_id);
+        clients.0.insert(event.id, ServerClient::new(player));
+    }
+    for event in connection_lost_events.read() {
+        remove_client(server.endpoint_mut(), &mut clients, event.id);
+    }
+    CONNECTED_PLAYERS.store(clients.0.len() as u32, Ordering::Relaxed);
+}
+
+fn remove_client(endpoint: &mut Endpoint, clients: &mut ServerClients, client_id: ClientId) {
+    if let Some(mut client) = clients.0.remove(&client_id) {
+        println!("Removed {}", client_id);
+        //The clients that saw it are told it is gone
+        if let Some((id, _, _)) = client.last_update {
+            for (other_id, other) in clients.0.iter_mut() {
+                if other.interest.visible_players.remove(&id) {
+                    endpoint.try_send_typed(*other_id, Message::PlayerLeftView(id));
+                }
+            }
+        }
+        if let Some(uuid) = client.uuid {
+            if let Some(kc) = unsafe { (*addr_of!(KNOWN_CAMERAS)).as_ref() } {
+                kc.remove(&uuid);
+            }
+            //What it held in its mouse goes back in its inventory, if there is room
+            if client.mouse.0 != 0 && client.inv.add(client.mouse.0, client.mouse.1).is_some() {
+                QUEUED_SQL.push(QueuedSqlType::InventoryInventoryUpdate(uuid, client.inv.inv));
+            }
+            let generation = LEFT_GENERATION.fetch_add(1, Ordering::Relaxed);
+            LEFT_INVENTORIES.insert(uuid, (generation, client.inv.inv));
+            QUEUED_SQL.push(QueuedSqlType::LeftInventorySaved(uuid, generation));
+        }
+    }
+}
+
+fn clients_seeing(clients: &ServerClients, candidates: &[ClientId], spot: &vec::IVec3) -> Vec<ClientId> {
+    candidates
+        .iter()
+        .copied()
+        .filter(|id| clients.0.get(id).map_or(false, |client| client.interest.sees_spot(spot)))
+        .collect()
+}
+
+//Forwards a player's movement to the clients that see it, and when it changes chunk, updates which players it sees in turn
+fn move_player(endpoint: &mut Endpoint, clients: &mut ServerClients, client_id: ClientId, id: (u64, u64), pos: SerVec3, rot: SerVec3) {
+    //Out of the map while the others are visited
+    let Some(mut mover) = clients.0.remove(&client_id) else {
+        return;
+    };
+    mover.last_update = Some((id, pos, rot));
+    let changed_chunk = mover.interest.update_center(pos.0);
+
+    for (other_id, other) in clients.0.iter_mut() {
+        if other.interest.sees(pos.0) {
+            if other.interest.visible_players.insert(id) {
+                endpoint.try_send_typed(*other_id, Message::PlayerEnteredView(id, pos, rot));
+            } else {
+                let _ = endpoint.send_typed(*other_id, Message::PlayerUpdate(id, pos, rot));
+            }
+        } else if other.interest.visible_players.remove(&id) {
+            endpoint.try_send_typed(*other_id, Message::PlayerLeftView(id));
+        }
+
+        if !changed_chunk {
+            continue;
+        }
+        if let Some((other_player, other_pos, other_rot)) = other.last_update {
+            if mover.interest.sees(other_pos.0) {
+                if mover.interest.visible_players.insert(other_player) {
+                    endpoint.try_send_typed(client_id, Message::PlayerEnteredView(other_player, other_pos, other_rot));
+                }
+            } else if mover.interest.visible_players.remove(&other_player) {
+                endpoint.try_send_typed(client_id, Message::PlayerLeftView(other_player));
+            }
+        }
+    }
+
+    clients.0.insert(client_id, mover);
+}
+
+//Validates a client's edits together, and applies them if they all pass. Otherwise the client is told what those blocks
+//really are, and gets its inventory again, to undo what it predicted
+fn apply_block_edits(
+    endpoint: &mut Endpoint,
+    client_id: ClientId,
+    client: &mut ServerClient,
+    csys: &ChunkSystem,
+    edit_cache: &mut ChunkEditCache,
+    edits: &[(SerIVec3, u32)],
+) -> bool {
+    let position = client.last_update.map(|(_, pos, _)| pos.0);
+    let mut inv = client.inv.clone();
+    let mut changed_slots = Vec::new();
+
+    let checked: Vec<(vec::IVec3, u32, u32)> = edits
+        .iter()
+        .map(|(spot, block)| {
+            let vspot = vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z);
+            (vspot, csys.blockat(vspot), *block)
+        })
+        .collect();
+    let cluster = is_crystal_cluster_break(&checked);
+
+    for (index, (vspot, old, block)) in checked.iter().enumerate() {
+        //Past the crystal clicked, a cluster may reach further than the player
+        let result = if cluster && index > 0 {
+            check_block_change(*old, *block, &inv)
+        } else {
+            check_block_edit(position, vspot, *old, *block, &inv)
+        };
+        match result {
+            Ok(outcome) => {
+                if let Some(id) = outcome.placed {
+                    changed_slots.extend(inv.take_one(id));
+                }
+                if let Some(id) = outcome.broke {
+                    changed_slots.extend(inv.add(id, 1));
+                }
+            }
+            Err(rejection) => {
+                println!("Refused block edit from {} at {:?}: {:?}", client_id, vspot, rejection);
+                let corrections = edits
+                    .iter()
+                    .map(|(spot, _)| (*spot, csys.blockat(vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z))))
+                    .collect();
+                endpoint.try_send_typed(client_id, Message::BlockCorrection(corrections));
+                for (slotindex, slot) in client.inv.inv.iter().enumerate() {
+                    endpoint.try_send_typed(client_id, Message::InvUpdate(slotindex, *slot));
+                }
+                return false;
+            }
+        }
+    }
+
+    let currseed = unsafe { CURRSEED.load(Ordering::Relaxed) };
+    for (spot, block) in edits {
+        let vspot = vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z);
+        csys.set_block(vspot, *block, true);
+        edit_cache.record_block(vspot, *block);
+        QUEUED_SQL.push(QueuedSqlType::UserDataMap(currseed, vspot, *block));
+    }
+
+    //Dropped blocks and used up items are counted here, the client hears of it through InvUpdates
+    if !changed_slots.is_empty() {
+        client.inv = inv;
+        changed_slots.sort();
+        changed_slots.dedup();
+        for slotindex in changed_slots {
+            endpoint.try_send_typed(client_id, Message::InvUpdate(slotindex, client.inv.inv[slotindex]));
+        }
+        if let Some(uuid) = client.uuid {
+            QUEUED_SQL.push(QueuedSqlType::InventoryInventoryUpdate(uuid, client.inv.inv));
+        }
+    }
+
+    true
+}
+
+//Applies an intent to the server's copies of the client's slots and the chests, then sends what the named or changed
+//slots now hold: to the client, and for chests to the players who see them. A refused intent changes nothing, but the
+//client still gets the slots it named, in case it had them wrong
+fn apply_inventory_intent(
+    endpoint: &mut Endpoint,
+    clients: &mut ServerClients,
+    client_id: ClientId,
+    others: &[ClientId],
+    csys: &ChunkSystem,
+    chestreg: &DashMap<vec::IVec3, ChestInventory>,
+    edit_cache: &mut ChunkEditCache,
+    intent: &InventoryIntent,
+) {
+    let Some(client) = clients.0.get_mut(&client_id) else {
+        return;
+    };
+    let position = client.last_update.map(|(_, pos, _)| pos.0);
+    let named = named_slots(intent);
+
+    //Chests it names must be chest blocks in reach. They are made empty on first use
+    let mut result = Ok(Vec::new());
+    for slot in named.iter() {
+        if let SlotRef::Chest(chest, _) = slot {
+            let vchest = vec::IVec3::new(chest.0.x, chest.0.y, chest.0.z);
+            if !can_use_chest(position, &vchest, csys.blockat(vchest)) {
+                result = Err(TransactionRejection::ChestOutOfReach);
+                break;
+            }
+            chestreg.entry(vchest).or_insert(ChestInventory {
+                dirty: false,
+                inv: [(0, 0); ROWLENGTH as usize * 4],
+            });
+        }
+    }
+    //Recipes not made at the small table need the crafting table it last opened, still there and in reach
+    match intent {
+        InventoryIntent::OpenCraftingTable(table) => {
+            client.crafting_table = Some(vec::IVec3::new(table.0.x, table.0.y, table.0.z));
+        }
+        InventoryIntent::CraftRecipe(recipe, _) if RECIPES.get(*recipe).map_or(false, |recipe| !recipe.2) => {
+            let at_table = client
+                .crafting_table
+                .map_or(false, |table| can_use_crafting_table(position, &table, csys.blockat(table)));
+            if !at_table {
+                result = Err(TransactionRejection::NoCraftingTable);
+            }
+        }
+        _ => {}
+    }
+    if result.is_ok() {
+        let mut slots = PlayerSlots {
+            inv: &mut client.inv,
+            mouse: &mut client.mouse,
+            chests: chestreg,
+        };
+        result = slots.apply(intent);
+    }
+
+    let (applied, slots) = match result {
+        Ok(changed) => (true, changed),
+        Err(rejection) => {
+            println!("Refused inventory intent from {}, {:?}: {:?}", client_id, intent, rejection);
+            //Chests it can not reach stay unknown to it
+            let named = named
+                .into_iter()
+                .filter(|slot| rejection != TransactionRejection::ChestOutOfReach || !matches!(slot, SlotRef::Chest(..)))
+                .collect();
+            (false, named)
+        }
+    };
+
+    let mut inv_changed = false;
+    let mut chests = Vec::new();
+    for slot in slots {
+        match slot {
+            SlotRef::Mouse => endpoint.try_send_typed(client_id, Message::ItemToYourMouse(client.mouse)),
+            SlotRef::Inv(slotindex) => {
+                if let Some(contents) = client.inv.inv.get(slotindex) {
+                    endpoint.try_send_typed(client_id, Message::InvUpdate(slotindex, *contents));
+                    inv_changed = true;
+                }
+            }
+            SlotRef::Chest(chest, slotindex) => chests.push((chest, slotindex)),
+        }
+    }
+    if applied && inv_changed {
+        if let Some(uuid) = client.uuid {
+            QUEUED_SQL.push(QueuedSqlType::InventoryInventoryUpdate(uuid, client.inv.inv));
+        }
+    }
+
+    let currseed = unsafe { CURRSEED.load(Ordering::Relaxed) };
+    for (chest, slotindex) in chests {
+        let vchest = vec::IVec3::new(chest.0.x, chest.0.y, chest.0.z);
+        let Some(chestinv) = chestreg.get(&vchest).map(|chestinv| chestinv.inv) else {
+            continue;
+        };
+        let Some(contents) = chestinv.get(slotindex).copied() else {
+            continue;
+        };
+
+        endpoint.try_send_typed(client_id, Message::ChestUpdate(chest, slotindex, contents));
+        if applied {
+            QUEUED_SQL.push(QueuedSqlType::ChestInventoryUpdate(vchest, chestinv, currseed));
+            edit_cache.record_chest(vchest);
+
+            let seeing = clients_seeing(clients, others, &vchest);
+            let _ = endpoint.send_group_typed(seeing.iter(), Message::ChestUpdate(chest, slotindex, contents));
+        }
+    }
 }
 
 pub fn handle_client_messages(
     mut server: ResMut<QuintetServer>,
-    /*...*/
+    mut clients: ResMut<ServerClients>,
+    mut edit_cache: ResMut<ChunkEditCache>,
 ) {
+    if !SERVER_WORLD_READY.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let csys = unsafe { (*addr_of!(CHUNKSYS)).as_ref() };
+    let chestreg = unsafe { (*addr_of!(CHEST_REGISTRY)).as_ref() };
+    let tod = unsafe { (*addr_of!(TIMEOFDAY)).as_ref() };
+    let knowncams = unsafe { (*addr_of!(KNOWN_CAMERAS)).as_ref() };
+    let udm = unsafe { (*addr_of!(USERDATAMAP)).as_ref() };
+
+    let (csys, chestreg, tod, knowncams, udm) = match (csys, chestreg, tod, knowncams, udm) {
+        (Some(csys), Some(chestreg), Some(tod), Some(knowncams), Some(udm)) => (csys, chestreg, tod, knowncams, udm),
+        _ => return,
+    };
+
+    if !edit_cache.is_built() {
+        edit_cache.build(udm, chestreg);
+    }
+
     let endpoint = server.endpoint_mut();
     for client_id in endpoint.clients() {
+        let others: Vec<ClientId> = endpoint.clients().into_iter().filter(|c| *c != client_id).collect();
+
         while let Some(message) = endpoint.try_receive_message_from::<Message>(client_id) {
             match message {
+                (_, Message::RequestWorldInfo) => {
+                    println!("Recvd req world");
+
+                    let pt = csys.read().planet_type;
+                    let currseed = unsafe { CURRSEED.load(Ordering::Relaxed) };
+
+                    endpoint.try_send_typed(client_id, Message::WorldInfo(pt, currseed));
+                },
+                (_, Message::RequestChunkEdits(chunk)) => {
+                    let vchunk = vec::IVec2::new(chunk.0.x, chunk.0.y);
+                    let edits = edit_cache.edits(&vchunk);
+                    let chests = edit_cache.chests(&vchunk, chestreg);
+
+                    endpoint.try_send_typed(client_id, Message::ChunkEdits(chunk, edits, chests));
+                },
+                (_, Message::TellYouMyID(id1, id2)) => {
+                    let uuid = Uuid::from_u64_pair(id1, id2);
+                    println!("Received your client id, its {}", uuid);
+
+                    let Some(client) = clients.0.get(&client_id) else {
+                        continue;
+                    };
+                    //Only the id it joined with, and only once
+                    if uuid != client.player || client.uuid.is_some() {
+                        println!("Refused id {} from {}, it joined as {}", uuid, client_id, client.player);
+                        continue;
+                    }
+                    if clients.0.values().any(|other| other.uuid == Some(uuid)) {
+                        endpoint.try_disconnect_client(client_id, DisconnectReason::new(1, "player already connected"));
+                        remove_client(endpoint, &mut clients, client_id);
+                        break;
+                    }
+
+                    let Some(client) = clients.0.get_mut(&client_id) else {
+                        continue;
+                    };
+                    client.uuid = Some(uuid);
+                    //The database may not have its last inventory yet if it just left
+                    client.inv.inv = match LEFT_INVENTORIES.remove(&uuid) {
+                        Some((_, (_, inv))) => inv,
+                        None => load_client_inventory(uuid),
+                    };
+
+                    //The client's inventory lives here, it starts from what we send
+                    for (slotindex, slot) in client.inv.inv.iter().enumerate() {
+                        endpoint.try_send_typed(client_id, Message::InvUpdate(slotindex, *slot));
+                    }
+                    endpoint.try_send_typed(client_id, Message::ItemToYourMouse(client.mouse));
+                },
+                (_, Message::PlayerUpdate(id, pos, rot)) => {
+                    if let Some(client) = clients.0.get_mut(&client_id) {
+                        let uuid = Uuid::from_u64_pair(id.0, id.1);
+                        if uuid != client.player {
+                            continue;
+                        }
+                        knowncams.insert(uuid, pos.0);
+
+                        if client.saveposcounter > 10 {
+                            client.saveposcounter = 0;
+                            QUEUED_SQL.push(QueuedSqlType::PlayerPositionUpdate(uuid, pos.0, rot.0.x, rot.0.y));
+                        } else {
+                            client.saveposcounter += 1;
+                        }
+                    }
+
+                    move_player(endpoint, &mut clients, client_id, id, pos, rot);
+
+                    let t = *tod.lock();
+                    endpoint.try_send_typed(client_id, Message::TimeUpdate(t));
+                },
+                (_, Message::BlockSet(spot, block)) => {
+                    println!("Recvd block set");
+                    let Some(client) = clients.0.get_mut(&client_id) else {
+                        continue;
+                    };
+                    if !apply_block_edits(endpoint, client_id, client, &csys.read(), &mut edit_cache, &[(spot, block)]) {
+                        continue;
+                    }
+
+                    let vspot = vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z);
+                    let seeing = clients_seeing(&clients, &others, &vspot);
+                    let _ = endpoint.send_group_typed(seeing.iter(), Message::BlockSet(spot, block));
+                },
+                (_, Message::MultiBlockSet(blocks)) => {
+                    println!("Recvd multi block set");
+                    let Some(client) = clients.0.get_mut(&client_id) else {
+                        continue;
+                    };
+                    if !apply_block_edits(endpoint, client_id, client, &csys.read(), &mut edit_cache, &blocks) {
+                        continue;
+                    }
+
+                    //Each client only gets the blocks of its own view
+                    for other in others.iter() {
+                        if let Some(other_client) = clients.0.get(other) {
+                            let seen: Vec<(SerIVec3, u32)> = blocks
+                                .iter()
+                                .filter(|(spot, _)| other_client.interest.sees_spot(&vec::IVec3::new(spot.0.x, spot.0.y, spot.0.z)))
+                                .copied()
+                                .collect();
+                            if !seen.is_empty() {
+                                let _ = endpoint.send_typed(*other, Message::MultiBlockSet(seen));
+                            }
+                        }
+                    }
+                },
+                (_, Message::InventoryIntent(intent)) => {
+                    apply_inventory_intent(endpoint, &mut clients, client_id, &others, &csys.read(), chestreg, &mut edit_cache, &intent);
+                },
+                (_, Message::MobAck(seq)) => {
+                    if let Some(client) = clients.0.get_mut(&client_id) {
+                        client.mobs.acknowledge(seq);
+                    }
+                },
+                (_, Message::Disconnect) => {
+                    endpoint.try_disconnect_client(client_id, DisconnectReason::new(0, "client disconnected"));
+                    remove_client(endpoint, &mut clients, client_id);
+                    break;
+                },
                 _ => {
 
                 }
-                // (channelid, Message::Disconnect) => {
-
-                // },
-                // (channelid, Message::ChestUpdate(chestloc, slotindex, slot)) => {
-
-                // },
-                // (channelid, Message::BlockSet(servec3, blockid)) => {
-
-                // },
-                // (channelid, Message::InvUpdate(slotindex, slot)) => {
-
-                // },
-                // (channelid, Message::ItemToYourMouse(slot)) => {
-
-                // },
-                // (channelid, Message::MobUpdate) => {
-
-                // },
-                // (channelid, Message::MobUpdateBatch) => {
-
-                // },
-                // (channelid, Message::MultiBlockSet(blocks)) => {
-
-                // },
-                // (channelid, Message::RequestWorldInfo) => {
-
-                // },
-                // (channelid, Message::TellYouMyID(id1, id2)) => {
-
-                // },
-                // (channelid, Message::YourId(id1, id2)) => {
-
-                // },
-                // (channelid, Message::TimeUpdate(newtime)) => {
-
-                // },
-                // (channelid, Message::WorldInfo(chestregbytes, pt, udmbytes, seed)) => {
-                //     //ChestReg, Pt, Udm, Seed
-                // },
-                // (channelid, Message::PlayerUpdate(uuid, pos, rot)) => {
-                //     let uuid = Uuid::from_u64_pair(uuid.0, uuid.1);
-                //     println!("Received {} {} {} {}", uuid, pos.0.x, pos.0.y, pos.0.z);
-                //     // Send a message to a group of clients
-                //     // if let Err(e) = endpoint.broadcast_message_on(1, message.1.clone()) {
-                //     //     println!("Error broadcasting message: {:?}", e);
-                //     // } else {
-                //     //     println!("Broadcasted message to all clients.");
-                //     // }
-
-                //     // TODO: handle potential error
-                //     let _ = endpoint.send_group_message_on(
-                //         endpoint.clients().iter().filter(|s| **s != client_id),
-                //         1,
-                //         message.1.clone(),
-                //     );
-
-                //     /*...*/
-                // }
-                  // // Match on your own message types ...
-                  //You can also use endpoint.broadcast_message, which will send a message to all connected clients.
-                  // ClientMessage::Join { username} => {
-                  //     // Send a messsage to 1 client
-                  //     endpoint.send_message(client_id, ServerMessage::InitClient {/*...*/}).unwrap();
-                  //     /*...*/
-                  // }
-                  // ClientMessage::Disconnect { } => {
-                  //     // Disconnect a client
-                  //     endpoint.disconnect_client(client_id);
-                  //     /*...*/
-                  // }
-                  // ClientMessage::ChatMessage { message } => {
-                  //     // Send a message to a group of clients
-                  //     endpoint.send_group_message(
-                  //             client_group, // Iterator of ClientId
-                  //             ServerMessage::ChatMessage {/*...*/}
-                  //         )
-                  //         .unwrap();
-                  //     /*...*/
-                  // }
             }
         }
     }
 }
+
+#[derive(Resource)]
+pub struct MobUpdateTimer(pub Timer);
+
+//Sends every client the mobs in its view that changed since the last state it acknowledged
+pub fn replicate_mobs(
+    mut server: ResMut<QuintetServer>,
+    mut clients: ResMut<ServerClients>,
+    time: Res<Time>,
+    mut timer: ResMut<MobUpdateTimer>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let nsme = match unsafe { (*addr_of!(NON_STATIC_MODEL_ENTITIES)).as_ref() } {
+        Some(nsme) => nsme,
+        None => return,
+    };
+    let mobs: Vec<MobSnapshot> = nsme.iter().map(|e| MobSnapshot::of(e.value())).collect();
+
+    let endpoint = server.endpoint_mut();
+    for (client_id, client) in clients.0.iter_mut() {
+        //Mobs out of view are despawned on the client, and spawned again when they come back
+        let visible: Vec<MobSnapshot> = mobs.iter().filter(|mob| client.interest.sees(mob.state.position())).cloned().collect();
+        for (seq, mut deltas) in client.mobs.build_batches(&visible) {
+            let message = match deltas.len() {
+                1 => Message::MobUpdate(seq, deltas.pop().unwrap()),
+                _ => Message::MobUpdateBatch(seq, deltas),
+            };
+            endpoint.try_send_typed(*client_id, message);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use once_cell::sync::Lazy;
 use tracing::info;
+
+use crate::inventory::{Inventory, MAX_STACK};
 pub type Recipe = (Vec<(u32, u32)>, (u32, u32), bool);
 
 pub static mut RECIPES_DISABLED: bool = false;
@@ -78,4 +80,50 @@ pub static RECIPES: Lazy<[Recipe; 25]> = Lazy::new(||
 
 
     }
-);
\ No newline at end of file
+);
+
+
+//Crafts recipe once in inv, if it has the ingredients and room for the result. Returns whether it did
+pub fn craft_in_inventory(recipe: &Recipe, inv: &mut Inventory) -> bool {
+    let mut slots = inv.inv;
+
+    for req in &recipe.0 {
+        let amt: u32 = slots.iter().filter(|slot| slot.0 == req.0).map(|slot| slot.1).sum();
+        if amt < req.1 {
+            return false;
+        }
+    }
+
+    //Subtract the ingredients
+    for req in &recipe.0 {
+        let mut needed = req.1;
+        for slot in slots.iter_mut().filter(|slot| slot.0 == req.0) {
+            let taken = slot.1.min(needed);
+            slot.1 -= taken;
+            needed -= taken;
+            if slot.1 == 0 {
+                *slot = (0, 0);
+            }
+            if needed == 0 {
+                break;
+            }
+        }
+    }
+
+    //Then the result goes in the first empty spot or matching item slot, the craft is off if there is none
+    let result = recipe.1;
+    match slots
+        .iter_mut()
+        .find(|slot| (slot.0 == 0 || slot.0 == result.0) && slot.1 + result.1 <= MAX_STACK)
+    {
+        Some(slot) => {
+            slot.0 = result.0;
+            slot.1 += result.1;
+        }
+        None => return false,
+    }
+
+    inv.inv = slots;
+    inv.dirty = true;
+    true
+}
@@ -4,18 +4,169 @@
 
 use borsh::*;
 
-// use uuid::Uuid;
 use bevy::prelude::*;
+use jeffy_quintet::routed_message;
+use jeffy_quintet::shared::admission::{Admission, JoinRequest};
+use jeffy_quintet::shared::channels::ChannelPriority;
+use uuid::Uuid;
 
-
+use crate::game::ROWLENGTH;
 // use crate::vec;
 
 
 pub const MOB_BATCH_SIZE: usize = 16;
+
+//Bump whenever the layout of Message changes, borsh would misdecode messages between two different layouts
+pub const PROTOCOL_VERSION: u32 = 9;
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+//Optional features, as bits of the hello's feature set. None yet
+pub const SUPPORTED_FEATURES: u64 = 0;
+//Features a client must support to join
+pub const REQUIRED_FEATURES: u64 = 0;
+
+//First exchange of every connection, sent as the join request before any Message and answered by accept or reject
+pub fn hello(player_id: Uuid) -> JoinRequest {
+    JoinRequest {
+        protocol_version: PROTOCOL_VERSION,
+        game_version: GAME_VERSION.to_string(),
+        features: SUPPORTED_FEATURES,
+        player_id: player_id.as_u128(),
+        token: None,
+    }
+}
+
+//Server side of the hello: refuses clients which would not understand our messages, with a reason they can show
+pub fn check_hello(hello: &JoinRequest) -> Admission {
+    if hello.protocol_version != PROTOCOL_VERSION {
+        return Admission::Reject(format!(
+            "Incompatible game version: the server runs {} (protocol {}), you have {} (protocol {}).",
+            GAME_VERSION, PROTOCOL_VERSION, hello.game_version, hello.protocol_version
+        ));
+    }
+    let missing = REQUIRED_FEATURES & !hello.features;
+    if missing != 0 {
+        return Admission::Reject(format!(
+            "Your game {} lacks features this server requires ({:#x}).",
+            hello.game_version, missing
+        ));
+    }
+    Admission::Accept
+}
+//Mob positions travel in 1/16 of a block, rotations in 1/65536 of a turn
+pub const MOB_POSITION_STEPS: f32 = 16.0;
+pub const MOB_ROTATION_STEPS: f32 = 65536.0;
+
+//Quantized state of a mob, what deltas are made against
+#[derive(Clone, Debug, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct MobState {
+    pub pos: [i32; 3],
+    //Around y, the only rotation mobs have
+    pub rot: u16,
+    pub sounding: bool,
+}
+
+impl MobState {
+    pub fn quantize(position: Vec3, rot_y: f32, sounding: bool) -> Self {
+        let turn = rot_y.rem_euclid(std::f32::consts::TAU) / std::f32::consts::TAU;
+        Self {
+            pos: [
+                (position.x * MOB_POSITION_STEPS).round() as i32,
+                (position.y * MOB_POSITION_STEPS).round() as i32,
+                (position.z * MOB_POSITION_STEPS).round() as i32,
+            ],
+            rot: ((turn * MOB_ROTATION_STEPS).round() as u32 & 0xFFFF) as u16,
+            sounding,
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        Vec3::new(
+            self.pos[0] as f32 / MOB_POSITION_STEPS,
+            self.pos[1] as f32 / MOB_POSITION_STEPS,
+            self.pos[2] as f32 / MOB_POSITION_STEPS,
+        )
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rot as f32 / MOB_ROTATION_STEPS * std::f32::consts::TAU
+    }
+
+    //Position change from base, if small enough to send as a delta
+    pub fn delta_from(&self, base: &MobState) -> Option<[i16; 3]> {
+        Some([
+            i16::try_from(self.pos[0] - base.pos[0]).ok()?,
+            i16::try_from(self.pos[1] - base.pos[1]).ok()?,
+            i16::try_from(self.pos[2] - base.pos[2]).ok()?,
+        ])
+    }
+
+    pub fn apply_delta(base: &MobState, dpos: [i16; 3], rot: u16, sounding: bool) -> Self {
+        Self {
+            pos: [
+                base.pos[0] + dpos[0] as i32,
+                base.pos[1] + dpos[1] as i32,
+                base.pos[2] + dpos[2] as i32,
+            ],
+            rot,
+            sounding,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum MobDelta {
+    //Full state, for mobs the client has not acknowledged yet (or whose last acknowledged state is too old)
+    Spawn {
+        id: u32,
+        model_index: u8,
+        scale: f32,
+        hostile: bool,
+        state: MobState,
+    },
+    //Change against the state this mob had in batch base_seq, which the client acknowledged
+    Delta {
+        id: u32,
+        base_seq: u32,
+        dpos: [i16; 3],
+        rot: u16,
+        sounding: bool,
+    },
+    Despawn {
+        id: u32,
+    },
+}
+
+//A slot an inventory intent acts on: the item held by the mouse, one of the player's inventory, or one of a chest
+#[derive(Clone, Debug, Copy, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum SlotRef {
+    Mouse,
+    Inv(usize),
+    Chest(SerIVec3, usize),
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum InventoryIntent {
+    //Whole stack onto another slot: merged if the same item, swapped otherwise
+    MoveStack(SlotRef, SlotRef),
+    //That many items of a stack onto another slot, empty or of the same item
+    SplitStack(SlotRef, SlotRef, u32),
+    //Chest slot into the inventory, wherever it fits
+    TakeFromChest(SerIVec3, usize),
+    //Index into RECIPES, and whether to craft as many as possible
+    CraftRecipe(usize, bool),
+    //One item of a food stack in the inventory, eaten
+    ConsumeSlot(usize),
+    //The crafting table it opened, for the recipes that can't be made at the small table
+    OpenCraftingTable(SerIVec3),
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct SerVec3(pub Vec3);
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq)]
 pub struct SerIVec3(pub IVec3);
+#[derive(Clone, Debug, Copy)]
+pub struct SerIVec2(pub IVec2);
 
 impl BorshSerialize for SerVec3 {
     fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
@@ -71,38 +222,85 @@ impl BorshDeserialize for SerIVec3 {
     }
 }
 
-#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
-pub enum Message {
-    None,
-    // RequestUdm,
-    // RequestSeed,
-    // RequestPt,
-    // ReqChestReg,
-    RequestWorldInfo,
-
-    // ChestReg(Vec<u8>), //If somethings a Vec<u8> its a db file
-    // Pt(u8),
-    // Udm(Vec<u8>),
-    // Seed(u32),
-
-    //ChestReg, Pt, Udm, Seed
-    WorldInfo(Vec<u8>, u8, Vec<u8>, u32),
-
-    TimeUpdate(f32),
-    PlayerUpdate((u64, u64), SerVec3, SerVec3),
-    BlockSet(SerIVec3, u32),
-    MultiBlockSet(Vec<(SerIVec3, u32)>),
-    YourId(u64, u64),
-    TellYouMyID(u64, u64),
-    ChestUpdate(SerIVec3, usize, (u32, u32)),
-    InvUpdate(usize, (u32, u32)),
-    ItemToYourMouse((u32, u32)),
-    Disconnect,
-
-    //TODO: These
-    MobUpdate,
-    MobUpdateBatch,
-    //RequestTakeoff,
+impl BorshSerialize for SerIVec2 {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.0.x.serialize(writer)?;
+        self.0.y.serialize(writer)?;
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for SerIVec2 {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(SerIVec2(IVec2 {
+            x: BorshDeserialize::deserialize(buf)?,
+            y: BorshDeserialize::deserialize(buf)?,
+        }))
+    }
+
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        Ok(SerIVec2(IVec2 {
+            x: BorshDeserialize::deserialize_reader(reader)?,
+            y: BorshDeserialize::deserialize_reader(reader)?,
+        }))
+    }
+}
+
+pub const MAX_MULTI_BLOCK_SET_SIZE: usize = 1024 * 1024;
+//Largest single message: every edit and chest of one heavily built chunk
+pub const MAX_CHUNK_EDITS_SIZE: usize = 4 * 1024 * 1024;
+//Chunk edits go on their own, lower priority, reliable channel so they do not hold back block edits
+pub const CHUNK_EDITS_PRIORITY: ChannelPriority = -1;
+
+// Route of each message: (channel type, direction, max size[, priority])
+routed_message! {
+    #[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+    pub enum Message {
+        None => (Unreliable, Both, 1),
+        // RequestUdm,
+        // RequestSeed,
+        // RequestPt,
+        // ReqChestReg,
+        RequestWorldInfo => (OrderedReliable, ClientToServer, 1),
+
+        // ChestReg(Vec<u8>), //If somethings a Vec<u8> its a db file
+        // Pt(u8),
+        // Udm(Vec<u8>),
+        // Seed(u32),
+
+        //Pt, Seed. User edits are then pulled chunk by chunk with RequestChunkEdits
+        WorldInfo(u8, u32) => (OrderedReliable, ServerToClient, 8),
+        RequestChunkEdits(SerIVec2) => (OrderedReliable, ClientToServer, 8),
+        //Chunk, its user edited blocks, its chests
+        ChunkEdits(SerIVec2, Vec<(SerIVec3, u32)>, Vec<(SerIVec3, [(u32, u32); ROWLENGTH as usize * 4])>) => (OrderedReliable, ServerToClient, MAX_CHUNK_EDITS_SIZE, CHUNK_EDITS_PRIORITY),
+
+        TimeUpdate(f32) => (Unreliable, ServerToClient, 16),
+        //Uuid, Pos, (Pitch, Yaw, Model y rotation). Sequenced so an old position arriving late never overwrites a newer one
+        PlayerUpdate((u64, u64), SerVec3, SerVec3) => (UnreliableSequenced, Both, 64),
+        BlockSet(SerIVec3, u32) => (OrderedReliable, Both, 32),
+        MultiBlockSet(Vec<(SerIVec3, u32)>) => (OrderedReliable, Both, MAX_MULTI_BLOCK_SET_SIZE),
+        //What the blocks of a refused BlockSet / MultiBlockSet really are, for the client to roll its prediction back
+        BlockCorrection(Vec<(SerIVec3, u32)>) => (OrderedReliable, ServerToClient, MAX_MULTI_BLOCK_SET_SIZE),
+        YourId(u64, u64) => (OrderedReliable, ServerToClient, 32),
+        TellYouMyID(u64, u64) => (OrderedReliable, ClientToServer, 32),
+        //What the player wants done to its slots. The server applies it to its copies and answers with the slots changed
+        InventoryIntent(InventoryIntent) => (OrderedReliable, ClientToServer, 64),
+        //Authoritative slot contents
+        ChestUpdate(SerIVec3, usize, (u32, u32)) => (OrderedReliable, ServerToClient, 64),
+        InvUpdate(usize, (u32, u32)) => (OrderedReliable, ServerToClient, 32),
+        ItemToYourMouse((u32, u32)) => (OrderedReliable, ServerToClient, 16),
+        Disconnect => (OrderedReliable, Both, 1),
+
+        //Batch sequence number, then one mob / up to MOB_BATCH_SIZE mobs
+        MobUpdate(u32, MobDelta) => (Unreliable, ServerToClient, 64),
+        MobUpdateBatch(u32, Vec<MobDelta>) => (Unreliable, ServerToClient, 1024),
+        //Sequence number of a mob batch the client decoded, deltas are then made against it
+        MobAck(u32) => (Unreliable, ClientToServer, 8),
+        //Another player came within / went out of our view radius. Updates of players not in view are ignored
+        PlayerEnteredView((u64, u64), SerVec3, SerVec3) => (OrderedReliable, ServerToClient, 64),
+        PlayerLeftView((u64, u64)) => (OrderedReliable, ServerToClient, 32),
+        //RequestTakeoff,
+    }
 }
 
 // #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -6,7 +6,7 @@ use gltf::mesh::util::ReadIndices;
 use crate::{
     audio::spawn_audio_thread, blockinfo::Blocks, game::{
         Game, JGltfNode, AUDIOPLAYER, CAMERA, CROUCHING, CURRENT_AVAIL_RECIPES, DECIDEDSPORMP, MOUSEX, MOUSEY, SHOWTOOLTIP, SINGLEPLAYER, TOOLTIPNAME
-    }, keybinds::{AboutToRebind, ABOUTTOREBIND, LISTENINGFORREBIND}, menu3d::draw_3d_menu_button, newclient::{ADDRESSENTERED, THEENTEREDADDRESS}, recipes::{RECIPES_DISABLED, RECIPE_COOLDOWN_TIMER}, statics::{
+    }, keybinds::{AboutToRebind, ABOUTTOREBIND, LISTENINGFORREBIND}, menu3d::draw_3d_menu_button, landiscovery::{start_lan_search, LAN_SEARCHING, LAN_SERVERS}, newclient::{ADDRESSENTERED, CONNECTION_LOST, CONNECTION_REFUSED, THEENTEREDADDRESS}, recipes::{RECIPES_DISABLED, RECIPE_COOLDOWN_TIMER}, statics::{
         LAST_ENTERED_SERVERADDRESS, load_misc, load_or_initialize_statics, MISCSETTINGS, save_lesa,
     }, texture::Texture
 };
@@ -768,6 +768,9 @@ impl WindowAndKeyContext {
                                     //     }
                                     // }
                                     
+                                } else if let Some(reason) = CONNECTION_REFUSED.lock().clone() {
+                                    //Still waiting for the world, but the server will never send it
+                                    Self::draw_notice(&mut self.imgui, &self.guirenderer, &self.window, "Connection Refused Window", &reason);
                                 }
 
                                 self.imgui
@@ -807,6 +810,11 @@ impl WindowAndKeyContext {
                                     self.guirenderer.render(&mut self.imgui);
                                 }
 
+                                if let Some(reason) = CONNECTION_LOST.lock().clone() {
+                                    //The server is gone, say why instead of leaving the world frozen or the loading screen hanging
+                                    Self::draw_notice(&mut self.imgui, &self.guirenderer, &self.window, "Connection Lost Window", &reason);
+                                }
+
                                 if gmenuopen {
                                     let gamecurrentbuttons = g.currentbuttons.clone();
 
@@ -1446,6 +1454,26 @@ impl WindowAndKeyContext {
                                         .store(true, std::sync::atomic::Ordering::Relaxed);
                                 }
                                 // pos_y += button_height + 10.0; // Add some spacing between buttons
+
+                                ui.set_cursor_pos([pos_x, pos_y + 75.0]);
+
+                                let searching = LAN_SEARCHING.load(std::sync::atomic::Ordering::Relaxed);
+                                let search_label = if searching { "Searching for LAN servers..." } else { "Search for LAN servers" };
+                                if ui.button_with_size(search_label, [button_width, button_height]) && !searching {
+                                    start_lan_search();
+                                }
+
+                                //Clicking a found server puts its address in the box, Connect works as usual
+                                for (i, server) in LAN_SERVERS.lock().iter().enumerate() {
+                                    ui.set_cursor_pos([pos_x, pos_y + 100.0 + i as f32 * 25.0]);
+                                    let label = format!(
+                                        "{} ({} players) - {}##lanserver{}",
+                                        server.announcement.name, server.announcement.players, server.address, i
+                                    );
+                                    if ui.button_with_size(label, [button_width, button_height]) {
+                                        self.serveraddrbuffer = server.address.to_string();
+                                    }
+                                }
                             });
 
                         // Render the ImGui frame
@@ -1594,6 +1622,35 @@ impl WindowAndKeyContext {
         self.window.write().swap_buffers();
     }
 
+    //Centered box of text over whatever is drawn, for connection problems
+    fn draw_notice(
+        imgui: &mut imgui::Context,
+        guirenderer: &imgui_opengl_renderer::Renderer,
+        window: &RwLock<PWindow>,
+        name: &str,
+        text: &str,
+    ) {
+        let (width, height) = window.read().get_framebuffer_size();
+        imgui.io_mut().display_size = [width as f32, height as f32];
+
+        let ui = imgui.frame();
+        let window_size = [600.0, 100.0];
+        ui.window(name)
+            .size(window_size, Condition::Always)
+            .position(
+                [
+                    width as f32 / 2.0 - window_size[0] / 2.0,
+                    height as f32 / 2.0 - window_size[1] / 2.0,
+                ],
+                Condition::Always,
+            )
+            .flags(WindowFlags::NO_DECORATION | WindowFlags::NO_MOVE | WindowFlags::NO_RESIZE)
+            .build(|| {
+                ui.text_wrapped(text);
+            });
+        guirenderer.render(imgui);
+    }
+
     fn set_mod(io: &mut imgui::Io, modifier: Modifiers) {
         io.key_ctrl = modifier.intersects(Modifiers::Control);
         io.key_alt = modifier.intersects(Modifiers::Alt);
@@ -1,4 +1,24 @@
+use bevy::math::Vec3;
+use borsh::{BorshDeserialize, BorshSerialize};
+use dashmap::DashMap;
+use jeffy_quintet::shared::admission::Admission;
+use uuid::Uuid;
+use voxelland::blockedits::{check_block_edit, is_crystal_cluster_break, EditOutcome, EditRejection, CLUSTER_CRYSTAL};
+use voxelland::chunk::CH_W;
+use voxelland::chunkedits::{ChunkEditCache, PendingChunkEdits};
+use voxelland::game::ROWLENGTH;
+use voxelland::interest::{ClientInterest, VIEW_RADIUS};
+use voxelland::invtransactions::{can_use_crafting_table, chest_pos, chest_slot, PlayerSlots, TransactionRejection};
+use voxelland::inventory::{ChestInventory, Inventory, MAX_STACK};
+use voxelland::mobreplication::{MobBaselines, MobReplication, MobSnapshot, ReceivedMob, MOB_HISTORY};
 use voxelland::packedvertex::PackedVertex;
+use voxelland::recipes::RECIPES;
+use voxelland::specialblocks::door::DOORTOP_BITS;
+use voxelland::vec::{IVec2, IVec3};
+use voxelland::server_types::{
+    check_hello, hello, InventoryIntent, Message, MobDelta, MobState, SerIVec3, SlotRef, GAME_VERSION, MOB_BATCH_SIZE,
+    PROTOCOL_VERSION,
+};
 
 #[test]
 fn test_coord_packing() {
@@ -79,3 +99,385 @@ fn test_coord_unpacking() {
     assert_eq!(amb, unpk_amb as u8);
     assert_eq!(unpk_bl as u8, bl);
 }
+
+//Message as an older client would lay it out: WorldInfo did not exist yet, so every later variant had a smaller index
+#[allow(dead_code)]
+#[derive(BorshSerialize, BorshDeserialize)]
+enum OldMessage {
+    None,
+    RequestWorldInfo,
+    TimeUpdate(f32),
+}
+
+#[test]
+fn test_mismatched_layouts_are_refused() {
+    //Without the hello, an old TimeUpdate would be read as something else entirely
+    let old_bytes = borsh::to_vec(&OldMessage::TimeUpdate(0.5)).unwrap();
+    assert!(!matches!(
+        borsh::from_slice::<Message>(&old_bytes),
+        Ok(Message::TimeUpdate(_))
+    ));
+
+    let mut old_hello = hello(Uuid::new_v4());
+    old_hello.protocol_version = PROTOCOL_VERSION - 1;
+    old_hello.game_version = "0.0.1".to_string();
+    match check_hello(&old_hello) {
+        Admission::Reject(reason) => {
+            assert!(reason.contains("0.0.1"));
+            assert!(reason.contains(GAME_VERSION));
+        }
+        Admission::Accept => panic!("A client with another protocol version was admitted"),
+    }
+
+    let mut new_hello = hello(Uuid::new_v4());
+    new_hello.protocol_version = PROTOCOL_VERSION + 1;
+    assert!(matches!(check_hello(&new_hello), Admission::Reject(_)));
+
+    assert_eq!(check_hello(&hello(Uuid::new_v4())), Admission::Accept);
+}
+
+#[test]
+fn test_hello_starts_with_protocol_version() {
+    //Whatever else changes in the hello, the version must stay readable by every build
+    let bytes = borsh::to_vec(&hello(Uuid::new_v4())).unwrap();
+    assert_eq!(bytes[..4], PROTOCOL_VERSION.to_le_bytes());
+}
+
+fn mob(id: u32, position: Vec3) -> MobSnapshot {
+    MobSnapshot {
+        id,
+        model_index: 2,
+        scale: 1.0,
+        hostile: false,
+        state: MobState::quantize(position, 1.0, false),
+    }
+}
+
+#[test]
+fn test_mob_replication() {
+    let mut server = MobReplication::default();
+    let mut client = MobBaselines::default();
+
+    //Everything is sent whole until acknowledged, in batches of MOB_BATCH_SIZE
+    let mobs: Vec<MobSnapshot> = (0..20).map(|id| mob(id, Vec3::new(id as f32, 80.0, 0.5))).collect();
+    let batches = server.build_batches(&mobs);
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].1.len(), MOB_BATCH_SIZE);
+    for (seq, deltas) in batches {
+        assert!(deltas.iter().all(|delta| matches!(delta, MobDelta::Spawn { .. })));
+        let received = client.decode(seq, deltas).unwrap();
+        assert!(received.iter().all(|mob| matches!(mob, ReceivedMob::Spawn { .. })));
+        server.acknowledge(seq);
+    }
+
+    //Unchanged mobs are not sent again, moved ones as deltas
+    let mut mobs = mobs;
+    mobs[3] = mob(3, Vec3::new(3.25, 81.0, 0.5));
+    mobs.remove(7);
+    let batches = server.build_batches(&mobs);
+    assert_eq!(batches.len(), 1);
+    let (seq, deltas) = batches.into_iter().next().unwrap();
+    assert_eq!(deltas.len(), 2);
+    let received = client.decode(seq, deltas).unwrap();
+    assert!(received.contains(&ReceivedMob::Update {
+        id: 3,
+        position: Vec3::new(3.25, 81.0, 0.5),
+        rot: mobs[3].state.rotation(),
+        sounding: false,
+    }));
+    assert!(received.contains(&ReceivedMob::Despawn { id: 7 }));
+
+    //Without an ack, the despawn is repeated
+    let batches = server.build_batches(&mobs);
+    assert!(batches[0].1.contains(&MobDelta::Despawn { id: 7 }));
+}
+
+#[test]
+fn test_mob_moving_after_long_idle() {
+    let mut server = MobReplication::default();
+    let mut client = MobBaselines::default();
+
+    let mut mobs = vec![mob(0, Vec3::new(0.5, 80.0, 0.5)), mob(1, Vec3::new(1.5, 80.0, 0.5))];
+    for (seq, deltas) in server.build_batches(&mobs) {
+        client.decode(seq, deltas).unwrap();
+        server.acknowledge(seq);
+    }
+
+    //Mob 1 stays put while mob 0 keeps moving, for longer than the history
+    for tick in 0..MOB_HISTORY * 2 {
+        mobs[0] = mob(0, Vec3::new(0.5 + (tick % 2) as f32, 80.0, 0.5));
+        for (seq, deltas) in server.build_batches(&mobs) {
+            client.decode(seq, deltas).unwrap();
+            server.acknowledge(seq);
+        }
+    }
+
+    //Its base is too old for a delta, it is sent whole once and then acknowledged
+    mobs[1] = mob(1, Vec3::new(2.5, 80.0, 0.5));
+    let batches = server.build_batches(&mobs);
+    assert_eq!(batches.len(), 1);
+    let (seq, deltas) = batches.into_iter().next().unwrap();
+    assert!(deltas.iter().any(|delta| matches!(delta, MobDelta::Spawn { id: 1, .. })));
+    let received = client.decode(seq, deltas).unwrap();
+    assert!(received.iter().any(|mob| matches!(mob, ReceivedMob::Spawn { id: 1, position, .. } if *position == Vec3::new(2.5, 80.0, 0.5))));
+    server.acknowledge(seq);
+
+    assert!(server.build_batches(&mobs).is_empty());
+}
+
+#[test]
+fn test_mob_state_quantization() {
+    let state = MobState::quantize(Vec3::new(-12.5, 100.0625, 7.0), -std::f32::consts::FRAC_PI_2, true);
+    assert_eq!(state.position(), Vec3::new(-12.5, 100.0625, 7.0));
+    assert!((state.rotation() - 3.0 * std::f32::consts::FRAC_PI_2).abs() < 0.001);
+    assert!(state.sounding);
+
+    let far = MobState::quantize(Vec3::new(5000.0, 0.0, 0.0), 0.0, false);
+    assert_eq!(far.delta_from(&state), None);
+}
+
+#[test]
+fn test_client_interest() {
+    let mut interest = ClientInterest::default();
+    //Nothing is seen before the first position
+    assert!(!interest.sees(Vec3::ZERO));
+
+    assert!(interest.update_center(Vec3::new(1.0, 60.0, 1.0)));
+    assert!(interest.sees(Vec3::ZERO));
+    assert!(interest.sees_spot(&IVec3::new(VIEW_RADIUS * CH_W, 0, -VIEW_RADIUS * CH_W)));
+    assert!(!interest.sees_spot(&IVec3::new((VIEW_RADIUS + 1) * CH_W, 0, 0)));
+
+    //Moving within the same chunk keeps the subscription
+    assert!(!interest.update_center(Vec3::new(2.0, 70.0, 2.0)));
+
+    assert!(interest.update_center(Vec3::new(((VIEW_RADIUS + 1) * CH_W) as f32, 60.0, 0.0)));
+    assert!(interest.sees_spot(&IVec3::new((VIEW_RADIUS + 1) * CH_W, 0, 0)));
+    assert!(!interest.sees_spot(&IVec3::new(-CH_W, 0, 0)));
+}
+
+#[test]
+fn test_chunk_edits() {
+    let udm = DashMap::new();
+    udm.insert(IVec3::new(1, 40, 1), 5);
+    udm.insert(IVec3::new(CH_W + 1, 40, 1), 6);
+    let chestreg = DashMap::new();
+    chestreg.insert(
+        IVec3::new(2, 41, 2),
+        ChestInventory { dirty: false, inv: [(3, 1); ROWLENGTH as usize * 4] },
+    );
+
+    let mut cache = ChunkEditCache::default();
+    cache.build(&udm, &chestreg);
+    let origin = IVec2::new(0, 0);
+    let edits = cache.edits(&origin);
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].1, 5);
+    assert_eq!(cache.chests(&origin, &chestreg).len(), 1);
+    assert!(cache.edits(&IVec2::new(5, 5)).is_empty());
+
+    //Later edits land in the chunk they belong to
+    cache.record_block(IVec3::new(-1, 40, 0), 7);
+    assert_eq!(cache.edits(&IVec2::new(-1, 0)).len(), 1);
+    cache.record_block(IVec3::new(1, 40, 1), 0);
+    assert_eq!(cache.edits(&origin)[0].1, 0);
+
+    //Replies to chunks never asked for are dropped
+    let mut pending = PendingChunkEdits::default();
+    assert!(pending.receive(origin, cache.edits(&origin)).is_none());
+
+    //A block the server set after the request is newer than the reply's
+    pending.request(origin);
+    pending.block_set(IVec3::new(1, 40, 1));
+    let reply = vec![
+        (SerIVec3(bevy::math::IVec3::new(1, 40, 1)), 5),
+        (SerIVec3(bevy::math::IVec3::new(3, 40, 3)), 9),
+    ];
+    assert_eq!(pending.receive(origin, reply), Some(vec![(IVec3::new(3, 40, 3), 9)]));
+    assert!(pending.receive(origin, Vec::new()).is_none());
+}
+
+#[test]
+fn test_block_edit_checks() {
+    let mut inv = Inventory { dirty: false, inv: [(0, 0); ROWLENGTH as usize] };
+    let spot = IVec3::new(4, 40, 4);
+    let near = Some(Vec3::new(4.5, 41.5, 6.0));
+
+    assert_eq!(check_block_edit(None, &spot, 1, 0, &inv), Err(EditRejection::OutOfReach));
+    assert_eq!(
+        check_block_edit(Some(Vec3::new(4.5, 41.5, 40.0)), &spot, 1, 0, &inv),
+        Err(EditRejection::OutOfReach)
+    );
+    assert_eq!(check_block_edit(near, &spot, 15, 0, &inv), Err(EditRejection::Unbreakable));
+    assert_eq!(
+        check_block_edit(near, &spot, 1, 0, &inv),
+        Ok(EditOutcome { broke: Some(1), placed: None })
+    );
+    //Red crystal that can't be placed gives the kind that can
+    assert_eq!(
+        check_block_edit(near, &spot, 16, 0, &inv),
+        Ok(EditOutcome { broke: Some(17), placed: None })
+    );
+    assert_eq!(check_block_edit(near, &spot, 0, 32, &inv), Err(EditRejection::NotPlaceable));
+    assert_eq!(check_block_edit(near, &spot, 0, 5, &inv), Err(EditRejection::NotOwned));
+
+    //The top half of a door is free, and breaking it gives nothing
+    assert_eq!(check_block_edit(near, &spot, 0, 19 | DOORTOP_BITS, &inv), Ok(EditOutcome::default()));
+    assert_eq!(check_block_edit(near, &spot, 19 | DOORTOP_BITS, 0, &inv), Ok(EditOutcome::default()));
+
+    assert_eq!(inv.add(5, 2), Some(0));
+    assert_eq!(inv.add(1, 1), Some(1));
+    assert_eq!(inv.add(5, 1), Some(0));
+    assert_eq!(inv.inv[0], (5, 3));
+    assert_eq!(
+        check_block_edit(near, &spot, 1, 5, &inv),
+        Ok(EditOutcome { broke: Some(1), placed: Some(5) })
+    );
+
+    assert_eq!(inv.take_one(1), Some(1));
+    assert_eq!(inv.inv[1], (0, 0));
+    assert_eq!(inv.take_one(1), None);
+}
+
+#[test]
+fn test_crystal_cluster_breaks() {
+    let clicked = IVec3::new(4, 40, 4);
+    let above = IVec3::new(4, 41, 4);
+    //Touches above, not the clicked one: fine since above broke before it
+    let further = IVec3::new(4, 42, 4);
+    let apart = IVec3::new(8, 40, 4);
+
+    assert!(is_crystal_cluster_break(&[
+        (clicked, CLUSTER_CRYSTAL, 0),
+        (above, CLUSTER_CRYSTAL, 0),
+        (further, CLUSTER_CRYSTAL, 0),
+    ]));
+    //Connected to nothing broken before it
+    assert!(!is_crystal_cluster_break(&[(clicked, CLUSTER_CRYSTAL, 0), (further, CLUSTER_CRYSTAL, 0)]));
+    assert!(!is_crystal_cluster_break(&[(clicked, CLUSTER_CRYSTAL, 0), (apart, CLUSTER_CRYSTAL, 0)]));
+    //Not a crystal on the server's side, or placing instead of breaking
+    assert!(!is_crystal_cluster_break(&[(clicked, CLUSTER_CRYSTAL, 0), (above, 1, 0)]));
+    assert!(!is_crystal_cluster_break(&[(clicked, CLUSTER_CRYSTAL, 0), (above, CLUSTER_CRYSTAL, 5)]));
+    //A lone crystal is an ordinary edit
+    assert!(!is_crystal_cluster_break(&[(clicked, CLUSTER_CRYSTAL, 0)]));
+}
+
+#[test]
+fn test_inventory_intents() {
+    let mut inv = Inventory { dirty: false, inv: [(0, 0); ROWLENGTH as usize] };
+    inv.inv[0] = (5, 10);
+    inv.inv[1] = (1, 3);
+    let food = ROWLENGTH as usize - 1;
+    inv.inv[food] = (32, 1);
+    let mut mouse = (0, 0);
+    let chests = DashMap::new();
+    let chest = IVec3::new(2, 30, 2);
+    chests.insert(chest, ChestInventory { dirty: false, inv: [(0, 0); ROWLENGTH as usize * 4] });
+
+    let mut slots = PlayerSlots { inv: &mut inv, mouse: &mut mouse, chests: &chests };
+
+    //Half of a stack into the mouse, then one of it onto a chest slot
+    assert!(slots.apply(&InventoryIntent::SplitStack(SlotRef::Inv(0), SlotRef::Mouse, 5)).is_ok());
+    assert_eq!(slots.get(&SlotRef::Mouse), Some((5, 5)));
+    assert!(slots.apply(&InventoryIntent::SplitStack(SlotRef::Mouse, chest_slot(chest, 3), 1)).is_ok());
+    assert_eq!(slots.get(&chest_slot(chest, 3)), Some((5, 1)));
+    assert_eq!(
+        slots.apply(&InventoryIntent::SplitStack(SlotRef::Mouse, SlotRef::Inv(1), 1)),
+        Err(TransactionRejection::CantSplit)
+    );
+    assert_eq!(
+        slots.apply(&InventoryIntent::SplitStack(SlotRef::Mouse, chest_slot(chest, 3), 9)),
+        Err(TransactionRejection::CantSplit)
+    );
+
+    //Onto the same item it merges, onto another it swaps
+    assert!(slots.apply(&InventoryIntent::MoveStack(SlotRef::Mouse, SlotRef::Inv(0))).is_ok());
+    assert_eq!(slots.get(&SlotRef::Inv(0)), Some((5, 9)));
+    assert_eq!(slots.get(&SlotRef::Mouse), Some((0, 0)));
+    assert!(slots.apply(&InventoryIntent::MoveStack(SlotRef::Inv(1), SlotRef::Inv(0))).is_ok());
+    assert_eq!(slots.get(&SlotRef::Inv(0)), Some((1, 3)));
+    assert_eq!(slots.get(&SlotRef::Inv(1)), Some((5, 9)));
+    assert_eq!(
+        slots.apply(&InventoryIntent::MoveStack(SlotRef::Inv(ROWLENGTH as usize), SlotRef::Inv(0))),
+        Err(TransactionRejection::NoSuchSlot)
+    );
+
+    //The chest stack goes onto the stack of that item
+    assert_eq!(
+        slots.apply(&InventoryIntent::TakeFromChest(chest_pos(chest), 3)),
+        Ok(vec![chest_slot(chest, 3), SlotRef::Inv(1)])
+    );
+    assert_eq!(slots.get(&SlotRef::Inv(1)), Some((5, 10)));
+    assert_eq!(
+        slots.apply(&InventoryIntent::TakeFromChest(chest_pos(chest), 3)),
+        Err(TransactionRejection::Empty)
+    );
+
+    //Crafting is checked against RECIPES: 1 -> 14 x8
+    let recipe = RECIPES.iter().position(|recipe| recipe.0 == vec![(1, 1)] && recipe.1 == (14, 8)).unwrap();
+    assert!(slots.apply(&InventoryIntent::CraftRecipe(recipe, true)).is_ok());
+    let crafted: u32 = slots.inv.inv.iter().filter(|slot| slot.0 == 14).map(|slot| slot.1).sum();
+    assert_eq!(crafted, 24);
+    assert!(!slots.inv.has(1));
+    assert_eq!(
+        slots.apply(&InventoryIntent::CraftRecipe(recipe, false)),
+        Err(TransactionRejection::CantCraft)
+    );
+    assert_eq!(
+        slots.apply(&InventoryIntent::CraftRecipe(RECIPES.len(), false)),
+        Err(TransactionRejection::NoSuchRecipe)
+    );
+
+    //Only food is eaten, one at a time
+    assert_eq!(
+        slots.apply(&InventoryIntent::ConsumeSlot(1)),
+        Err(TransactionRejection::NotFood)
+    );
+    assert_eq!(slots.apply(&InventoryIntent::ConsumeSlot(food)), Ok(vec![SlotRef::Inv(food)]));
+    assert_eq!(slots.get(&SlotRef::Inv(food)), Some((0, 0)));
+    assert_eq!(
+        slots.apply(&InventoryIntent::ConsumeSlot(food)),
+        Err(TransactionRejection::Empty)
+    );
+}
+
+#[test]
+fn test_stacks_stop_at_max_stack() {
+    let mut inv = Inventory { dirty: false, inv: [(0, 0); ROWLENGTH as usize] };
+    inv.inv[0] = (5, MAX_STACK - 10);
+    inv.inv[1] = (5, 30);
+    inv.inv[2] = (5, MAX_STACK - 3);
+    let mut mouse = (0, 0);
+    let chests = DashMap::new();
+    let mut slots = PlayerSlots { inv: &mut inv, mouse: &mut mouse, chests: &chests };
+
+    //Merging fills the stack up, the rest stays where it was
+    assert!(slots.apply(&InventoryIntent::MoveStack(SlotRef::Inv(1), SlotRef::Inv(0))).is_ok());
+    assert_eq!(slots.get(&SlotRef::Inv(0)), Some((5, MAX_STACK)));
+    assert_eq!(slots.get(&SlotRef::Inv(1)), Some((5, 20)));
+
+    //Splitting moves only what fits, and nothing goes onto a full stack
+    assert!(slots.apply(&InventoryIntent::SplitStack(SlotRef::Inv(1), SlotRef::Inv(2), 10)).is_ok());
+    assert_eq!(slots.get(&SlotRef::Inv(2)), Some((5, MAX_STACK)));
+    assert_eq!(slots.get(&SlotRef::Inv(1)), Some((5, 17)));
+    assert_eq!(
+        slots.apply(&InventoryIntent::SplitStack(SlotRef::Inv(1), SlotRef::Inv(0), 1)),
+        Err(TransactionRejection::CantSplit)
+    );
+
+    //Added items skip the full stacks
+    assert_eq!(inv.add(5, MAX_STACK - 17), Some(1));
+    assert_eq!(inv.add(5, 1), Some(3));
+}
+
+#[test]
+fn test_crafting_table_reach() {
+    let table = IVec3::new(4, 40, 4);
+    let near = Some(Vec3::new(4.5, 41.5, 6.0));
+
+    assert!(can_use_crafting_table(near, &table, 31));
+    assert!(!can_use_crafting_table(None, &table, 31));
+    assert!(!can_use_crafting_table(Some(Vec3::new(4.5, 41.5, 40.0)), &table, 31));
+    //Broken since, or a chest
+    assert!(!can_use_crafting_table(near, &table, 0));
+    assert!(!can_use_crafting_table(near, &table, 21));
+}